@@ -0,0 +1,292 @@
+use crate::combat;
+use crate::models;
+use crate::pathfinding;
+
+/// Plays every faction's turn after the player's, until it is the player's
+/// turn again, returning the attacks made along the way.
+///
+/// Stops early if the game ends, and gives up after one round in case the
+/// player has no units to hand back to.
+pub fn play_computer_turns(game: &mut models::Game) -> Vec<models::Attack> {
+    let mut attacks = Vec::new();
+    for _ in models::Faction::all_values() {
+        game.check_result();
+        if game.active_faction == models::Faction::Player || game.result.is_some() {
+            break;
+        }
+        attacks.extend(take_turn(game));
+        game.end_turn();
+    }
+    game.check_result();
+    attacks
+}
+
+/// Moves and attacks with each of the active faction's units in turn,
+/// following each unit's behavior.
+pub fn take_turn(game: &mut models::Game) -> Vec<models::Attack> {
+    let unit_ids: Vec<u32> = game
+        .units
+        .values()
+        .filter(|unit| unit.faction == game.active_faction)
+        .map(|unit| unit.id)
+        .collect();
+
+    let mut attacks = Vec::new();
+    for unit_id in unit_ids.into_iter() {
+        match take_unit_turn(game, unit_id) {
+            Some(attack) => attacks.push(attack),
+            None => (),
+        };
+    }
+    attacks
+}
+
+fn take_unit_turn(game: &mut models::Game, unit_id: u32) -> Option<models::Attack> {
+    // Units defeated earlier in the turn are gone.
+    let unit = game.units.get(&unit_id)?.clone();
+
+    match unit.behavior {
+        models::Behavior::Guard => (),
+        _ => {
+            match destination(game, &unit) {
+                Some(position) if position != unit.position => {
+                    let _ = game.move_unit(unit.id, position);
+                }
+                _ => (),
+            };
+        }
+    };
+
+    let unit = game.units.get(&unit_id)?.clone();
+    let target = weakest_target_from(game, &unit, unit.position)?;
+    game.attack_unit(unit.id, target).ok()
+}
+
+/// Where `unit` should move to this turn, if anywhere: the cheapest tile to
+/// attack from, if it can get there this turn, or (for aggressive units) as
+/// far as it can get on the way to it.
+fn destination(game: &models::Game, unit: &models::Unit) -> Option<(u32, u32)> {
+    let paths = pathfinding::paths_from(&game.map, unit.position, &|position| {
+        game.blocks(unit, position)
+    });
+    let (&goal, &cost) = paths
+        .costs
+        .iter()
+        .filter(|(&position, _)| weakest_target_from(game, unit, position).is_some())
+        .min_by_key(|(&position, &cost)| (cost, position))?;
+
+    let movement = unit.class.movement();
+    if cost <= movement {
+        return Some(goal);
+    }
+    match unit.behavior {
+        models::Behavior::Aggressive => paths
+            .path_to(goal)?
+            .into_iter()
+            .take_while(|position| paths.costs[position] <= movement)
+            .last(),
+        _ => None,
+    }
+}
+
+/// The unit `unit` is hostile to with the least hp that it could attack from
+/// `position`.
+fn weakest_target_from(
+    game: &models::Game,
+    unit: &models::Unit,
+    position: (u32, u32),
+) -> Option<u32> {
+    game.units
+        .values()
+        .filter(|other| unit.faction.is_hostile_to(&other.faction))
+        .filter(|other| combat::distance(position, other.position) <= unit.class.range())
+        .min_by_key(|other| (other.hp, other.id))
+        .map(|other| other.id)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+    use crate::rng::Rng;
+
+    fn unit(
+        id: u32,
+        position: (u32, u32),
+        faction: models::Faction,
+        behavior: models::Behavior,
+    ) -> models::Unit {
+        models::Unit {
+            id: id,
+            name: format!("Knight {}", id),
+            class: models::Character::Knight,
+            hp: models::Character::Knight.max_hp(),
+            position: position,
+            faction: faction,
+            behavior: behavior,
+            moved: false,
+            acted: false,
+        }
+    }
+
+    /// A game on a blank 5x5 grass map with `units`, on `active_faction`'s
+    /// turn.
+    fn game(active_faction: models::Faction, units: Vec<models::Unit>) -> models::Game {
+        models::Game {
+            id: 1,
+            map: models::Map {
+                id: 1,
+                default_terrain: models::Terrain::Grass,
+                specified_terrain: BTreeMap::new(),
+                hint_max_x: 5,
+                hint_max_y: 5,
+                cursor: (0, 0),
+                name: "".to_string(),
+                anchor: None,
+            },
+            units: units.into_iter().map(|unit| (unit.id, unit)).collect(),
+            current_selection: (0, 0),
+            selected_unit: None,
+            turn: 1,
+            active_faction: active_faction,
+            rng: Rng { seed: 1, draws: 0 },
+            objective: models::Objective::Rout,
+            result: None,
+            version: 0,
+        }
+    }
+
+    #[test]
+    fn aggressive_units_advance_on_targets_out_of_reach() {
+        let mut game = game(
+            models::Faction::Enemy,
+            vec![
+                unit(
+                    1,
+                    (0, 0),
+                    models::Faction::Player,
+                    models::Behavior::Aggressive,
+                ),
+                unit(
+                    2,
+                    (4, 4),
+                    models::Faction::Enemy,
+                    models::Behavior::Aggressive,
+                ),
+            ],
+        );
+
+        assert!(take_turn(&mut game).is_empty());
+        // A knight moves 4 tiles of grass, all of them towards the player.
+        let position = game.units[&2].position;
+        assert_eq!(combat::distance((4, 4), position), 4);
+        assert_eq!(combat::distance((0, 0), position), 4);
+    }
+
+    #[test]
+    fn defensive_units_hold_still_with_nothing_in_reach() {
+        let mut game = game(
+            models::Faction::Enemy,
+            vec![
+                unit(
+                    1,
+                    (0, 0),
+                    models::Faction::Player,
+                    models::Behavior::Aggressive,
+                ),
+                unit(
+                    2,
+                    (4, 4),
+                    models::Faction::Enemy,
+                    models::Behavior::Defensive,
+                ),
+            ],
+        );
+
+        assert!(take_turn(&mut game).is_empty());
+        assert_eq!(game.units[&2].position, (4, 4));
+    }
+
+    #[test]
+    fn guards_never_move() {
+        // The player is close enough to walk up to, but not to attack.
+        let mut game = game(
+            models::Faction::Enemy,
+            vec![
+                unit(
+                    1,
+                    (2, 4),
+                    models::Faction::Player,
+                    models::Behavior::Aggressive,
+                ),
+                unit(2, (4, 4), models::Faction::Enemy, models::Behavior::Guard),
+            ],
+        );
+
+        assert!(take_turn(&mut game).is_empty());
+        assert_eq!(game.units[&2].position, (4, 4));
+        assert_eq!(game.units[&1].hp, models::Character::Knight.max_hp());
+    }
+
+    #[test]
+    fn neutral_units_never_attack() {
+        let mut game = game(
+            models::Faction::Neutral,
+            vec![
+                unit(
+                    1,
+                    (0, 0),
+                    models::Faction::Player,
+                    models::Behavior::Aggressive,
+                ),
+                unit(
+                    2,
+                    (1, 0),
+                    models::Faction::Neutral,
+                    models::Behavior::Aggressive,
+                ),
+                unit(
+                    3,
+                    (0, 1),
+                    models::Faction::Enemy,
+                    models::Behavior::Aggressive,
+                ),
+            ],
+        );
+
+        assert!(take_turn(&mut game).is_empty());
+        assert_eq!(game.units[&1].hp, models::Character::Knight.max_hp());
+        assert_eq!(game.units[&3].hp, models::Character::Knight.max_hp());
+        assert_eq!(game.units[&2].position, (1, 0));
+    }
+
+    #[test]
+    fn computer_turns_hand_back_to_the_player() {
+        let mut game = game(
+            models::Faction::Player,
+            vec![
+                unit(
+                    1,
+                    (0, 0),
+                    models::Faction::Player,
+                    models::Behavior::Aggressive,
+                ),
+                unit(
+                    2,
+                    (4, 4),
+                    models::Faction::Enemy,
+                    models::Behavior::Defensive,
+                ),
+                unit(3, (4, 0), models::Faction::Neutral, models::Behavior::Guard),
+            ],
+        );
+        game.end_turn();
+        assert!(game.active_faction == models::Faction::Enemy);
+
+        play_computer_turns(&mut game);
+        assert!(game.active_faction == models::Faction::Player);
+        assert_eq!(game.turn, 2);
+        assert!(game.result.is_none());
+    }
+}
@@ -0,0 +1,187 @@
+use crate::models;
+use crate::rng::Rng;
+
+/// How often an attack lands before the defender's terrain is considered.
+const BASE_HIT_CHANCE: u32 = 90;
+/// How often a landed attack does double damage.
+const CRITICAL_CHANCE: u32 = 10;
+
+/// What happened when one unit attacked another.
+pub struct Outcome {
+    pub hit: bool,
+    pub critical: bool,
+    pub damage: u32,
+}
+
+/// Rolls `attacker`'s attack on `defender`, standing on `terrain`, and
+/// takes the damage off the defender's hp.
+///
+/// Draws twice from `rng` whether or not the attack lands, so the draws
+/// that follow do not depend on the outcome.
+pub fn attack(
+    attacker: &models::Unit,
+    defender: &mut models::Unit,
+    terrain: &models::Terrain,
+    rng: &mut Rng,
+) -> Outcome {
+    let hit = rng.chance(BASE_HIT_CHANCE.saturating_sub(terrain.avoid()));
+    let critical = rng.chance(CRITICAL_CHANCE);
+
+    let damage = if hit {
+        let base = std::cmp::max(
+            1,
+            attacker
+                .class
+                .attack()
+                .saturating_sub(defender.class.defense() + terrain.defense_bonus()),
+        );
+        if critical {
+            base * 2
+        } else {
+            base
+        }
+    } else {
+        0
+    };
+    defender.hp = defender.hp.saturating_sub(damage);
+
+    Outcome {
+        hit: hit,
+        critical: hit && critical,
+        damage: damage,
+    }
+}
+
+/// Steps left, right, up and down between two positions.
+pub fn distance(a: (u32, u32), b: (u32, u32)) -> u32 {
+    let dx = std::cmp::max(a.0, b.0) - std::cmp::min(a.0, b.0);
+    let dy = std::cmp::max(a.1, b.1) - std::cmp::min(a.1, b.1);
+    dx + dy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(id: u32, class: models::Character) -> models::Unit {
+        models::Unit {
+            id: id,
+            name: class.display_string(),
+            hp: class.max_hp(),
+            class: class,
+            position: (0, 0),
+            faction: models::Faction::Player,
+            behavior: models::Behavior::Aggressive,
+            moved: false,
+            acted: false,
+        }
+    }
+
+    /// `attacker` attacking a fresh `defender` on `terrain`, with the first
+    /// two draws from `seed`. Returns the outcome and the defender's hp.
+    fn attack_from_seed(
+        seed: u32,
+        attacker: models::Character,
+        defender: models::Character,
+        terrain: models::Terrain,
+    ) -> (Outcome, u32) {
+        let mut rng = Rng {
+            seed: seed,
+            draws: 0,
+        };
+        let mut defender = unit(2, defender);
+        let outcome = attack(&unit(1, attacker), &mut defender, &terrain, &mut rng);
+        assert_eq!(rng.draws, 2);
+        (outcome, defender.hp)
+    }
+
+    #[test]
+    fn a_hit_does_attack_less_defense() {
+        // Seed 1 draws 65 (a hit) and 19 (not critical).
+        let (outcome, hp) = attack_from_seed(
+            1,
+            models::Character::Mage,
+            models::Character::Knight,
+            models::Terrain::Grass,
+        );
+        assert!(outcome.hit);
+        assert!(!outcome.critical);
+        assert_eq!(outcome.damage, 4);
+        assert_eq!(hp, 16);
+    }
+
+    #[test]
+    fn a_miss_does_no_damage() {
+        // Seed 6 draws 92, over the 90% chance of hitting on grass.
+        let (outcome, hp) = attack_from_seed(
+            6,
+            models::Character::Mage,
+            models::Character::Knight,
+            models::Terrain::Grass,
+        );
+        assert!(!outcome.hit);
+        assert!(!outcome.critical);
+        assert_eq!(outcome.damage, 0);
+        assert_eq!(hp, 20);
+    }
+
+    #[test]
+    fn a_critical_hit_doubles_the_damage() {
+        // Seed 0 draws 35 (a hit) and 0 (critical).
+        let (outcome, hp) = attack_from_seed(
+            0,
+            models::Character::Mage,
+            models::Character::Knight,
+            models::Terrain::Grass,
+        );
+        assert!(outcome.hit);
+        assert!(outcome.critical);
+        assert_eq!(outcome.damage, 8);
+        assert_eq!(hp, 12);
+    }
+
+    #[test]
+    fn terrain_avoid_turns_a_hit_into_a_miss() {
+        // Seed 7 draws 87 and then 4: a critical hit on grass, but over the
+        // 70% chance of hitting on rock, where it is no critical either.
+        let (outcome, _) = attack_from_seed(
+            7,
+            models::Character::Mage,
+            models::Character::Knight,
+            models::Terrain::Grass,
+        );
+        assert!(outcome.hit && outcome.critical);
+
+        let (outcome, hp) = attack_from_seed(
+            7,
+            models::Character::Mage,
+            models::Character::Knight,
+            models::Terrain::Rock,
+        );
+        assert!(!outcome.hit);
+        assert!(!outcome.critical);
+        assert_eq!(hp, 20);
+    }
+
+    #[test]
+    fn a_hit_always_does_at_least_1_damage() {
+        // The knight's 7 attack against another knight's 5 defense and
+        // rock's 3 bonus.
+        let (outcome, hp) = attack_from_seed(
+            1,
+            models::Character::Knight,
+            models::Character::Knight,
+            models::Terrain::Rock,
+        );
+        assert!(outcome.hit);
+        assert_eq!(outcome.damage, 1);
+        assert_eq!(hp, 19);
+    }
+
+    #[test]
+    fn measures_distance_in_steps() {
+        assert_eq!(distance((0, 0), (0, 0)), 0);
+        assert_eq!(distance((1, 2), (3, 1)), 3);
+        assert_eq!(distance((3, 1), (1, 2)), 3);
+    }
+}
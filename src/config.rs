@@ -0,0 +1,139 @@
+use std::collections::BTreeMap;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::db;
+
+const DEFAULT_CONFIG_FILE_NAME: &'static str = "rpq.toml";
+
+const USAGE: &'static str = "usage: rpq [--config FILE] [--data-dir DIR] [--images-dir DIR] \
+[--bind ADDRESS] [--port PORT] [--storage csv|sqlite|memory]
+
+Each flag can also be set with an environment variable (RPQ_CONFIG, RPQ_DATA_DIR,
+RPQ_IMAGES_DIR, RPQ_BIND, RPQ_PORT, RPQ_STORAGE) or, except for the config file
+itself, in the config file (data_dir, images_dir, bind_address, port, storage).
+Flags beat environment variables, which beat the config file.";
+
+/// Where this server keeps its data and how it listens.
+pub struct Config {
+    pub data_dir: PathBuf,
+    pub images_dir: PathBuf,
+    pub bind_address: IpAddr,
+    pub port: u16,
+    pub storage: db::Backend,
+}
+
+/// Every setting of the config file is optional.
+#[derive(Deserialize, Default)]
+struct ConfigFile {
+    data_dir: Option<String>,
+    images_dir: Option<String>,
+    bind_address: Option<String>,
+    port: Option<u16>,
+    storage: Option<String>,
+}
+
+impl Config {
+    /// Gathers the settings from the command line flags, `RPQ_*` environment
+    /// variables and the config file (`rpq.toml` unless one is named), in
+    /// that order of priority, falling back to serving `db/` and `images/` on
+    /// 127.0.0.1:3000 with csv storage.
+    pub fn load() -> Result<Self, String> {
+        let flags = parse_flags(std::env::args().skip(1))?;
+
+        let file = match setting(&flags, "config", "RPQ_CONFIG", None) {
+            Some(path) => read_config_file(&path)?,
+            None => {
+                if std::path::Path::new(DEFAULT_CONFIG_FILE_NAME).exists() {
+                    read_config_file(DEFAULT_CONFIG_FILE_NAME)?
+                } else {
+                    ConfigFile::default()
+                }
+            }
+        };
+
+        Ok(Config {
+            data_dir: PathBuf::from(
+                setting(&flags, "data-dir", "RPQ_DATA_DIR", file.data_dir).unwrap_or("db".into()),
+            ),
+            images_dir: PathBuf::from(
+                setting(&flags, "images-dir", "RPQ_IMAGES_DIR", file.images_dir)
+                    .unwrap_or("images".into()),
+            ),
+            bind_address: match setting(&flags, "bind", "RPQ_BIND", file.bind_address) {
+                Some(s) => s
+                    .parse()
+                    .map_err(|_| format!("bind address must be an IP address, not {}", s))?,
+                None => IpAddr::from([127, 0, 0, 1]),
+            },
+            port: match setting(&flags, "port", "RPQ_PORT", file.port.map(|p| p.to_string())) {
+                Some(s) => s
+                    .parse()
+                    .map_err(|_| format!("port must be a number up to 65535, not {}", s))?,
+                None => 3000,
+            },
+            storage: match setting(&flags, "storage", "RPQ_STORAGE", file.storage) {
+                Some(s) => db::Backend::parse(&s).ok_or(format!(
+                    "storage must be one of csv, sqlite, memory, not {}",
+                    s
+                ))?,
+                None => db::Backend::Csv,
+            },
+        })
+    }
+
+    pub fn socket_addr(&self) -> SocketAddr {
+        SocketAddr::new(self.bind_address, self.port)
+    }
+}
+
+fn setting(
+    flags: &BTreeMap<String, String>,
+    flag: &str,
+    env_var: &str,
+    from_file: Option<String>,
+) -> Option<String> {
+    flags
+        .get(flag)
+        .cloned()
+        .or(std::env::var(env_var).ok())
+        .or(from_file)
+}
+
+/// Accepts `--name value` and `--name=value`.
+fn parse_flags<I: Iterator<Item = String>>(
+    mut args: I,
+) -> Result<BTreeMap<String, String>, String> {
+    let mut flags = BTreeMap::new();
+    while let Some(arg) = args.next() {
+        let name = match arg.strip_prefix("--") {
+            Some(name) => name,
+            None => return Err(format!("unexpected argument {}\n\n{}", arg, USAGE)),
+        };
+        if name == "help" {
+            return Err(USAGE.into());
+        }
+        let (name, value) = match name.split_once('=') {
+            Some((name, value)) => (name.to_string(), value.to_string()),
+            None => match args.next() {
+                Some(value) => (name.to_string(), value),
+                None => return Err(format!("--{} needs a value\n\n{}", name, USAGE)),
+            },
+        };
+        match name.as_str() {
+            "config" | "data-dir" | "images-dir" | "bind" | "port" | "storage" => {
+                flags.insert(name, value);
+            }
+            _ => return Err(format!("unknown flag --{}\n\n{}", name, USAGE)),
+        };
+    }
+    Ok(flags)
+}
+
+fn read_config_file(path: &str) -> Result<ConfigFile, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|e| format!("could not read config file {}: {}", path, e))?;
+    toml::from_str(&contents).map_err(|e| format!("could not parse config file {}: {}", path, e))
+}
@@ -1,7 +1,238 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::Path;
+
+/// Everything that can go wrong reading or writing the tables.
+///
+/// Tables and files are named without their directory, so the message is
+/// safe to show to whoever made the request.
 #[derive(Debug)]
 pub enum DBError {
-    FindingTable(String),
-    ParsingRecord(String),
-    FindingRecord(String),
-    Internal(std::io::Error),
+    /// A table's file could not be opened.
+    FindingTable {
+        table: String,
+        source: io::Error,
+    },
+    /// A stored row does not fit its record type.
+    ParsingRecord {
+        table: String,
+        /// Counting data rows from 1, when known.
+        row: Option<u64>,
+        field: Option<String>,
+        cause: Cause,
+    },
+    /// A record could not be written out.
+    WritingRecord {
+        table: String,
+        /// Counting data rows from 1, when known.
+        row: Option<u64>,
+        cause: Cause,
+    },
+    /// No row of `table` has the id that was asked for.
+    FindingRecord {
+        table: String,
+    },
+    /// The row was changed by someone else since the caller read it.
+    Conflict {
+        table: String,
+    },
+    /// The tables were written by a newer build than this one.
+    UnsupportedSchemaVersion {
+        found: u32,
+        supported: u32,
+    },
+    /// Tables written by an older build could not be brought up to
+    /// `to_version` without losing rows; they are left as they were.
+    Migrating {
+        to_version: u32,
+        reason: String,
+    },
+    /// A write was turned away for storing something `table` cannot hold,
+    /// like a map 0 tiles wide.
+    InvalidValue {
+        table: String,
+        reason: String,
+    },
+    /// Reading, writing or locking `file` (a table, the journal or the lock)
+    /// failed.
+    Io {
+        file: String,
+        source: io::Error,
+    },
+    Sqlite(rusqlite::Error),
+}
+
+/// Why a row could not be read or written.
+#[derive(Debug)]
+pub enum Cause {
+    Csv(csv::Error),
+    /// A stored value this build does not know, like an unrecognised terrain.
+    UnknownValue(String),
+}
+
+impl DBError {
+    pub fn finding_table(file_name: &str, source: io::Error) -> Self {
+        DBError::FindingTable {
+            table: display_name(file_name),
+            source: source,
+        }
+    }
+
+    pub fn io(file_name: &str, source: io::Error) -> Self {
+        DBError::Io {
+            file: display_name(file_name),
+            source: source,
+        }
+    }
+
+    pub fn finding_record(table: &str) -> Self {
+        DBError::FindingRecord {
+            table: table.to_string(),
+        }
+    }
+
+    pub fn conflict(table: &str) -> Self {
+        DBError::Conflict {
+            table: table.to_string(),
+        }
+    }
+
+    pub fn invalid_value(table: &str, reason: String) -> Self {
+        DBError::InvalidValue {
+            table: table.to_string(),
+            reason: reason,
+        }
+    }
+}
+
+impl fmt::Display for DBError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DBError::FindingTable { table, source } => {
+                write!(f, "could not open table {}: {}", table, source)
+            }
+            DBError::ParsingRecord {
+                table,
+                row,
+                field,
+                cause,
+            } => {
+                write!(f, "could not read ")?;
+                write_row(f, table, row)?;
+                match field {
+                    Some(field) => write!(f, ", field {}", field)?,
+                    None => (),
+                };
+                write!(f, ": {}", cause)
+            }
+            DBError::WritingRecord { table, row, cause } => {
+                write!(f, "could not write ")?;
+                write_row(f, table, row)?;
+                write!(f, ": {}", cause)
+            }
+            DBError::FindingRecord { table } => write!(f, "no such record in {}", table),
+            DBError::Conflict { table } => {
+                write!(f, "the {} record was changed by someone else", table)
+            }
+            DBError::UnsupportedSchemaVersion { found, supported } => write!(
+                f,
+                "tables are at schema version {}, but this build only supports up to {}",
+                found, supported
+            ),
+            DBError::Migrating { to_version, reason } => write!(
+                f,
+                "could not upgrade the tables to schema version {}: {}",
+                to_version, reason
+            ),
+            DBError::InvalidValue { table, reason } => {
+                write!(f, "could not write to {}: {}", table, reason)
+            }
+            DBError::Io { file, source } => write!(f, "could not access {}: {}", file, source),
+            DBError::Sqlite(e) => write!(f, "sqlite: {}", e),
+        }
+    }
+}
+
+impl Error for DBError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DBError::FindingTable { source, .. } => Some(source),
+            DBError::ParsingRecord { cause, .. } => cause.source(),
+            DBError::WritingRecord { cause, .. } => cause.source(),
+            DBError::FindingRecord { .. } => None,
+            DBError::Conflict { .. } => None,
+            DBError::UnsupportedSchemaVersion { .. } => None,
+            DBError::Migrating { .. } => None,
+            DBError::InvalidValue { .. } => None,
+            DBError::Io { source, .. } => Some(source),
+            DBError::Sqlite(e) => Some(e),
+        }
+    }
+}
+
+impl Cause {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Cause::Csv(e) => Some(e),
+            Cause::UnknownValue(_) => None,
+        }
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Cause::Csv(e) => match e.kind() {
+                // The row and field are already given by the `DBError`.
+                csv::ErrorKind::Deserialize { err, .. } => write!(f, "{}", err.kind()),
+                csv::ErrorKind::Serialize(message) => write!(f, "{}", message),
+                _ => write!(f, "{}", e),
+            },
+            Cause::UnknownValue(value) => write!(f, "unknown value {:?}", value),
+        }
+    }
+}
+
+fn write_row(f: &mut fmt::Formatter, table: &str, row: &Option<u64>) -> fmt::Result {
+    match row {
+        Some(row) => write!(f, "row {} of {}", row, table),
+        None => write!(f, "a row of {}", table),
+    }
+}
+
+/// The last component of a path, so errors do not give away where the data
+/// directory is.
+pub fn display_name(file_name: &str) -> String {
+    match Path::new(file_name).file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => file_name.to_string(),
+    }
+}
+
+/// An empty directory for a test's tables, removed again once dropped.
+#[cfg(test)]
+pub struct TempDir {
+    path: std::path::PathBuf,
+}
+
+#[cfg(test)]
+impl TempDir {
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("rpq-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir { path: path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+#[cfg(test)]
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
 }
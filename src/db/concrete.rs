@@ -1,107 +1,186 @@
 use std::collections::BTreeMap;
-
-use serde::{Deserialize, Serialize};
+use std::path::Path;
 
 use crate::models;
+use crate::painting;
+use crate::rng::Rng;
 
 use super::common::DBError;
-use super::engine;
-
-const GAME_DB_FILE_NAME: &'static str = "db/game.csv";
-const MAP_DB_FILE_NAME: &'static str = "db/map.csv";
-const TILES_DB_FILE_NAME: &'static str = "db/tiles.csv";
-const CHARACTER_DB_FILE_NAME: &'static str = "db/characters.csv";
-
-const ALL_DB_FILE_NAMES: &'static [&'static str] = &[
-    GAME_DB_FILE_NAME,
-    MAP_DB_FILE_NAME,
-    TILES_DB_FILE_NAME,
-    CHARACTER_DB_FILE_NAME,
-];
-
-#[derive(Serialize, Deserialize, Clone)]
-struct DBGame {
-    id: u32,
-    map_id: u32,
-    cursor_x: u32,
-    cursor_y: u32,
-}
+use super::csv_storage::CsvStorage;
+use super::memory_storage::MemoryStorage;
+use super::records::{DBCharacter, DBEdit, DBEvent, DBGame, DBMap, DBStartingUnit, DBTileLine};
+use super::sqlite_storage::SqliteStorage;
+use super::storage::Storage;
 
-#[derive(Serialize, Deserialize, Clone)]
-struct DBMap {
-    id: u32,
-    default_terrain: models::Terrain,
-    hint_max_x: u32,
-    hint_max_y: u32,
+/// Which `Storage` implementation a `DB` is opened over.
+#[derive(Clone, Copy, Debug)]
+pub enum Backend {
+    Csv,
+    Sqlite,
+    Memory,
 }
 
-#[derive(Serialize, Deserialize, Clone)]
-struct DBTileLine {
-    id: u32,
-    map_id: u32,
-    terrain: models::Terrain,
-    x: u32,
-    y: u32,
-}
-
-#[derive(Serialize, Deserialize, Clone)]
-struct DBCharacter {
-    id: u32,
-    game_id: u32,
-    character: models::Character,
-    x: u32,
-    y: u32,
+impl Backend {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "csv" => Some(Backend::Csv),
+            "sqlite" => Some(Backend::Sqlite),
+            "memory" => Some(Backend::Memory),
+            _ => None,
+        }
+    }
 }
 
 pub struct DB {
-    engine: engine::Engine,
+    storage: Box<dyn Storage + Send + Sync>,
 }
 
 impl DB {
-    pub fn new() -> Self {
-        DB {
-            engine: engine::Engine::new(ALL_DB_FILE_NAMES),
-        }
+    /// Opens the tables kept in `data_dir` by `backend`.
+    ///
+    /// Meant to be opened once and shared: the memory backend starts out
+    /// empty and the csv backend keeps parsed tables around between reads.
+    pub fn new(backend: Backend, data_dir: &Path) -> Result<Self, DBError> {
+        Ok(DB::from_storage(match backend {
+            Backend::Csv => Box::new(CsvStorage::new(data_dir)?),
+            Backend::Sqlite => Box::new(SqliteStorage::new(data_dir)?),
+            Backend::Memory => Box::new(MemoryStorage::new()),
+        }))
+    }
+
+    pub fn from_storage(storage: Box<dyn Storage + Send + Sync>) -> Self {
+        DB { storage: storage }
     }
 
     pub fn get_games(&self) -> Result<Vec<models::Game>, DBError> {
         Ok(self
             .read_db_games()?
             .into_iter()
-            .map(|x| {
-                let map = self.get_db_map(x.map_id)?;
-                let tiles = self.read_db_tile_lines_for_map_id(map.id)?;
-                let characters = self.read_db_characters_for_game_id(x.id)?;
-                Ok(game_model_from_db(x, map, tiles, characters))
-            })
+            .map(|x| self.game_from_db(x))
             .collect::<Result<Vec<models::Game>, DBError>>()?)
     }
 
+    /// Loads one game, reading only its own map, tiles and characters.
     pub fn get_game(&self, game_id: u32) -> Result<models::Game, DBError> {
-        get_single_result(self.get_games()?, |game| game.id == game_id, "games")
+        self.game_from_db(get_db_game(self.storage.as_ref(), game_id)?)
+    }
+
+    fn game_from_db(&self, game: DBGame) -> Result<models::Game, DBError> {
+        let map = get_db_map(self.storage.as_ref(), game.map_id)?;
+        let tiles = self.storage.read_tile_lines_for_map_id(map.id)?;
+        let characters = self.storage.read_characters_for_game_id(game.id)?;
+        Ok(game_model_from_db(game, map, tiles, characters))
     }
 
     fn read_db_games(&self) -> Result<Vec<DBGame>, DBError> {
-        self.engine.read_db_records(GAME_DB_FILE_NAME)
+        self.storage.read_games()
+    }
+
+    /// Loads the game as it was when it was added, before anything in its
+    /// log happened: its map with no tiles set and no characters.
+    pub fn get_game_start(&self, game_id: u32) -> Result<models::Game, DBError> {
+        let game = get_db_game(self.storage.as_ref(), game_id)?;
+        let map = get_db_map(self.storage.as_ref(), game.map_id)?;
+        Ok(game_model_from_db(
+            DBGame {
+                id: game.id,
+                ..new_db_game(game.map_id, game.rng_seed)
+            },
+            map,
+            Vec::new(),
+            Vec::new(),
+        ))
+    }
+
+    /// Everything done to the game since it was added, oldest first.
+    pub fn get_game_log(&self, game_id: u32) -> Result<Vec<models::LogEntry>, DBError> {
+        Ok(self
+            .storage
+            .read_events_for_game_id(game_id)?
+            .into_iter()
+            .map(|record| models::LogEntry {
+                turn: record.turn,
+                event: record.event,
+            })
+            .collect())
     }
 
     pub fn add_game(&self) -> Result<(), DBError> {
-        let map = self.add_db_map()?;
+        self.storage.transaction(&mut |tx| {
+            let map = add_db_map(tx)?;
 
-        let mut records = self.read_db_games()?;
-        let max_id = records
-            .iter()
-            .fold(0, |acc, game| std::cmp::max(acc, game.id));
+            tx.insert_game(new_db_game(map.id, Rng::fresh_seed()))?;
 
-        records.push(DBGame {
-            id: max_id + 1,
-            map_id: map.id,
-            cursor_x: 0,
-            cursor_y: 0,
-        });
+            Ok(())
+        })
+    }
+
+    /// Adds a game on a copy of the map's terrain, with a character for each
+    /// of the map's starting units. The copy is the game's own, so editing
+    /// either one later leaves the other be.
+    pub fn add_game_from_map(&self, map_id: u32) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let template = get_db_map(tx, map_id)?;
+            let map = tx.insert_map(DBMap {
+                cursor_x: 0,
+                cursor_y: 0,
+                anchor_x: None,
+                anchor_y: None,
+                ..template.clone()
+            })?;
+            let game = tx.insert_game(new_db_game(map.id, Rng::fresh_seed()))?;
+
+            for tile in tx.read_tile_lines_for_map_id(template.id)?.into_iter() {
+                log_db_start_event(
+                    tx,
+                    game.id,
+                    models::Event::SetTerrain {
+                        position: (tile.x, tile.y),
+                        terrain: tile.terrain.clone(),
+                    },
+                )?;
+                tx.set_tile_line(DBTileLine {
+                    map_id: map.id,
+                    ..tile
+                })?;
+            }
+
+            let mut names: Vec<String> = Vec::new();
+            for unit in tx.read_starting_units_for_map_id(template.id)?.into_iter() {
+                let name = unit.character.next_unit_name(
+                    &names
+                        .iter()
+                        .map(|name| name.as_str())
+                        .collect::<Vec<&str>>(),
+                );
+                names.push(name.clone());
+                tx.set_character(DBCharacter {
+                    id: 0,
+                    game_id: game.id,
+                    character: unit.character.clone(),
+                    x: unit.x,
+                    y: unit.y,
+                    name: name,
+                    faction: unit.faction,
+                    behavior: unit.behavior,
+                    hp: unit.character.max_hp(),
+                    moved: false,
+                    acted: false,
+                })?;
+            }
+            // Logged once stored, so replays give them the same ids.
+            for record in tx.read_characters_for_game_id(game.id)?.into_iter() {
+                log_db_start_event(
+                    tx,
+                    game.id,
+                    models::Event::RestoreCharacter {
+                        unit: unit_model_from_db(record),
+                    },
+                )?;
+            }
 
-        self.engine
-            .write_replace_records(GAME_DB_FILE_NAME, records)
+            Ok(())
+        })
     }
 
     pub fn get_maps(&self) -> Result<Vec<models::Map>, DBError> {
@@ -109,132 +188,465 @@ impl DB {
             .read_db_maps()?
             .into_iter()
             .map(|map| {
-                let tiles = self.read_db_tile_lines_for_map_id(map.id)?;
+                let tiles = self.storage.read_tile_lines_for_map_id(map.id)?;
                 Ok(map_model_from_db(map, tiles))
             })
             .collect::<Result<Vec<models::Map>, DBError>>()?)
     }
 
+    /// Loads one map, reading only its own tiles.
     pub fn get_map(&self, map_id: u32) -> Result<models::Map, DBError> {
-        get_single_result(self.get_maps()?, |record| record.id == map_id, "maps")
+        let map = get_db_map(self.storage.as_ref(), map_id)?;
+        let tiles = self.storage.read_tile_lines_for_map_id(map.id)?;
+        Ok(map_model_from_db(map, tiles))
     }
 
-    fn add_db_map(&self) -> Result<DBMap, DBError> {
-        let mut records = self.read_db_maps()?;
-        let max_id = records
-            .iter()
-            .fold(0, |acc, record| std::cmp::max(acc, record.id));
+    /// The characters the map starts its games with.
+    pub fn get_map_starting_units(
+        &self,
+        map_id: u32,
+    ) -> Result<Vec<models::StartingUnit>, DBError> {
+        Ok(self
+            .storage
+            .read_starting_units_for_map_id(map_id)?
+            .into_iter()
+            .map(starting_unit_model_from_db)
+            .collect())
+    }
 
-        let new_record = DBMap {
-            id: max_id + 1,
-            default_terrain: models::Terrain::Grass,
-            hint_max_x: 15,
-            hint_max_y: 12,
-        };
+    fn read_db_maps(&self) -> Result<Vec<DBMap>, DBError> {
+        self.storage.read_maps()
+    }
 
-        records.push(new_record.clone());
+    /// Adds a blank map to be edited on its own.
+    pub fn add_map(&self) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            add_db_map(tx)?;
 
-        self.engine
-            .write_replace_records(MAP_DB_FILE_NAME, records)?;
+            Ok(())
+        })
+    }
 
-        Ok(new_record)
+    /// The game played on the map, if any. Each game is added with a map of
+    /// its own, which is edited through the game so its log keeps up.
+    pub fn get_map_game_id(&self, map_id: u32) -> Result<Option<u32>, DBError> {
+        Ok(self
+            .read_db_games()?
+            .into_iter()
+            .find(|game| game.map_id == map_id)
+            .map(|game| game.id))
     }
 
-    fn get_db_map(&self, map_id: u32) -> Result<DBMap, DBError> {
-        get_single_result(self.read_db_maps()?, |record| record.id == map_id, "maps")
+    /// Moves the map editor's cursor.
+    pub fn update_map_cursor(&self, map_id: u32, cursor: (u32, u32)) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let map = get_db_map(tx, map_id)?;
+            tx.update_map(DBMap {
+                cursor_x: cursor.0,
+                cursor_y: cursor.1,
+                ..map
+            })
+        })
     }
 
-    fn read_db_maps(&self) -> Result<Vec<DBMap>, DBError> {
-        self.engine.read_db_records(MAP_DB_FILE_NAME)
+    /// Puts the anchor brushes paint from at `anchor`, or with `None` takes
+    /// it away.
+    pub fn update_map_anchor(
+        &self,
+        map_id: u32,
+        anchor: Option<(u32, u32)>,
+    ) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let map = get_editable_db_map(tx, map_id)?;
+            update_db_map_anchor(tx, map, anchor)
+        })
     }
 
-    pub fn update_game_cursor(&self, id: u32, cursor: (u32, u32)) -> Result<(), DBError> {
-        let records = self
-            .read_db_games()?
-            .into_iter()
-            .map(|mut record| {
-                if record.id == id {
-                    record.cursor_x = cursor.0;
-                    record.cursor_y = cursor.1;
-                    record
-                } else {
-                    record
-                }
+    /// Paints `terrain` with `brush` from the map's anchor to the map
+    /// editor's cursor, writing all the tiles at once. Without an anchor
+    /// the cursor stands in for it.
+    pub fn paint_map_terrain(
+        &self,
+        map_id: u32,
+        brush: models::Brush,
+        terrain: models::Terrain,
+    ) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let map = get_editable_db_map(tx, map_id)?;
+            let tiles = tx.read_tile_lines_for_map_id(map.id)?;
+            let map = map_model_from_db(map, tiles);
+            let positions =
+                painting::tiles(&map, brush, map.anchor.unwrap_or(map.cursor), map.cursor);
+
+            tx.paint_tile_lines(
+                map.id,
+                &positions
+                    .into_iter()
+                    .map(|position| (position, Some(terrain.clone())))
+                    .collect::<Vec<_>>(),
+            )
+        })
+    }
+
+    /// Sets the terrain under the map editor's cursor.
+    pub fn update_map_terrain(&self, map_id: u32, terrain: models::Terrain) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let map = get_editable_db_map(tx, map_id)?;
+            tx.set_tile_line(DBTileLine {
+                id: 0,
+                map_id: map.id,
+                terrain: terrain.clone(),
+                x: map.cursor_x,
+                y: map.cursor_y,
             })
-            .collect();
-        self.engine
-            .write_replace_records(GAME_DB_FILE_NAME, records)
+        })
     }
 
-    fn read_db_tile_lines_for_map_id(&self, map_id: u32) -> Result<Vec<DBTileLine>, DBError> {
-        Ok(self
-            .read_db_tile_lines()?
-            .into_iter()
-            .filter(|record| record.map_id == map_id)
-            .collect())
+    /// Goes back to the map's default terrain under the map editor's cursor.
+    pub fn unset_map_terrain(&self, map_id: u32) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let map = get_editable_db_map(tx, map_id)?;
+            tx.delete_tile_line_at(map.id, map.cursor_x, map.cursor_y)
+        })
     }
 
-    fn read_db_tile_lines(&self) -> Result<Vec<DBTileLine>, DBError> {
-        self.engine.read_db_records(TILES_DB_FILE_NAME)
+    /// Starts the map's games with a `character` under the map editor's
+    /// cursor, changing the class of any starting unit already there.
+    pub fn update_map_character(
+        &self,
+        map_id: u32,
+        character: models::Character,
+    ) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let map = get_editable_db_map(tx, map_id)?;
+            match db_starting_unit_at(tx, &map)? {
+                Some(record) => tx.update_starting_unit(DBStartingUnit {
+                    character: character.clone(),
+                    ..record
+                }),
+                None => tx.set_starting_unit(DBStartingUnit {
+                    id: 0,
+                    map_id: map.id,
+                    character: character.clone(),
+                    x: map.cursor_x,
+                    y: map.cursor_y,
+                    faction: models::Faction::Player,
+                    behavior: models::Behavior::Aggressive,
+                }),
+            }
+        })
     }
 
-    fn read_db_characters_for_game_id(&self, game_id: u32) -> Result<Vec<DBCharacter>, DBError> {
-        Ok(self
-            .read_db_characters()?
-            .into_iter()
-            .filter(|record| record.game_id == game_id)
-            .collect())
+    /// Sets the faction of the starting unit under the map editor's cursor.
+    pub fn update_map_faction(&self, map_id: u32, faction: models::Faction) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let map = get_editable_db_map(tx, map_id)?;
+            match db_starting_unit_at(tx, &map)? {
+                Some(record) => tx.update_starting_unit(DBStartingUnit {
+                    faction: faction.clone(),
+                    ..record
+                }),
+                None => Err(DBError::finding_record("starting_units")),
+            }
+        })
+    }
+
+    /// Sets how the computer plays the starting unit under the map editor's
+    /// cursor.
+    pub fn update_map_behavior(
+        &self,
+        map_id: u32,
+        behavior: models::Behavior,
+    ) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let map = get_editable_db_map(tx, map_id)?;
+            match db_starting_unit_at(tx, &map)? {
+                Some(record) => tx.update_starting_unit(DBStartingUnit {
+                    behavior: behavior.clone(),
+                    ..record
+                }),
+                None => Err(DBError::finding_record("starting_units")),
+            }
+        })
     }
 
-    fn read_db_characters(&self) -> Result<Vec<DBCharacter>, DBError> {
-        self.engine.read_db_records(CHARACTER_DB_FILE_NAME)
+    /// Removes the starting unit under the map editor's cursor.
+    pub fn unset_map_character(&self, map_id: u32) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let map = get_editable_db_map(tx, map_id)?;
+            tx.delete_starting_unit_at(map.id, map.cursor_x, map.cursor_y)
+        })
     }
 
-    pub fn update_game_terrain(
+    pub fn update_map_name(&self, map_id: u32, name: String) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let map = get_editable_db_map(tx, map_id)?;
+            tx.update_map(DBMap {
+                name: name.clone(),
+                ..map
+            })
+        })
+    }
+
+    /// Sets the terrain of every tile of the map not set to something else.
+    pub fn update_map_default_terrain(
         &self,
-        game_id: u32,
+        map_id: u32,
         terrain: models::Terrain,
     ) -> Result<(), DBError> {
-        let mut records = self.read_db_tile_lines()?;
-        let max_id = records
-            .iter()
-            .fold(0, |acc, record| std::cmp::max(acc, record.id));
+        self.storage.transaction(&mut |tx| {
+            let map = get_editable_db_map(tx, map_id)?;
+            tx.update_map(DBMap {
+                default_terrain: terrain.clone(),
+                ..map
+            })
+        })
+    }
 
-        let game = self.get_game(game_id)?;
+    /// Makes the map `size` (at least 1 by 1) tiles wide and high, removing
+    /// any tiles and starting units left outside it, bringing the cursor
+    /// back inside and clearing an anchor left outside.
+    pub fn update_map_size(&self, map_id: u32, size: (u32, u32)) -> Result<(), DBError> {
+        let (width, height) = size;
+        if width == 0 || height == 0 {
+            return Err(DBError::invalid_value(
+                "maps",
+                format!("a map cannot be {} by {} tiles", width, height),
+            ));
+        }
+        self.storage.transaction(&mut |tx| {
+            let map = get_editable_db_map(tx, map_id)?;
+            let inside_anchor = match (map.anchor_x, map.anchor_y) {
+                (Some(x), Some(y)) => x < width && y < height,
+                _ => false,
+            };
 
-        let new_record = DBTileLine {
-            id: max_id + 1,
-            map_id: game.map.id,
-            terrain: terrain,
-            x: game.current_selection.0,
-            y: game.current_selection.1,
-        };
+            tx.delete_tile_lines_outside(map.id, width, height)?;
+            tx.delete_starting_units_outside(map.id, width, height)?;
 
-        records.push(new_record.clone());
+            tx.update_map(DBMap {
+                hint_max_x: width,
+                hint_max_y: height,
+                cursor_x: std::cmp::min(map.cursor_x, width - 1),
+                cursor_y: std::cmp::min(map.cursor_y, height - 1),
+                anchor_x: map.anchor_x.filter(|_| inside_anchor),
+                anchor_y: map.anchor_y.filter(|_| inside_anchor),
+                ..map
+            })
+        })
+    }
 
-        records = records
-            .into_iter()
-            .map(|record| {
-                (
-                    (record.map_id, record.x, record.y),
-                    (record.id, record.terrain),
-                )
+    /// Moves the game's cursor, as long as nothing else has changed the game
+    /// since it was read at `expected_version`.
+    pub fn update_game_cursor(
+        &self,
+        id: u32,
+        expected_version: u32,
+        cursor: (u32, u32),
+    ) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            log_db_event(tx, id, models::Event::Cursor { position: cursor })?;
+
+            update_db_game(tx, id, expected_version, &mut |record| {
+                record.cursor_x = cursor.0;
+                record.cursor_y = cursor.1;
             })
-            .collect::<BTreeMap<_, _>>()
-            .into_iter()
-            .map(|(key, value)| DBTileLine {
-                id: value.0,
-                map_id: key.0,
-                terrain: value.1,
-                x: key.1,
-                y: key.2,
+        })
+    }
+
+    /// Picks (or with `None`, drops) the unit about to be moved, as long as
+    /// the game is still at `expected_version`.
+    pub fn update_game_selected_unit(
+        &self,
+        id: u32,
+        expected_version: u32,
+        selected_unit: Option<u32>,
+    ) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let game = get_db_game(tx, id)?;
+            log_db_event(
+                tx,
+                id,
+                match selected_unit {
+                    Some(_) => models::Event::Select {
+                        position: (game.cursor_x, game.cursor_y),
+                    },
+                    None => models::Event::Deselect,
+                },
+            )?;
+
+            update_db_game(tx, id, expected_version, &mut |record| {
+                record.selected_unit_id = selected_unit;
             })
-            .collect::<Vec<DBTileLine>>();
+        })
+    }
+
+    /// Stores `unit` as moved, along with whether that ended `game`, and
+    /// clears the game's selection and edit history, as long as the game is
+    /// still at `expected_version`.
+    pub fn move_game_unit(
+        &self,
+        expected_version: u32,
+        game: &models::Game,
+        unit: &models::Unit,
+    ) -> Result<(), DBError> {
+        let game_id = game.id;
+        self.storage.transaction(&mut |tx| {
+            let from = match tx
+                .read_characters_for_game_id(game_id)?
+                .into_iter()
+                .find(|record| record.id == unit.id)
+            {
+                Some(record) => (record.x, record.y),
+                None => return Err(DBError::finding_record("characters")),
+            };
+            log_db_event(
+                tx,
+                game_id,
+                models::Event::Move {
+                    from: from,
+                    to: unit.position,
+                },
+            )?;
+
+            update_db_game(tx, game_id, expected_version, &mut |record| {
+                record.selected_unit_id = None;
+                record.result = game.result.clone();
+            })?;
+
+            tx.delete_edits_for_game_id(game_id)?;
+            update_db_units(tx, game_id, &[unit])
+        })
+    }
+
+    /// Stores both units after `attack`, removing the defender if it was
+    /// defeated, along with how far `game`'s random number generator got and
+    /// whether the attack ended it, and clears the game's selection and edit
+    /// history, as long as the game is still at `expected_version`.
+    pub fn attack_game_unit(
+        &self,
+        expected_version: u32,
+        game: &models::Game,
+        attack: &models::Attack,
+    ) -> Result<(), DBError> {
+        let game_id = game.id;
+        self.storage.transaction(&mut |tx| {
+            log_db_event(
+                tx,
+                game_id,
+                models::Event::Attack {
+                    from: attack.attacker.position,
+                    to: attack.defender.position,
+                },
+            )?;
+
+            update_db_game(tx, game_id, expected_version, &mut |record| {
+                record.selected_unit_id = None;
+                record.rng_draws = game.rng.draws;
+                record.result = game.result.clone();
+            })?;
+            tx.delete_edits_for_game_id(game_id)?;
+
+            if attack.defender.hp == 0 {
+                update_db_units(tx, game_id, &[&attack.attacker])?;
+                let (x, y) = attack.defender.position;
+                tx.delete_character_at(game_id, x, y)
+            } else {
+                update_db_units(tx, game_id, &[&attack.attacker, &attack.defender])
+            }
+        })
+    }
+
+    /// Stores the turn, active faction, random number generator, result and
+    /// units of `game` after its turn (and any computer turns after it) has ended,
+    /// removing units defeated along the way and clearing its edit history,
+    /// as long as it is still at `expected_version`.
+    pub fn end_game_turn(&self, expected_version: u32, game: &models::Game) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            log_db_event(tx, game.id, models::Event::EndTurn)?;
+
+            update_db_game(tx, game.id, expected_version, &mut |record| {
+                record.selected_unit_id = None;
+                record.turn = game.turn;
+                record.active_faction = game.active_faction.clone();
+                record.rng_draws = game.rng.draws;
+                record.result = game.result.clone();
+            })?;
+            tx.delete_edits_for_game_id(game.id)?;
+
+            for record in tx.read_characters_for_game_id(game.id)?.into_iter() {
+                if !game.units.contains_key(&record.id) {
+                    tx.delete_character_at(game.id, record.x, record.y)?;
+                }
+            }
+            update_db_units(tx, game.id, &game.units.values().collect::<Vec<_>>())
+        })
+    }
 
-        self.engine
-            .write_replace_records(TILES_DB_FILE_NAME, records)?;
+    /// Sets what the player has to do to win the game, which starts over
+    /// deciding whether it has been won or lost.
+    pub fn update_game_objective(
+        &self,
+        game_id: u32,
+        objective: models::Objective,
+    ) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let game = get_db_game(tx, game_id)?;
+            log_db_event(
+                tx,
+                game_id,
+                models::Event::SetObjective {
+                    objective: objective.clone(),
+                },
+            )?;
+            record_db_edit(
+                tx,
+                game_id,
+                models::Event::SetObjective {
+                    objective: game.objective.clone(),
+                },
+                models::Event::SetObjective {
+                    objective: objective.clone(),
+                },
+            )?;
+
+            update_db_game(tx, game_id, game.version, &mut |record| {
+                record.objective = objective.clone();
+                record.result = None;
+            })?;
+            finish_db_edit(tx, game_id)
+        })
+    }
+
+    pub fn update_game_terrain(
+        &self,
+        game_id: u32,
+        terrain: models::Terrain,
+    ) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let game = get_db_game(tx, game_id)?;
+            let position = (game.cursor_x, game.cursor_y);
+            let redo = models::Event::SetTerrain {
+                position: position,
+                terrain: terrain.clone(),
+            };
+            log_db_event(tx, game_id, redo.clone())?;
+            record_db_edit(
+                tx,
+                game_id,
+                terrain_event(position, db_terrain_at(tx, &game)?),
+                redo,
+            )?;
 
-        Ok(())
+            tx.set_tile_line(DBTileLine {
+                id: 0,
+                map_id: game.map_id,
+                terrain: terrain.clone(),
+                x: game.cursor_x,
+                y: game.cursor_y,
+            })?;
+            finish_db_edit(tx, game_id)
+        })
     }
 
     pub fn update_game_character(
@@ -242,87 +654,534 @@ impl DB {
         game_id: u32,
         character: models::Character,
     ) -> Result<(), DBError> {
-        let mut records = self.read_db_characters()?;
-        let max_id = records
-            .iter()
-            .fold(0, |acc, record| std::cmp::max(acc, record.id));
+        self.storage.transaction(&mut |tx| {
+            let game = get_db_game(tx, game_id)?;
+            let position = (game.cursor_x, game.cursor_y);
+            let units = tx.read_characters_for_game_id(game.id)?;
+            let before = db_character_at(tx, &game)?;
 
-        let game = self.get_game(game_id)?;
+            tx.set_character(DBCharacter {
+                id: 0,
+                game_id: game.id,
+                character: character.clone(),
+                x: game.cursor_x,
+                y: game.cursor_y,
+                name: character.next_unit_name(
+                    &units
+                        .iter()
+                        .map(|unit| unit.name.as_str())
+                        .collect::<Vec<&str>>(),
+                ),
+                faction: models::Faction::Player,
+                behavior: models::Behavior::Aggressive,
+                hp: character.max_hp(),
+                moved: false,
+                acted: false,
+            })?;
 
-        let new_record = DBCharacter {
-            id: max_id + 1,
-            game_id: game.map.id,
-            character: character,
-            x: game.current_selection.0,
-            y: game.current_selection.1,
-        };
+            // The log needs the id the character was stored with, so replays
+            // refer to it the same way.
+            let after = match db_character_at(tx, &game)? {
+                Some(record) => record,
+                None => return Err(DBError::finding_record("characters")),
+            };
+            log_db_event(
+                tx,
+                game_id,
+                models::Event::PlaceCharacter {
+                    position: position,
+                    unit_id: after.id,
+                    class: character.clone(),
+                },
+            )?;
+            record_db_edit(
+                tx,
+                game_id,
+                character_event(position, before),
+                character_event(position, Some(after)),
+            )?;
+            finish_db_edit(tx, game_id)
+        })
+    }
 
-        records.push(new_record.clone());
+    /// Sets how the computer plays the character under the game's cursor.
+    pub fn update_game_behavior(
+        &self,
+        game_id: u32,
+        behavior: models::Behavior,
+    ) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let game = get_db_game(tx, game_id)?;
 
-        records = records
-            .into_iter()
-            .map(|record| {
-                (
-                    (record.game_id, record.x, record.y),
-                    (record.id, record.character),
-                )
-            })
-            .collect::<BTreeMap<_, _>>()
-            .into_iter()
-            .map(|(key, value)| DBCharacter {
-                id: value.0,
-                game_id: key.0,
-                character: value.1,
-                x: key.1,
-                y: key.2,
-            })
-            .collect::<Vec<DBCharacter>>();
+            let record = match tx
+                .read_characters_for_game_id(game.id)?
+                .into_iter()
+                .find(|record| record.x == game.cursor_x && record.y == game.cursor_y)
+            {
+                Some(record) => record,
+                None => return Err(DBError::finding_record("characters")),
+            };
+            log_db_event(
+                tx,
+                game_id,
+                models::Event::SetBehavior {
+                    position: (record.x, record.y),
+                    behavior: behavior.clone(),
+                },
+            )?;
+            let after = DBCharacter {
+                behavior: behavior.clone(),
+                ..record.clone()
+            };
+            record_db_edit(
+                tx,
+                game_id,
+                character_event((record.x, record.y), Some(record.clone())),
+                character_event((record.x, record.y), Some(after.clone())),
+            )?;
+            tx.update_character(after)?;
+            finish_db_edit(tx, game_id)
+        })
+    }
+
+    /// Puts the character under the game's cursor on `faction`'s side.
+    pub fn update_game_faction(
+        &self,
+        game_id: u32,
+        faction: models::Faction,
+    ) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let game = get_db_game(tx, game_id)?;
 
-        self.engine
-            .write_replace_records(CHARACTER_DB_FILE_NAME, records)?;
+            let record = match tx
+                .read_characters_for_game_id(game.id)?
+                .into_iter()
+                .find(|record| record.x == game.cursor_x && record.y == game.cursor_y)
+            {
+                Some(record) => record,
+                None => return Err(DBError::finding_record("characters")),
+            };
+            log_db_event(
+                tx,
+                game_id,
+                models::Event::SetFaction {
+                    position: (record.x, record.y),
+                    faction: faction.clone(),
+                },
+            )?;
+            let after = DBCharacter {
+                faction: faction.clone(),
+                ..record.clone()
+            };
+            record_db_edit(
+                tx,
+                game_id,
+                character_event((record.x, record.y), Some(record.clone())),
+                character_event((record.x, record.y), Some(after.clone())),
+            )?;
+            tx.update_character(after)?;
+            finish_db_edit(tx, game_id)
+        })
+    }
 
-        Ok(())
+    /// Puts the anchor the game's edit page paints from at `anchor`, or with
+    /// `None` takes it away. It is kept on the game's map, as the map editor's
+    /// is, but is not logged: it changes nothing the game is played with.
+    pub fn update_game_anchor(
+        &self,
+        game_id: u32,
+        anchor: Option<(u32, u32)>,
+    ) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let game = get_db_game(tx, game_id)?;
+            let map = get_db_map(tx, game.map_id)?;
+            update_db_map_anchor(tx, map, anchor)
+        })
     }
 
-    pub fn unset_game_terrain(&self, game_id: u32) -> Result<(), DBError> {
-        let mut records = self.read_db_tile_lines()?;
+    /// Paints as `paint_map_terrain` does, but to the game's cursor, logged
+    /// as one edit that puts every tile back as it was when undone.
+    pub fn paint_game_terrain(
+        &self,
+        game_id: u32,
+        brush: models::Brush,
+        terrain: models::Terrain,
+    ) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let game = get_db_game(tx, game_id)?;
+            let map = get_db_map(tx, game.map_id)?;
+            let tiles = tx.read_tile_lines_for_map_id(map.id)?;
+            let map = map_model_from_db(map, tiles);
+            let cursor = (game.cursor_x, game.cursor_y);
+            let positions = painting::tiles(&map, brush, map.anchor.unwrap_or(cursor), cursor);
 
-        let game = self.get_game(game_id)?;
+            let painted = positions
+                .iter()
+                .map(|&position| (position, Some(terrain.clone())))
+                .collect::<Vec<_>>();
+            let redo = models::Event::Paint {
+                tiles: painted.clone(),
+            };
+            log_db_event(tx, game_id, redo.clone())?;
+            record_db_edit(
+                tx,
+                game_id,
+                models::Event::Paint {
+                    tiles: positions
+                        .iter()
+                        .map(|position| (*position, map.specified_terrain.get(position).cloned()))
+                        .collect(),
+                },
+                redo,
+            )?;
 
-        records = records
-            .into_iter()
-            .filter(|record| {
-                !((record.map_id == game.map.id)
-                    && (record.x == game.current_selection.0)
-                    && (record.y == game.current_selection.1))
-            })
-            .collect();
+            tx.paint_tile_lines(game.map_id, &painted)?;
+            finish_db_edit(tx, game_id)
+        })
+    }
 
-        self.engine
-            .write_replace_records(TILES_DB_FILE_NAME, records)?;
+    pub fn unset_game_terrain(&self, game_id: u32) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let game = get_db_game(tx, game_id)?;
+            let position = (game.cursor_x, game.cursor_y);
+            let redo = models::Event::UnsetTerrain { position: position };
+            log_db_event(tx, game_id, redo.clone())?;
+            record_db_edit(
+                tx,
+                game_id,
+                terrain_event(position, db_terrain_at(tx, &game)?),
+                redo,
+            )?;
 
-        Ok(())
+            tx.delete_tile_line_at(game.map_id, game.cursor_x, game.cursor_y)?;
+            finish_db_edit(tx, game_id)
+        })
     }
 
     pub fn unset_game_character(&self, game_id: u32) -> Result<(), DBError> {
-        let mut records = self.read_db_characters()?;
+        self.storage.transaction(&mut |tx| {
+            let game = get_db_game(tx, game_id)?;
+            let position = (game.cursor_x, game.cursor_y);
+            let redo = models::Event::RemoveCharacter { position: position };
+            log_db_event(tx, game_id, redo.clone())?;
+            record_db_edit(
+                tx,
+                game_id,
+                character_event(position, db_character_at(tx, &game)?),
+                redo,
+            )?;
 
-        let game = self.get_game(game_id)?;
+            tx.delete_character_at(game.id, game.cursor_x, game.cursor_y)?;
+            finish_db_edit(tx, game_id)
+        })
+    }
 
-        records = records
-            .into_iter()
-            .filter(|record| {
-                !((record.game_id == game.id)
-                    && (record.x == game.current_selection.0)
-                    && (record.y == game.current_selection.1))
+    /// Takes back the latest of the game's edits that is not undone already,
+    /// if there is one.
+    pub fn undo_game_edit(&self, game_id: u32) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let edit = match tx
+                .read_edits_for_game_id(game_id)?
+                .into_iter()
+                .rev()
+                .find(|edit| !edit.undone)
+            {
+                Some(edit) => edit,
+                None => return Ok(()),
+            };
+            apply_db_edit(tx, game_id, &edit.undo)?;
+            tx.update_edit(DBEdit {
+                undone: true,
+                ..edit
             })
-            .collect();
+        })
+    }
+
+    /// Makes again the earliest of the game's undone edits, if there is one.
+    pub fn redo_game_edit(&self, game_id: u32) -> Result<(), DBError> {
+        self.storage.transaction(&mut |tx| {
+            let edit = match tx
+                .read_edits_for_game_id(game_id)?
+                .into_iter()
+                .find(|edit| edit.undone)
+            {
+                Some(edit) => edit,
+                None => return Ok(()),
+            };
+            apply_db_edit(tx, game_id, &edit.redo)?;
+            tx.update_edit(DBEdit {
+                undone: false,
+                ..edit
+            })
+        })
+    }
+}
+
+fn get_db_game(storage: &dyn Storage, game_id: u32) -> Result<DBGame, DBError> {
+    match storage.read_game(game_id)? {
+        Some(game) => Ok(game),
+        None => Err(DBError::finding_record("games")),
+    }
+}
+
+fn get_db_map(storage: &dyn Storage, map_id: u32) -> Result<DBMap, DBError> {
+    match storage.read_map(map_id)? {
+        Some(map) => Ok(map),
+        None => Err(DBError::finding_record("maps")),
+    }
+}
+
+/// Loads a map to be edited on its own, which a game's map is not: the game
+/// logs its edits, so its map is only edited through it.
+fn get_editable_db_map(storage: &dyn Storage, map_id: u32) -> Result<DBMap, DBError> {
+    let map = get_db_map(storage, map_id)?;
+    if storage
+        .read_games()?
+        .iter()
+        .any(|game| game.map_id == map.id)
+    {
+        return Err(DBError::invalid_value(
+            "maps",
+            "map belongs to a game".to_string(),
+        ));
+    }
+    Ok(map)
+}
+
+fn update_db_map_anchor(
+    storage: &dyn Storage,
+    map: DBMap,
+    anchor: Option<(u32, u32)>,
+) -> Result<(), DBError> {
+    storage.update_map(DBMap {
+        anchor_x: anchor.map(|(x, _)| x),
+        anchor_y: anchor.map(|(_, y)| y),
+        ..map
+    })
+}
+
+/// A game as it is when first added, on `map_id`.
+fn new_db_game(map_id: u32, rng_seed: u32) -> DBGame {
+    DBGame {
+        id: 0,
+        map_id: map_id,
+        cursor_x: 0,
+        cursor_y: 0,
+        version: 0,
+        selected_unit_id: None,
+        turn: 1,
+        active_faction: models::Faction::Player,
+        rng_seed: rng_seed,
+        rng_draws: 0,
+        objective: models::Objective::Rout,
+        result: None,
+    }
+}
+
+/// Adds `event` to the end of the game's log, on the turn the game is on
+/// before the event changes anything.
+fn log_db_event(storage: &dyn Storage, game_id: u32, event: models::Event) -> Result<(), DBError> {
+    let game = get_db_game(storage, game_id)?;
+    storage.append_event(DBEvent {
+        id: 0,
+        game_id: game_id,
+        turn: game.turn,
+        event: event,
+    })
+}
+
+/// Adds `event` to the game's log on turn 0, as part of how the game
+/// started rather than anything done in it.
+fn log_db_start_event(
+    storage: &dyn Storage,
+    game_id: u32,
+    event: models::Event,
+) -> Result<(), DBError> {
+    storage.append_event(DBEvent {
+        id: 0,
+        game_id: game_id,
+        turn: 0,
+        event: event,
+    })
+}
+
+/// Adds an edit to the game's history, which `undo` takes back and `redo`
+/// makes again. Edits undone before this can no longer be redone.
+fn record_db_edit(
+    storage: &dyn Storage,
+    game_id: u32,
+    undo: models::Event,
+    redo: models::Event,
+) -> Result<(), DBError> {
+    storage.delete_undone_edits(game_id)?;
+    storage.insert_edit(DBEdit {
+        id: 0,
+        game_id: game_id,
+        undo: undo,
+        redo: redo,
+        undone: false,
+    })
+}
+
+/// Makes the change `event` describes to the game, and logs it. Only the
+/// kinds of event that `record_db_edit` is given are ever applied.
+fn apply_db_edit(
+    storage: &dyn Storage,
+    game_id: u32,
+    event: &models::Event,
+) -> Result<(), DBError> {
+    let game = get_db_game(storage, game_id)?;
+    log_db_event(storage, game_id, event.clone())?;
+    let applied = match event {
+        models::Event::SetTerrain { position, terrain } => storage.set_tile_line(DBTileLine {
+            id: 0,
+            map_id: game.map_id,
+            terrain: terrain.clone(),
+            x: position.0,
+            y: position.1,
+        }),
+        models::Event::UnsetTerrain { position } => {
+            storage.delete_tile_line_at(game.map_id, position.0, position.1)
+        }
+        models::Event::RestoreCharacter { unit } => {
+            storage.put_character(db_character_from_unit(game_id, unit))
+        }
+        models::Event::RemoveCharacter { position } => {
+            storage.delete_character_at(game_id, position.0, position.1)
+        }
+        models::Event::SetObjective { objective } => storage.update_game(DBGame {
+            objective: objective.clone(),
+            result: None,
+            ..game
+        }),
+        models::Event::Paint { tiles } => storage.paint_tile_lines(game.map_id, tiles),
+        _ => Ok(()),
+    };
+    applied?;
+    finish_db_edit(storage, game_id)
+}
+
+/// The terrain set at the game's cursor, if any.
+fn db_terrain_at(storage: &dyn Storage, game: &DBGame) -> Result<Option<models::Terrain>, DBError> {
+    Ok(storage
+        .read_tile_lines_for_map_id(game.map_id)?
+        .into_iter()
+        .find(|record| record.x == game.cursor_x && record.y == game.cursor_y)
+        .map(|record| record.terrain))
+}
+
+/// The character at the game's cursor, if any.
+fn db_character_at(storage: &dyn Storage, game: &DBGame) -> Result<Option<DBCharacter>, DBError> {
+    Ok(storage
+        .read_characters_for_game_id(game.id)?
+        .into_iter()
+        .find(|record| record.x == game.cursor_x && record.y == game.cursor_y))
+}
+
+/// The starting unit under the map editor's cursor, if any.
+fn db_starting_unit_at(
+    storage: &dyn Storage,
+    map: &DBMap,
+) -> Result<Option<DBStartingUnit>, DBError> {
+    Ok(storage
+        .read_starting_units_for_map_id(map.id)?
+        .into_iter()
+        .find(|record| record.x == map.cursor_x && record.y == map.cursor_y))
+}
+
+/// The event that leaves `position` with `terrain`, or none set.
+fn terrain_event(position: (u32, u32), terrain: Option<models::Terrain>) -> models::Event {
+    match terrain {
+        Some(terrain) => models::Event::SetTerrain {
+            position: position,
+            terrain: terrain,
+        },
+        None => models::Event::UnsetTerrain { position: position },
+    }
+}
+
+/// The event that leaves `position` with exactly `character`, or empty.
+fn character_event(position: (u32, u32), character: Option<DBCharacter>) -> models::Event {
+    match character {
+        Some(character) => models::Event::RestoreCharacter {
+            unit: unit_model_from_db(character),
+        },
+        None => models::Event::RemoveCharacter { position: position },
+    }
+}
+
+/// Applies `change` to the game and bumps its version, unless someone else
+/// has changed it since it was read at `expected_version`.
+fn update_db_game(
+    storage: &dyn Storage,
+    id: u32,
+    expected_version: u32,
+    change: &mut dyn FnMut(&mut DBGame),
+) -> Result<(), DBError> {
+    let mut record = get_db_game(storage, id)?;
+    if record.version != expected_version {
+        return Err(DBError::conflict("games"));
+    }
+    record.version += 1;
+    change(&mut record);
+    storage.update_game(record)
+}
 
-        self.engine
-            .write_replace_records(CHARACTER_DB_FILE_NAME, records)?;
+/// Counts an edit as a change to the game, so that writes from anyone who
+/// read it before the edit fail as `update_db_game` conflicts, and decides
+/// the game over again from what the edit left.
+fn finish_db_edit(storage: &dyn Storage, game_id: u32) -> Result<(), DBError> {
+    let game = get_db_game(storage, game_id)?;
+    let map = get_db_map(storage, game.map_id)?;
+    let tiles = storage.read_tile_lines_for_map_id(map.id)?;
+    let characters = storage.read_characters_for_game_id(game_id)?;
+    let version = game.version;
+    let mut model = game_model_from_db(game, map, tiles, characters);
+    model.recheck_result();
+    update_db_game(storage, game_id, version, &mut |record| {
+        record.result = model.result.clone();
+    })
+}
 
-        Ok(())
+/// Stores the position, faction, behavior, hp and turn flags of each of
+/// `units`, which must all be characters of the game.
+fn update_db_units(
+    storage: &dyn Storage,
+    game_id: u32,
+    units: &[&models::Unit],
+) -> Result<(), DBError> {
+    let records = storage.read_characters_for_game_id(game_id)?;
+    for unit in units.iter() {
+        let record = match records.iter().find(|record| record.id == unit.id) {
+            Some(record) => record.clone(),
+            None => return Err(DBError::finding_record("characters")),
+        };
+        storage.update_character(DBCharacter {
+            x: unit.position.0,
+            y: unit.position.1,
+            faction: unit.faction.clone(),
+            behavior: unit.behavior.clone(),
+            hp: unit.hp,
+            moved: unit.moved,
+            acted: unit.acted,
+            ..record
+        })?;
     }
+    Ok(())
+}
+
+fn add_db_map(storage: &dyn Storage) -> Result<DBMap, DBError> {
+    storage.insert_map(DBMap {
+        id: 0,
+        default_terrain: models::Terrain::Grass,
+        hint_max_x: 15,
+        hint_max_y: 12,
+        cursor_x: 0,
+        cursor_y: 0,
+        name: "".into(),
+        anchor_x: None,
+        anchor_y: None,
+    })
 }
 
 fn game_model_from_db(
@@ -334,11 +1193,60 @@ fn game_model_from_db(
     models::Game {
         id: g.id,
         map: map_model_from_db(m, tiles),
-        characters: characters
+        units: characters
             .into_iter()
-            .map(|character| ((character.x, character.y), character.character))
+            .map(|character| (character.id, unit_model_from_db(character)))
             .collect::<BTreeMap<_, _>>(),
         current_selection: (g.cursor_x, g.cursor_y),
+        selected_unit: g.selected_unit_id,
+        turn: g.turn,
+        active_faction: g.active_faction,
+        rng: Rng {
+            seed: g.rng_seed,
+            draws: g.rng_draws,
+        },
+        objective: g.objective,
+        result: g.result,
+        version: g.version,
+    }
+}
+
+fn unit_model_from_db(character: DBCharacter) -> models::Unit {
+    models::Unit {
+        id: character.id,
+        name: character.name,
+        class: character.character,
+        position: (character.x, character.y),
+        faction: character.faction,
+        behavior: character.behavior,
+        hp: character.hp,
+        moved: character.moved,
+        acted: character.acted,
+    }
+}
+
+fn db_character_from_unit(game_id: u32, unit: &models::Unit) -> DBCharacter {
+    DBCharacter {
+        id: unit.id,
+        game_id: game_id,
+        character: unit.class.clone(),
+        x: unit.position.0,
+        y: unit.position.1,
+        name: unit.name.clone(),
+        faction: unit.faction.clone(),
+        behavior: unit.behavior.clone(),
+        hp: unit.hp,
+        moved: unit.moved,
+        acted: unit.acted,
+    }
+}
+
+fn starting_unit_model_from_db(unit: DBStartingUnit) -> models::StartingUnit {
+    models::StartingUnit {
+        class: unit.character,
+        position: (unit.x, unit.y),
+        faction: unit.faction,
+        behavior: unit.behavior,
     }
 }
 
@@ -352,18 +1260,344 @@ fn map_model_from_db(m: DBMap, tiles: Vec<DBTileLine>) -> models::Map {
             .collect::<BTreeMap<_, _>>(),
         hint_max_x: m.hint_max_x,
         hint_max_y: m.hint_max_y,
+        cursor: (m.cursor_x, m.cursor_y),
+        name: m.name,
+        anchor: match (m.anchor_x, m.anchor_y) {
+            (Some(x), Some(y)) => Some((x, y)),
+            _ => None,
+        },
     }
 }
 
-pub fn get_single_result<T, F: Fn(&T) -> bool>(
-    results: Vec<T>,
-    cmp: F,
-    name: &'static str,
-) -> Result<T, DBError> {
-    for result in results.into_iter() {
-        if cmp(&result) {
-            return Ok(result);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::replay;
+
+    /// A database holding one blank game, whose random numbers come from
+    /// `seed`, and that game's id.
+    fn db_with_game(seed: u32) -> (DB, u32) {
+        let db = DB::from_storage(Box::new(MemoryStorage::new()));
+        let mut game_id = 0;
+        db.storage
+            .transaction(&mut |tx| {
+                let map = add_db_map(tx)?;
+                game_id = tx.insert_game(new_db_game(map.id, seed))?.id;
+                Ok(())
+            })
+            .unwrap();
+        (db, game_id)
+    }
+
+    fn move_cursor(db: &DB, game_id: u32, position: (u32, u32)) {
+        let game = db.get_game(game_id).unwrap();
+        db.update_game_cursor(game_id, game.version, position)
+            .unwrap();
+    }
+
+    /// Puts a unit under the cursor at `position` from the edit page.
+    fn place(
+        db: &DB,
+        game_id: u32,
+        position: (u32, u32),
+        class: models::Character,
+        faction: models::Faction,
+        behavior: models::Behavior,
+    ) {
+        move_cursor(db, game_id, position);
+        db.update_game_character(game_id, class).unwrap();
+        db.update_game_faction(game_id, faction).unwrap();
+        db.update_game_behavior(game_id, behavior).unwrap();
+    }
+
+    /// Has the unit at `from` move to `to` and then attack whatever is at
+    /// `target`, going through the same steps as the play page. Whatever
+    /// the page would turn away is left undone.
+    fn play(db: &DB, game_id: u32, from: (u32, u32), to: (u32, u32), target: (u32, u32)) {
+        move_cursor(db, game_id, from);
+        let mut game = db.get_game(game_id).unwrap();
+        if game.select_unit().is_err() {
+            return;
+        }
+        db.update_game_selected_unit(game_id, game.version, game.selected_unit)
+            .unwrap();
+
+        move_cursor(db, game_id, to);
+        let mut game = db.get_game(game_id).unwrap();
+        match game.move_selected_unit() {
+            Ok(unit) => db.move_game_unit(game.version, &game, &unit).unwrap(),
+            Err(_) => return,
+        };
+
+        let mut game = db.get_game(game_id).unwrap();
+        if game.select_unit().is_err() {
+            return;
+        }
+        db.update_game_selected_unit(game_id, game.version, game.selected_unit)
+            .unwrap();
+        move_cursor(db, game_id, target);
+        let mut game = db.get_game(game_id).unwrap();
+        if let Ok(attack) = game.attack_with_selected_unit() {
+            db.attack_game_unit(game.version, &game, &attack).unwrap();
+        }
+    }
+
+    fn end_turn(db: &DB, game_id: u32) {
+        let mut game = db.get_game(game_id).unwrap();
+        game.end_turn();
+        crate::ai::play_computer_turns(&mut game);
+        db.end_game_turn(game.version, &game).unwrap();
+    }
+
+    /// The game as replaying its whole log gives it back.
+    fn replayed(db: &DB, game_id: u32) -> models::Game {
+        let log = db.get_game_log(game_id).unwrap();
+        replay::replay(
+            db.get_game_start(game_id).unwrap(),
+            &log,
+            replay::last_turn(&log),
+        )
+    }
+
+    /// Every unit, each with all of its fields.
+    fn units(game: &models::Game) -> Vec<String> {
+        game.units
+            .values()
+            .map(|unit| models::Event::RestoreCharacter { unit: unit.clone() }.log_string())
+            .collect()
+    }
+
+    #[test]
+    fn playing_forgets_the_edit_history() {
+        let (db, game_id) = db_with_game(1);
+        place(
+            &db,
+            game_id,
+            (5, 5),
+            models::Character::Thief,
+            models::Faction::Enemy,
+            models::Behavior::Guard,
+        );
+        place(
+            &db,
+            game_id,
+            (0, 0),
+            models::Character::Knight,
+            models::Faction::Player,
+            models::Behavior::Aggressive,
+        );
+        play(&db, game_id, (0, 0), (1, 0), (1, 0));
+        let moved = units(&db.get_game(game_id).unwrap());
+
+        // Undoing would otherwise put the knight back where it was placed.
+        db.undo_game_edit(game_id).unwrap();
+        db.redo_game_edit(game_id).unwrap();
+        let game = db.get_game(game_id).unwrap();
+        assert_eq!(units(&game), moved);
+        assert!(game.unit_at(&(1, 0)).is_some());
+        assert!(game.unit_at(&(0, 0)).is_none());
+    }
+
+    #[test]
+    fn undoing_or_redoing_nothing_changes_nothing() {
+        let (db, game_id) = db_with_game(1);
+        let version = db.get_game(game_id).unwrap().version;
+        db.undo_game_edit(game_id).unwrap();
+        db.redo_game_edit(game_id).unwrap();
+        assert_eq!(db.get_game(game_id).unwrap().version, version);
+        assert!(db.get_game_log(game_id).unwrap().is_empty());
+    }
+
+    #[test]
+    fn play_read_before_an_edit_conflicts() {
+        let (db, game_id) = db_with_game(1);
+        place(
+            &db,
+            game_id,
+            (0, 0),
+            models::Character::Knight,
+            models::Faction::Player,
+            models::Behavior::Aggressive,
+        );
+        let mut game = db.get_game(game_id).unwrap();
+        game.end_turn();
+
+        db.unset_game_character(game_id).unwrap();
+        match db.end_game_turn(game.version, &game) {
+            Err(DBError::Conflict { .. }) => (),
+            _ => panic!("a turn ended on a game read before an edit"),
+        };
+        assert!(db.get_game(game_id).unwrap().units.is_empty());
+    }
+
+    #[test]
+    fn edits_that_meet_the_objective_win_the_game() {
+        let (db, game_id) = db_with_game(1);
+        place(
+            &db,
+            game_id,
+            (0, 0),
+            models::Character::Knight,
+            models::Faction::Player,
+            models::Behavior::Aggressive,
+        );
+        place(
+            &db,
+            game_id,
+            (2, 2),
+            models::Character::Thief,
+            models::Faction::Enemy,
+            models::Behavior::Aggressive,
+        );
+        assert!(db.get_game(game_id).unwrap().result.is_none());
+
+        // Removing the last enemy routs them.
+        db.unset_game_character(game_id).unwrap();
+        let game = db.get_game(game_id).unwrap();
+        assert!(game.result == Some(models::GameResult::Won));
+        assert!(replayed(&db, game_id).result == game.result);
+
+        // A new objective is decided over again, straight away if a player
+        // unit already stands where it has to reach.
+        db.update_game_objective(game_id, models::Objective::Survive { turns: 5 })
+            .unwrap();
+        assert!(db.get_game(game_id).unwrap().result.is_none());
+        db.update_game_objective(game_id, models::Objective::Reach { position: (0, 0) })
+            .unwrap();
+        let game = db.get_game(game_id).unwrap();
+        assert!(game.result == Some(models::GameResult::Won));
+        assert!(replayed(&db, game_id).result == game.result);
+    }
+
+    #[test]
+    fn shrinking_a_map_removes_what_is_left_outside() {
+        let db = DB::from_storage(Box::new(MemoryStorage::new()));
+        db.add_map().unwrap();
+        let map_id = db.get_maps().unwrap()[0].id;
+        for &position in [(0, 0), (3, 1), (1, 4)].iter() {
+            db.update_map_cursor(map_id, position).unwrap();
+            db.update_map_terrain(map_id, models::Terrain::Rock)
+                .unwrap();
+            db.update_map_character(map_id, models::Character::Knight)
+                .unwrap();
+        }
+
+        db.update_map_size(map_id, (3, 4)).unwrap();
+        let map = db.get_map(map_id).unwrap();
+        assert_eq!((map.hint_max_x, map.hint_max_y), (3, 4));
+        assert_eq!(
+            map.specified_terrain.keys().collect::<Vec<_>>(),
+            vec![&(0, 0)]
+        );
+        let positions: Vec<(u32, u32)> = db
+            .get_map_starting_units(map_id)
+            .unwrap()
+            .iter()
+            .map(|unit| unit.position)
+            .collect();
+        assert_eq!(positions, vec![(0, 0)]);
+        assert!(map.cursor.0 < 3 && map.cursor.1 < 4);
+    }
+
+    #[test]
+    fn a_games_map_is_only_edited_through_the_game() {
+        let (db, game_id) = db_with_game(1234);
+        let map_id = db.get_game(game_id).unwrap().map.id;
+        let edits: Vec<Box<dyn Fn() -> Result<(), DBError>>> = vec![
+            Box::new(|| db.update_map_terrain(map_id, models::Terrain::Rock)),
+            Box::new(|| db.update_map_character(map_id, models::Character::Knight)),
+            Box::new(|| db.update_map_name(map_id, "taken".to_string())),
+            Box::new(|| db.update_map_size(map_id, (3, 3))),
+            Box::new(|| db.update_map_anchor(map_id, Some((0, 0)))),
+            Box::new(|| db.paint_map_terrain(map_id, models::Brush::Fill, models::Terrain::Rock)),
+        ];
+        for edit in edits.iter() {
+            match edit() {
+                Err(DBError::InvalidValue { .. }) => (),
+                _ => panic!("a game's map was edited on its own"),
+            };
+        }
+
+        db.update_game_anchor(game_id, Some((0, 0))).unwrap();
+        assert_eq!(db.get_game(game_id).unwrap().map.anchor, Some((0, 0)));
+    }
+
+    #[test]
+    fn a_map_cannot_shrink_to_nothing() {
+        let db = DB::from_storage(Box::new(MemoryStorage::new()));
+        db.add_map().unwrap();
+        let map_id = db.get_maps().unwrap()[0].id;
+        for &size in [(0, 5), (5, 0)].iter() {
+            match db.update_map_size(map_id, size) {
+                Err(DBError::InvalidValue { .. }) => (),
+                _ => panic!("a {:?} map was not turned away", size),
+            };
         }
+        db.update_map_size(map_id, (1, 1)).unwrap();
+    }
+
+    #[test]
+    fn replaying_the_log_gives_back_the_stored_game() {
+        let (db, game_id) = db_with_game(1234);
+        place(
+            &db,
+            game_id,
+            (0, 0),
+            models::Character::Mage,
+            models::Faction::Player,
+            models::Behavior::Aggressive,
+        );
+        place(
+            &db,
+            game_id,
+            (0, 1),
+            models::Character::Knight,
+            models::Faction::Player,
+            models::Behavior::Aggressive,
+        );
+        place(
+            &db,
+            game_id,
+            (4, 0),
+            models::Character::Thief,
+            models::Faction::Enemy,
+            models::Behavior::Aggressive,
+        );
+        place(
+            &db,
+            game_id,
+            (5, 2),
+            models::Character::Knight,
+            models::Faction::Enemy,
+            models::Behavior::Defensive,
+        );
+
+        for _ in 0..3 {
+            play(&db, game_id, (0, 0), (2, 0), (4, 0));
+            play(&db, game_id, (2, 0), (2, 0), (3, 0));
+            play(&db, game_id, (0, 1), (3, 1), (4, 1));
+            play(&db, game_id, (3, 1), (3, 1), (3, 0));
+            end_turn(&db, game_id);
+        }
+
+        let stored = db.get_game(game_id).unwrap();
+        let log = db.get_game_log(game_id).unwrap();
+        let replayed = replayed(&db, game_id);
+
+        // Enough happened for the comparison to mean something.
+        assert!(stored.turn > 1);
+        assert!(stored.rng.draws > 0);
+        assert!(log
+            .iter()
+            .any(|entry| matches!(entry.event, models::Event::Attack { .. })));
+
+        assert_eq!(units(&replayed), units(&stored));
+        assert_eq!(replayed.turn, stored.turn);
+        assert!(replayed.active_faction == stored.active_faction);
+        assert_eq!(replayed.rng.seed, stored.rng.seed);
+        assert_eq!(replayed.rng.draws, stored.rng.draws);
+        assert!(replayed.result == stored.result);
+        assert_eq!(replayed.selected_unit, stored.selected_unit);
+        assert_eq!(replayed.current_selection, stored.current_selection);
     }
-    return Err(DBError::FindingRecord(name.into()));
 }
@@ -0,0 +1,286 @@
+use std::path::Path;
+
+use super::common::DBError;
+use super::engine;
+use super::migrations::{self, Table};
+use super::records::{
+    DBCharacter, DBEdit, DBEvent, DBGame, DBIdCounter, DBMap, DBStartingUnit, DBTileLine,
+};
+use super::storage::Storage;
+use super::transaction::{self, TableWrites};
+
+const GAME_DB_FILE_NAME: &'static str = "game.csv";
+const MAP_DB_FILE_NAME: &'static str = "map.csv";
+const TILES_DB_FILE_NAME: &'static str = "tiles.csv";
+const CHARACTER_DB_FILE_NAME: &'static str = "characters.csv";
+const EVENT_DB_FILE_NAME: &'static str = "events.csv";
+const EDIT_DB_FILE_NAME: &'static str = "edits.csv";
+const STARTING_UNIT_DB_FILE_NAME: &'static str = "starting_units.csv";
+const ID_COUNTER_DB_FILE_NAME: &'static str = "ids.csv";
+const SCHEMA_DB_FILE_NAME: &'static str = "schema.csv";
+
+const JOURNAL_FILE_NAME: &'static str = "journal";
+const LOCK_FILE_NAME: &'static str = "lock";
+
+/// Storage backed by one csv file per table in a data directory.
+pub struct CsvStorage {
+    engine: engine::Engine,
+    files: FileNames,
+}
+
+impl CsvStorage {
+    pub fn new(data_dir: &Path) -> Result<Self, DBError> {
+        let files = FileNames::in_dir(data_dir);
+        let engine = engine::Engine::new(
+            path_in(data_dir, JOURNAL_FILE_NAME),
+            path_in(data_dir, LOCK_FILE_NAME),
+            &[
+                &files.games,
+                &files.maps,
+                &files.tile_lines,
+                &files.characters,
+                &files.events,
+                &files.edits,
+                &files.starting_units,
+                &files.id_counters,
+                &files.schema,
+            ],
+        )?;
+
+        {
+            let _lock = engine.lock_writes()?;
+            migrations::migrate_csv(&engine, &files.schema, &|table| {
+                files.for_table(table).to_string()
+            })?;
+        }
+
+        Ok(CsvStorage {
+            engine: engine,
+            files: files,
+        })
+    }
+}
+
+struct FileNames {
+    games: String,
+    maps: String,
+    tile_lines: String,
+    characters: String,
+    events: String,
+    edits: String,
+    starting_units: String,
+    id_counters: String,
+    schema: String,
+}
+
+impl FileNames {
+    fn in_dir(data_dir: &Path) -> Self {
+        FileNames {
+            games: path_in(data_dir, GAME_DB_FILE_NAME),
+            maps: path_in(data_dir, MAP_DB_FILE_NAME),
+            tile_lines: path_in(data_dir, TILES_DB_FILE_NAME),
+            characters: path_in(data_dir, CHARACTER_DB_FILE_NAME),
+            events: path_in(data_dir, EVENT_DB_FILE_NAME),
+            edits: path_in(data_dir, EDIT_DB_FILE_NAME),
+            starting_units: path_in(data_dir, STARTING_UNIT_DB_FILE_NAME),
+            id_counters: path_in(data_dir, ID_COUNTER_DB_FILE_NAME),
+            schema: path_in(data_dir, SCHEMA_DB_FILE_NAME),
+        }
+    }
+
+    fn for_table(&self, table: Table) -> &str {
+        match table {
+            Table::Games => &self.games,
+            Table::Maps => &self.maps,
+            Table::TileLines => &self.tile_lines,
+            Table::Characters => &self.characters,
+            Table::Events => &self.events,
+            Table::Edits => &self.edits,
+            Table::StartingUnits => &self.starting_units,
+            Table::IdCounters => &self.id_counters,
+        }
+    }
+}
+
+fn path_in(data_dir: &Path, file_name: &str) -> String {
+    data_dir.join(file_name).to_string_lossy().into_owned()
+}
+
+impl Storage for CsvStorage {
+    fn read_games(&self) -> Result<Vec<DBGame>, DBError> {
+        self.engine.read_db_records(&self.files.games)
+    }
+
+    fn write_games(&self, records: Vec<DBGame>) -> Result<(), DBError> {
+        self.engine
+            .write_replace_records(&self.files.games, records)
+    }
+
+    fn read_maps(&self) -> Result<Vec<DBMap>, DBError> {
+        self.engine.read_db_records(&self.files.maps)
+    }
+
+    fn write_maps(&self, records: Vec<DBMap>) -> Result<(), DBError> {
+        self.engine.write_replace_records(&self.files.maps, records)
+    }
+
+    fn read_tile_lines(&self) -> Result<Vec<DBTileLine>, DBError> {
+        self.engine.read_db_records(&self.files.tile_lines)
+    }
+
+    fn write_tile_lines(&self, records: Vec<DBTileLine>) -> Result<(), DBError> {
+        self.engine
+            .write_replace_records(&self.files.tile_lines, records)
+    }
+
+    fn read_characters(&self) -> Result<Vec<DBCharacter>, DBError> {
+        self.engine.read_db_records(&self.files.characters)
+    }
+
+    fn write_characters(&self, records: Vec<DBCharacter>) -> Result<(), DBError> {
+        self.engine
+            .write_replace_records(&self.files.characters, records)
+    }
+
+    fn read_events(&self) -> Result<Vec<DBEvent>, DBError> {
+        self.engine.read_db_records(&self.files.events)
+    }
+
+    fn write_events(&self, records: Vec<DBEvent>) -> Result<(), DBError> {
+        self.engine
+            .write_replace_records(&self.files.events, records)
+    }
+
+    fn read_edits(&self) -> Result<Vec<DBEdit>, DBError> {
+        self.engine.read_db_records(&self.files.edits)
+    }
+
+    fn write_edits(&self, records: Vec<DBEdit>) -> Result<(), DBError> {
+        self.engine
+            .write_replace_records(&self.files.edits, records)
+    }
+
+    fn read_starting_units(&self) -> Result<Vec<DBStartingUnit>, DBError> {
+        self.engine.read_db_records(&self.files.starting_units)
+    }
+
+    fn write_starting_units(&self, records: Vec<DBStartingUnit>) -> Result<(), DBError> {
+        self.engine
+            .write_replace_records(&self.files.starting_units, records)
+    }
+
+    fn read_id_counters(&self) -> Result<Vec<DBIdCounter>, DBError> {
+        self.engine.read_db_records(&self.files.id_counters)
+    }
+
+    fn write_id_counters(&self, records: Vec<DBIdCounter>) -> Result<(), DBError> {
+        self.engine
+            .write_replace_records(&self.files.id_counters, records)
+    }
+
+    fn write_tables(&self, writes: TableWrites) -> Result<(), DBError> {
+        let mut transaction = self.engine.transaction();
+        match writes.games {
+            Some(records) => transaction.stage_replace_records(&self.files.games, records)?,
+            None => (),
+        };
+        match writes.maps {
+            Some(records) => transaction.stage_replace_records(&self.files.maps, records)?,
+            None => (),
+        };
+        match writes.tile_lines {
+            Some(records) => transaction.stage_replace_records(&self.files.tile_lines, records)?,
+            None => (),
+        };
+        match writes.characters {
+            Some(records) => transaction.stage_replace_records(&self.files.characters, records)?,
+            None => (),
+        };
+        match writes.events {
+            Some(records) => transaction.stage_replace_records(&self.files.events, records)?,
+            None if !writes.appended_events.is_empty() => {
+                transaction.stage_append_records(&self.files.events, writes.appended_events)?
+            }
+            None => (),
+        };
+        match writes.edits {
+            Some(records) => transaction.stage_replace_records(&self.files.edits, records)?,
+            None => (),
+        };
+        match writes.starting_units {
+            Some(records) => {
+                transaction.stage_replace_records(&self.files.starting_units, records)?
+            }
+            None => (),
+        };
+        match writes.id_counters {
+            Some(records) => transaction.stage_replace_records(&self.files.id_counters, records)?,
+            None => (),
+        };
+        transaction.commit()
+    }
+
+    fn transaction(
+        &self,
+        f: &mut dyn FnMut(&dyn Storage) -> Result<(), DBError>,
+    ) -> Result<(), DBError> {
+        let _lock = self.engine.lock_writes()?;
+        transaction::staged_transaction(self, f)
+    }
+
+    fn read_game(&self, id: u32) -> Result<Option<DBGame>, DBError> {
+        Ok(self
+            .engine
+            .read_db_records_by(&self.files.games, "id", |r: &DBGame| r.id, id)?
+            .pop())
+    }
+
+    fn read_map(&self, id: u32) -> Result<Option<DBMap>, DBError> {
+        Ok(self
+            .engine
+            .read_db_records_by(&self.files.maps, "id", |r: &DBMap| r.id, id)?
+            .pop())
+    }
+
+    fn read_tile_lines_for_map_id(&self, map_id: u32) -> Result<Vec<DBTileLine>, DBError> {
+        self.engine.read_db_records_by(
+            &self.files.tile_lines,
+            "map_id",
+            |r: &DBTileLine| r.map_id,
+            map_id,
+        )
+    }
+
+    fn read_characters_for_game_id(&self, game_id: u32) -> Result<Vec<DBCharacter>, DBError> {
+        self.engine.read_db_records_by(
+            &self.files.characters,
+            "game_id",
+            |r: &DBCharacter| r.game_id,
+            game_id,
+        )
+    }
+
+    fn read_events_for_game_id(&self, game_id: u32) -> Result<Vec<DBEvent>, DBError> {
+        let mut records = self.engine.read_db_records_by(
+            &self.files.events,
+            "game_id",
+            |r: &DBEvent| r.game_id,
+            game_id,
+        )?;
+        records.sort_by_key(|r| r.id);
+        Ok(records)
+    }
+
+    fn next_event_id(&self) -> Result<u32, DBError> {
+        Ok(self
+            .engine
+            .read_db_max(&self.files.events, |r: &DBEvent| r.id)?
+            + 1)
+    }
+
+    fn append_event(&self, record: DBEvent) -> Result<(), DBError> {
+        let id = self.next_event_id()?;
+        self.engine
+            .write_append_records(&self.files.events, vec![DBEvent { id: id, ..record }])
+    }
+}
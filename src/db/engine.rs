@@ -1,61 +1,875 @@
-use std::fs::File;
+use std::any::Any;
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
 
+use fs2::FileExt;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 
-use super::common::DBError;
+use super::common::{display_name, Cause, DBError};
+
+/// Held by whichever thread of this process is writing, so only one of them
+/// waits on the file lock at a time.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// Held while a transaction commits, and while an engine finishes whatever
+/// commit the last process left behind, so recovery never races a commit.
+static COMMIT_LOCK: Mutex<()> = Mutex::new(());
+
+/// Numbers the temp files this process writes, so no two transactions ever
+/// stage a table to the same file.
+static NEXT_TMP_FILE: AtomicU64 = AtomicU64::new(0);
 
 pub struct Engine {
-    _nothing: (),
+    journal_file_name: String,
+    lock_file_name: String,
+    cache: Mutex<Cache>,
+}
+
+/// Parsed tables kept between reads.
+struct Cache {
+    /// Bumped by every commit, so a read that raced one does not cache what
+    /// it read.
+    generation: u64,
+    tables: HashMap<String, CachedTable>,
+}
+
+/// A table's parsed records, along with what its file looked like when they
+/// were read.
+///
+/// Writes from this process drop the entry on commit. Every write replaces the
+/// file or adds to its end, so one whose modification time or length no
+/// longer match has also been written since, which catches other processes
+/// sharing the files.
+struct CachedTable {
+    modified: SystemTime,
+    len: u64,
+    records: Arc<dyn Any + Send + Sync>,
+    /// Row positions in `records` by column value, for each column looked up
+    /// so far.
+    indexes: HashMap<&'static str, Arc<HashMap<u32, Vec<usize>>>>,
 }
 
 impl Engine {
-    pub fn new(table_names: &'static [&'static str]) -> Self {
+    pub fn new(
+        journal_file_name: String,
+        lock_file_name: String,
+        table_names: &[&str],
+    ) -> Result<Self, DBError> {
+        let engine = Engine {
+            journal_file_name: journal_file_name,
+            lock_file_name: lock_file_name,
+            cache: Mutex::new(Cache {
+                generation: 0,
+                tables: HashMap::new(),
+            }),
+        };
+
+        let _lock = engine.lock_writes()?;
+
+        engine.recover(table_names)?;
+
         for file_name in table_names.iter() {
             match File::open(file_name) {
                 Ok(_f) => {}
                 Err(_e) => {
-                    let mut writer = csv::Writer::from_path(file_name).unwrap();
-                    writer.flush().unwrap();
+                    File::create(file_name).map_err(|e| DBError::io(file_name, e))?;
                 }
             }
         }
-        Engine { _nothing: () }
+
+        Ok(engine)
+    }
+
+    /// Blocks until this caller is the only writer of the tables, among both
+    /// the threads of this process and any other process sharing the files.
+    ///
+    /// Callers hold the lock across their whole read-modify-write so no other
+    /// writer can slip in between.
+    pub fn lock_writes(&self) -> Result<WriteLock, DBError> {
+        let guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .open(&self.lock_file_name)
+            .map_err(|e| DBError::io(&self.lock_file_name, e))?;
+        file.lock_exclusive()
+            .map_err(|e| DBError::io(&self.lock_file_name, e))?;
+        Ok(WriteLock {
+            file: file,
+            _guard: guard,
+        })
+    }
+
+    /// Starts staging table writes that will all land on `commit`, or none of
+    /// them will.
+    pub fn transaction(&self) -> Transaction<'_> {
+        Transaction {
+            engine: self,
+            staged: Vec::new(),
+            appended: Vec::new(),
+            committed: false,
+        }
     }
 
+    /// Replaces the whole table with `records`.
     pub fn write_replace_records<S: Serialize>(
         &self,
-        db_file_name: &'static str,
+        db_file_name: &str,
         records: Vec<S>,
     ) -> Result<(), DBError> {
-        let mut writer = csv::Writer::from_path(db_file_name).unwrap();
+        let mut transaction = self.transaction();
+        transaction.stage_replace_records(db_file_name, records)?;
+        transaction.commit()
+    }
 
-        for record in records.into_iter() {
-            writer.serialize(record).unwrap();
-        }
-        match writer.flush() {
-            Ok(()) => (),
-            Err(e) => return Err(DBError::Internal(e)),
-        };
+    /// Adds `records` after the rows already in the table.
+    pub fn write_append_records<S: Serialize>(
+        &self,
+        db_file_name: &str,
+        records: Vec<S>,
+    ) -> Result<(), DBError> {
+        let mut transaction = self.transaction();
+        transaction.stage_append_records(db_file_name, records)?;
+        transaction.commit()
+    }
 
-        Ok(())
+    /// Reads every record of a table, only parsing the file again if it has
+    /// been written since the last read.
+    pub fn read_db_records<S: DeserializeOwned + Clone + Send + Sync + 'static>(
+        &self,
+        db_file_name: &str,
+    ) -> Result<Vec<S>, DBError> {
+        Ok(self.cached_records::<S>(db_file_name)?.as_ref().clone())
     }
 
-    pub fn read_db_records<S: DeserializeOwned>(
+    /// Reads the records of a table whose `column` (as given by `key_of`) is
+    /// `key`.
+    ///
+    /// Each column gets a secondary index the first time it is looked up,
+    /// kept alongside the cached records until the table is written again.
+    pub fn read_db_records_by<S: DeserializeOwned + Clone + Send + Sync + 'static>(
         &self,
-        db_file_name: &'static str,
+        db_file_name: &str,
+        column: &'static str,
+        key_of: fn(&S) -> u32,
+        key: u32,
     ) -> Result<Vec<S>, DBError> {
-        let mut rdr =
-            csv::Reader::from_reader(File::open(db_file_name).map_err(|e| {
-                DBError::FindingTable(format!("could not read from file: {:?}", e))
-            })?);
+        let records = self.cached_records::<S>(db_file_name)?;
+
+        let index = {
+            let mut cache = self.cache_lock();
+            match cache.tables.get_mut(db_file_name) {
+                Some(cached) if same_records(&cached.records, &records) => cached
+                    .indexes
+                    .entry(column)
+                    .or_insert_with(|| Arc::new(build_index(&records, key_of)))
+                    .clone(),
+                // Raced a write; index this read alone.
+                _ => Arc::new(build_index(&records, key_of)),
+            }
+        };
+
+        Ok(match index.get(&key) {
+            Some(rows) => rows.iter().map(|&row| records[row].clone()).collect(),
+            None => Vec::new(),
+        })
+    }
+
+    /// The highest `key_of` among a table's records (0 if it has none),
+    /// worked out from the cached records rather than a copy of them.
+    pub fn read_db_max<S: DeserializeOwned + Send + Sync + 'static>(
+        &self,
+        db_file_name: &str,
+        key_of: fn(&S) -> u32,
+    ) -> Result<u32, DBError> {
+        Ok(self
+            .cached_records::<S>(db_file_name)?
+            .iter()
+            .map(key_of)
+            .fold(0, std::cmp::max))
+    }
+
+    fn cached_records<S: DeserializeOwned + Send + Sync + 'static>(
+        &self,
+        db_file_name: &str,
+    ) -> Result<Arc<Vec<S>>, DBError> {
+        // Taken before reading, so a write landing part way through leaves the
+        // cache entry looking stale rather than fresh.
+        let metadata =
+            fs::metadata(db_file_name).map_err(|e| DBError::finding_table(db_file_name, e))?;
+        let modified = metadata
+            .modified()
+            .map_err(|e| DBError::io(db_file_name, e))?;
+
+        let generation = {
+            let cache = self.cache_lock();
+            match cache.tables.get(db_file_name) {
+                Some(cached) if cached.modified == modified && cached.len == metadata.len() => {
+                    match cached.records.clone().downcast::<Vec<S>>() {
+                        Ok(records) => return Ok(records),
+                        Err(_) => (),
+                    };
+                }
+                _ => (),
+            };
+            cache.generation
+        };
+
+        let records = Arc::new(self.parse_db_records::<S>(db_file_name)?);
+
+        let mut cache = self.cache_lock();
+        if cache.generation == generation {
+            cache.tables.insert(
+                db_file_name.to_string(),
+                CachedTable {
+                    modified: modified,
+                    len: metadata.len(),
+                    records: records.clone(),
+                    indexes: HashMap::new(),
+                },
+            );
+        }
+        Ok(records)
+    }
+
+    fn parse_db_records<S: DeserializeOwned>(&self, db_file_name: &str) -> Result<Vec<S>, DBError> {
+        let mut rdr = csv::Reader::from_reader(
+            File::open(db_file_name).map_err(|e| DBError::finding_table(db_file_name, e))?,
+        );
+        let headers = rdr
+            .headers()
+            .map_err(|e| parsing_error(db_file_name, None, e))?
+            .clone();
         let records = rdr
             .deserialize()
             .into_iter()
             .map(|result| -> Result<S, DBError> {
-                result.map_err(|e| DBError::ParsingRecord(format!("could not read row: {:?}", e)))
+                result.map_err(|e| parsing_error(db_file_name, Some(&headers), e))
             })
             .collect::<Result<Vec<S>, DBError>>()?;
         Ok(records)
     }
+
+    /// Reads a table as plain strings, whether or not its rows still match a
+    /// record type.
+    pub fn read_raw_table(&self, db_file_name: &str) -> Result<RawTable, DBError> {
+        let mut rdr = csv::Reader::from_reader(
+            File::open(db_file_name).map_err(|e| DBError::finding_table(db_file_name, e))?,
+        );
+        let headers = rdr
+            .headers()
+            .map_err(|e| parsing_error(db_file_name, None, e))?
+            .iter()
+            .map(|h| h.to_string())
+            .collect();
+        let rows = rdr
+            .records()
+            .map(|result| {
+                result
+                    .map(|record| record.iter().map(|v| v.to_string()).collect())
+                    .map_err(|e| parsing_error(db_file_name, None, e))
+            })
+            .collect::<Result<Vec<Vec<String>>, DBError>>()?;
+        Ok(RawTable {
+            headers: headers,
+            rows: rows,
+        })
+    }
+
+    fn forget_cached(&self, db_file_names: &[String]) {
+        let mut cache = self.cache_lock();
+        cache.generation += 1;
+        for db_file_name in db_file_names.iter() {
+            cache.tables.remove(db_file_name);
+        }
+    }
+
+    fn cache_lock(&self) -> MutexGuard<'_, Cache> {
+        self.cache.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Finishes whatever transaction was committing when the process last
+    /// stopped.
+    ///
+    /// A journal on disk means every staged table had been fully written, so
+    /// the remaining renames are replayed. Any other temp files left beside
+    /// `table_names`, and a journal that was never renamed into place, belong
+    /// to a transaction that never committed, and are removed.
+    fn recover(&self, table_names: &[&str]) -> Result<(), DBError> {
+        let _lock = COMMIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let mut journal = String::new();
+        match File::open(&self.journal_file_name) {
+            Ok(mut f) => {
+                f.read_to_string(&mut journal)
+                    .map_err(|e| DBError::io(&self.journal_file_name, e))?;
+            }
+            Err(e) => match e.kind() {
+                ErrorKind::NotFound => (),
+                _ => return Err(DBError::io(&self.journal_file_name, e)),
+            },
+        };
+
+        // Each line is a table and the temp file staged for it, followed for
+        // appends by how long the table was before.
+        for line in journal.lines() {
+            let fields: Vec<&str> = line.split('\t').collect();
+            match fields.as_slice() {
+                [table_name, staged_file_name] if Path::new(staged_file_name).exists() => {
+                    fs::rename(staged_file_name, table_name)
+                        .map_err(|e| DBError::io(table_name, e))?;
+                }
+                [table_name, staged_file_name, len] if Path::new(staged_file_name).exists() => {
+                    let len = len.parse::<u64>().map_err(|e| {
+                        DBError::io(
+                            &self.journal_file_name,
+                            io::Error::new(ErrorKind::InvalidData, e),
+                        )
+                    })?;
+                    append_file(table_name, staged_file_name, len)?;
+                }
+                _ => (),
+            };
+        }
+        sync_parent_dir(&self.journal_file_name)?;
+        remove_if_exists(&self.journal_file_name)?;
+        remove_if_exists(&tmp_journal_file_name(&self.journal_file_name))?;
+
+        for table_name in table_names.iter() {
+            remove_stale_tmp_files(table_name)?;
+        }
+        Ok(())
+    }
+}
+
+/// A table's header and rows as read from disk, for reshaping old tables.
+pub struct RawTable {
+    pub headers: Vec<String>,
+    pub rows: Vec<Vec<String>>,
+}
+
+impl RawTable {
+    pub fn column(&self, name: &str) -> Option<usize> {
+        self.headers.iter().position(|h| h == name)
+    }
+
+    /// Appends a column holding `default` in every existing row. A table that
+    /// has never been written (no header yet) is left alone.
+    pub fn add_column(&mut self, name: &str, default: &str) {
+        if self.headers.is_empty() || self.headers.iter().any(|h| h == name) {
+            return;
+        }
+        self.headers.push(name.to_string());
+        for row in self.rows.iter_mut() {
+            row.push(default.to_string());
+        }
+    }
+}
+
+/// The right to write, released on drop.
+pub struct WriteLock {
+    file: File,
+    _guard: MutexGuard<'static, ()>,
+}
+
+impl Drop for WriteLock {
+    fn drop(&mut self) {
+        let _ = self.file.unlock();
+    }
+}
+
+/// Table writes staged by an `Engine`.
+///
+/// Each staged table is written in full to a temp file of its own next to
+/// it, or for an append just the rows to add. On commit a journal naming the
+/// staged tables and their temp files (and how long each appended table
+/// was) is made durable first, and then every temp file is renamed over or
+/// copied onto the end of its table, so a crash part way through is
+/// finished by the next `Engine::new`.
+/// Dropping without committing discards the staged writes.
+pub struct Transaction<'a> {
+    engine: &'a Engine,
+    /// Each staged table, with the temp file it was written to.
+    staged: Vec<(String, String)>,
+    /// Tables in `staged` whose temp file holds rows to append rather than
+    /// the whole table.
+    appended: Vec<String>,
+    committed: bool,
+}
+
+impl<'a> Transaction<'a> {
+    pub fn stage_replace_records<S: Serialize>(
+        &mut self,
+        db_file_name: &str,
+        records: Vec<S>,
+    ) -> Result<(), DBError> {
+        let staged_file_name = self.stage(db_file_name);
+        write_records_to_file(db_file_name, &staged_file_name, records)
+    }
+
+    pub fn stage_raw_table(&mut self, db_file_name: &str, table: RawTable) -> Result<(), DBError> {
+        let staged_file_name = self.stage(db_file_name);
+        write_raw_table_to_file(db_file_name, &staged_file_name, table)
+    }
+
+    /// Stages `records` to be added after the rows already in the table,
+    /// without writing those rows again. A table is either replaced or
+    /// appended to in one transaction, and only appended to once.
+    pub fn stage_append_records<S: Serialize>(
+        &mut self,
+        db_file_name: &str,
+        records: Vec<S>,
+    ) -> Result<(), DBError> {
+        // A table that has never been written needs its header too.
+        let has_header = fs::metadata(db_file_name)
+            .map_err(|e| DBError::finding_table(db_file_name, e))?
+            .len()
+            > 0;
+        let staged_file_name = self.stage(db_file_name);
+        self.appended.push(db_file_name.to_string());
+        let mut writer = csv::WriterBuilder::new()
+            .has_headers(!has_header)
+            .from_writer(
+                File::create(&staged_file_name).map_err(|e| DBError::io(db_file_name, e))?,
+            );
+        for (row, record) in records.into_iter().enumerate() {
+            writer
+                .serialize(record)
+                .map_err(|e| writing_error(db_file_name, Some(row as u64 + 1), e))?;
+        }
+        finish_tmp_writer(db_file_name, writer)
+    }
+
+    /// The temp file the table is staged to, picked the first time it is
+    /// staged in this transaction.
+    fn stage(&mut self, db_file_name: &str) -> String {
+        match self
+            .staged
+            .iter()
+            .find(|(staged, _)| *staged == db_file_name)
+        {
+            Some((_, staged_file_name)) => staged_file_name.clone(),
+            None => {
+                let staged_file_name = tmp_file_name(db_file_name);
+                self.staged
+                    .push((db_file_name.to_string(), staged_file_name.clone()));
+                staged_file_name
+            }
+        }
+    }
+
+    pub fn commit(mut self) -> Result<(), DBError> {
+        if self.staged.is_empty() {
+            self.committed = true;
+            return Ok(());
+        }
+
+        let _lock = COMMIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        // How long each appended table is before its rows are added.
+        let lens = self
+            .staged
+            .iter()
+            .map(|(db_file_name, _)| {
+                if self.appended.contains(db_file_name) {
+                    fs::metadata(db_file_name)
+                        .map(|metadata| Some(metadata.len()))
+                        .map_err(|e| DBError::finding_table(db_file_name, e))
+                } else {
+                    Ok(None)
+                }
+            })
+            .collect::<Result<Vec<Option<u64>>, DBError>>()?;
+
+        // The journal is written beside its real name and renamed into place,
+        // so recovery never sees only some of its lines.
+        let journal_file_name = &self.engine.journal_file_name;
+        let staged_journal_file_name = tmp_journal_file_name(journal_file_name);
+        let mut journal = File::create(&staged_journal_file_name)
+            .map_err(|e| DBError::io(journal_file_name, e))?;
+        journal
+            .write_all(
+                self.staged
+                    .iter()
+                    .zip(lens.iter())
+                    .map(|((db_file_name, staged_file_name), len)| match len {
+                        Some(len) => format!("{}\t{}\t{}", db_file_name, staged_file_name, len),
+                        None => format!("{}\t{}", db_file_name, staged_file_name),
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n")
+                    .as_bytes(),
+            )
+            .and_then(|()| journal.sync_all())
+            .and_then(|()| fs::rename(&staged_journal_file_name, journal_file_name))
+            .map_err(|e| DBError::io(journal_file_name, e))?;
+        sync_parent_dir(journal_file_name)?;
+
+        // From here on the journal will finish the renames if we do not.
+        self.committed = true;
+
+        let renamed = self.staged.iter().zip(lens.iter()).try_for_each(
+            |((db_file_name, staged_file_name), len)| match len {
+                Some(len) => append_file(db_file_name, staged_file_name, *len),
+                None => fs::rename(staged_file_name, db_file_name)
+                    .map_err(|e| DBError::io(db_file_name, e)),
+            },
+        );
+        self.engine.forget_cached(
+            &self
+                .staged
+                .iter()
+                .map(|(db_file_name, _)| db_file_name.clone())
+                .collect::<Vec<String>>(),
+        );
+        renamed?;
+        sync_parent_dir(&self.engine.journal_file_name)?;
+
+        remove_if_exists(&self.engine.journal_file_name)
+    }
+}
+
+impl<'a> Drop for Transaction<'a> {
+    fn drop(&mut self) {
+        if !self.committed {
+            for (_, staged_file_name) in self.staged.iter() {
+                let _ = fs::remove_file(staged_file_name);
+            }
+        }
+    }
+}
+
+fn same_records<S>(cached: &Arc<dyn Any + Send + Sync>, records: &Arc<Vec<S>>) -> bool {
+    Arc::as_ptr(cached) as *const () == Arc::as_ptr(records) as *const ()
+}
+
+fn build_index<S>(records: &[S], key_of: fn(&S) -> u32) -> HashMap<u32, Vec<usize>> {
+    let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (row, record) in records.iter().enumerate() {
+        index.entry(key_of(record)).or_default().push(row);
+    }
+    index
+}
+
+/// A temp file next to the table that no other transaction will use.
+fn tmp_file_name(db_file_name: &str) -> String {
+    format!(
+        "{}.{}-{}.tmp",
+        db_file_name,
+        std::process::id(),
+        NEXT_TMP_FILE.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Where a commit writes its journal before renaming it into place.
+fn tmp_journal_file_name(journal_file_name: &str) -> String {
+    format!("{}.tmp", journal_file_name)
+}
+
+/// Removes the temp files `tmp_file_name` gives for the table, as left by
+/// transactions that never committed.
+fn remove_stale_tmp_files(db_file_name: &str) -> Result<(), DBError> {
+    let path = Path::new(db_file_name);
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let prefix = match path.file_name() {
+        Some(name) => format!("{}.", name.to_string_lossy()),
+        None => return Ok(()),
+    };
+    let dir_name = dir.to_string_lossy();
+    for entry in fs::read_dir(dir).map_err(|e| DBError::io(&dir_name, e))? {
+        let name = entry.map_err(|e| DBError::io(&dir_name, e))?.file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with(&prefix) && name.ends_with(".tmp") {
+            remove_if_exists(&dir.join(name.as_ref()).to_string_lossy())?;
+        }
+    }
+    Ok(())
+}
+
+/// Writes the whole table to its temp file.
+fn write_records_to_file<S: Serialize>(
+    db_file_name: &str,
+    staged_file_name: &str,
+    records: Vec<S>,
+) -> Result<(), DBError> {
+    let mut writer = create_tmp_writer(db_file_name, staged_file_name)?;
+
+    for (row, record) in records.into_iter().enumerate() {
+        writer
+            .serialize(record)
+            .map_err(|e| writing_error(db_file_name, Some(row as u64 + 1), e))?;
+    }
+
+    finish_tmp_writer(db_file_name, writer)
+}
+
+/// Writes the whole table to its temp file.
+fn write_raw_table_to_file(
+    db_file_name: &str,
+    staged_file_name: &str,
+    table: RawTable,
+) -> Result<(), DBError> {
+    let mut writer = create_tmp_writer(db_file_name, staged_file_name)?;
+
+    if !table.headers.is_empty() {
+        writer
+            .write_record(&table.headers)
+            .map_err(|e| writing_error(db_file_name, None, e))?;
+    }
+    for (row, values) in table.rows.iter().enumerate() {
+        writer
+            .write_record(values)
+            .map_err(|e| writing_error(db_file_name, Some(row as u64 + 1), e))?;
+    }
+
+    finish_tmp_writer(db_file_name, writer)
+}
+
+fn create_tmp_writer(
+    db_file_name: &str,
+    staged_file_name: &str,
+) -> Result<csv::Writer<File>, DBError> {
+    let file = File::create(staged_file_name).map_err(|e| DBError::io(db_file_name, e))?;
+    Ok(csv::Writer::from_writer(file))
+}
+
+fn finish_tmp_writer(db_file_name: &str, writer: csv::Writer<File>) -> Result<(), DBError> {
+    let file = writer
+        .into_inner()
+        .map_err(|e| DBError::io(db_file_name, e.into_error()))?;
+    file.sync_all().map_err(|e| DBError::io(db_file_name, e))
+}
+
+/// Turns a csv read error into one naming the table, row and (when the
+/// headers are known) field it happened at.
+fn parsing_error(
+    db_file_name: &str,
+    headers: Option<&csv::StringRecord>,
+    e: csv::Error,
+) -> DBError {
+    let (row, field) = match e.kind() {
+        csv::ErrorKind::Deserialize { pos, err } => (
+            pos.as_ref().map(|pos| pos.record()),
+            match (headers, err.field()) {
+                (Some(headers), Some(index)) => headers.get(index as usize).map(|h| h.to_string()),
+                _ => None,
+            },
+        ),
+        _ => (e.position().map(|pos| pos.record()), None),
+    };
+    DBError::ParsingRecord {
+        table: display_name(db_file_name),
+        row: row,
+        field: field,
+        cause: Cause::Csv(e),
+    }
+}
+
+fn writing_error(db_file_name: &str, row: Option<u64>, e: csv::Error) -> DBError {
+    DBError::WritingRecord {
+        table: display_name(db_file_name),
+        row: row,
+        cause: Cause::Csv(e),
+    }
+}
+
+/// Puts the rows staged in `staged_file_name` after the first `len` bytes of
+/// the table, dropping anything past those left by an earlier try, and then
+/// removes the temp file.
+fn append_file(db_file_name: &str, staged_file_name: &str, len: u64) -> Result<(), DBError> {
+    let mut staged = File::open(staged_file_name).map_err(|e| DBError::io(db_file_name, e))?;
+    let mut table = OpenOptions::new()
+        .write(true)
+        .open(db_file_name)
+        .map_err(|e| DBError::finding_table(db_file_name, e))?;
+    table
+        .set_len(len)
+        .and_then(|()| table.seek(SeekFrom::Start(len)))
+        .and_then(|_| io::copy(&mut staged, &mut table))
+        .and_then(|_| table.sync_all())
+        .map_err(|e| DBError::io(db_file_name, e))?;
+    remove_if_exists(staged_file_name)
+}
+
+fn remove_if_exists(file_name: &str) -> Result<(), DBError> {
+    match fs::remove_file(file_name) {
+        Ok(()) => Ok(()),
+        Err(e) => match e.kind() {
+            ErrorKind::NotFound => Ok(()),
+            _ => Err(DBError::io(file_name, e)),
+        },
+    }
+}
+
+/// Makes renames within the directory durable across a crash.
+fn sync_parent_dir(file_name: &str) -> Result<(), DBError> {
+    match Path::new(file_name).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)
+            .and_then(|d| d.sync_all())
+            .map_err(|e| DBError::io(file_name, e)),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::common::TempDir;
+    use serde::Deserialize;
+
+    #[derive(Serialize, Deserialize, Clone)]
+    struct Row {
+        id: u32,
+        name: String,
+    }
+
+    fn row(id: u32, name: &str) -> Row {
+        Row {
+            id: id,
+            name: name.to_string(),
+        }
+    }
+
+    fn engine_in(dir: &TempDir, table_names: &[&str]) -> Engine {
+        Engine::new(
+            dir.path().join("journal").to_string_lossy().into_owned(),
+            dir.path().join("lock").to_string_lossy().into_owned(),
+            table_names,
+        )
+        .unwrap()
+    }
+
+    fn names(engine: &Engine, table_name: &str) -> Vec<String> {
+        engine
+            .read_db_records::<Row>(table_name)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.name)
+            .collect()
+    }
+
+    #[test]
+    fn appends_leave_the_rows_before_them_alone() {
+        let dir = TempDir::new("engine-append");
+        let table_name = dir.path().join("rows.csv").to_string_lossy().into_owned();
+        let engine = engine_in(&dir, &[&table_name]);
+
+        // The first append to an empty table writes its header too.
+        engine
+            .write_append_records(&table_name, vec![row(1, "a")])
+            .unwrap();
+        let before = fs::read(&table_name).unwrap();
+        engine
+            .write_append_records(&table_name, vec![row(2, "b"), row(3, "c")])
+            .unwrap();
+
+        assert!(fs::read(&table_name).unwrap().starts_with(&before));
+        assert_eq!(names(&engine, &table_name), vec!["a", "b", "c"]);
+    }
+
+    #[test]
+    fn finishes_an_append_cut_off_part_way() {
+        let dir = TempDir::new("engine-recover-append");
+        let table_name = dir.path().join("rows.csv").to_string_lossy().into_owned();
+        let journal_file_name = dir.path().join("journal").to_string_lossy().into_owned();
+        {
+            let engine = engine_in(&dir, &[&table_name]);
+            engine
+                .write_replace_records(&table_name, vec![row(1, "a")])
+                .unwrap();
+        }
+
+        // As left by a commit that stopped while copying the staged rows.
+        let len = fs::metadata(&table_name).unwrap().len();
+        let staged_file_name = format!("{}.0-0.tmp", table_name);
+        fs::write(&staged_file_name, "2,b\n3,c\n").unwrap();
+        OpenOptions::new()
+            .append(true)
+            .open(&table_name)
+            .unwrap()
+            .write_all(b"2,")
+            .unwrap();
+        fs::write(
+            &journal_file_name,
+            format!("{}\t{}\t{}", table_name, staged_file_name, len),
+        )
+        .unwrap();
+
+        let engine = engine_in(&dir, &[&table_name]);
+        assert_eq!(names(&engine, &table_name), vec!["a", "b", "c"]);
+        assert!(!Path::new(&staged_file_name).exists());
+        assert!(!Path::new(&journal_file_name).exists());
+    }
+
+    #[test]
+    fn finishes_replacing_the_tables_a_commit_had_not_reached() {
+        let dir = TempDir::new("engine-recover-replace");
+        let first = dir.path().join("first.csv").to_string_lossy().into_owned();
+        let second = dir.path().join("second.csv").to_string_lossy().into_owned();
+        let journal_file_name = dir.path().join("journal").to_string_lossy().into_owned();
+        {
+            let engine = engine_in(&dir, &[&first, &second]);
+            engine
+                .write_replace_records(&first, vec![row(1, "a")])
+                .unwrap();
+            engine
+                .write_replace_records(&second, vec![row(1, "a")])
+                .unwrap();
+        }
+
+        // As left by a commit that stopped after renaming only the first of
+        // its two tables.
+        let first_staged_file_name = format!("{}.0-0.tmp", first);
+        let second_staged_file_name = format!("{}.0-1.tmp", second);
+        fs::write(&first, "id,name\n1,b\n").unwrap();
+        fs::write(&second_staged_file_name, "id,name\n1,b\n").unwrap();
+        fs::write(
+            &journal_file_name,
+            format!(
+                "{}\t{}\n{}\t{}",
+                first, first_staged_file_name, second, second_staged_file_name
+            ),
+        )
+        .unwrap();
+
+        let engine = engine_in(&dir, &[&first, &second]);
+        assert_eq!(names(&engine, &first), vec!["b"]);
+        assert_eq!(names(&engine, &second), vec!["b"]);
+        assert!(!Path::new(&second_staged_file_name).exists());
+        assert!(!Path::new(&journal_file_name).exists());
+    }
+
+    #[test]
+    fn ignores_a_journal_never_put_in_place() {
+        let dir = TempDir::new("engine-recover-torn");
+        let table_name = dir.path().join("rows.csv").to_string_lossy().into_owned();
+        let journal_file_name = dir.path().join("journal").to_string_lossy().into_owned();
+        {
+            let engine = engine_in(&dir, &[&table_name]);
+            engine
+                .write_replace_records(&table_name, vec![row(1, "a")])
+                .unwrap();
+        }
+
+        // As left by a commit that stopped while writing its journal.
+        let staged_file_name = format!("{}.0-0.tmp", table_name);
+        fs::write(&staged_file_name, "id,name\n1,b\n").unwrap();
+        fs::write(
+            tmp_journal_file_name(&journal_file_name),
+            format!("{}\t{}", table_name, staged_file_name),
+        )
+        .unwrap();
+
+        let engine = engine_in(&dir, &[&table_name]);
+        assert_eq!(names(&engine, &table_name), vec!["a"]);
+        assert!(!Path::new(&staged_file_name).exists());
+        assert!(!Path::new(&tmp_journal_file_name(&journal_file_name)).exists());
+    }
 }
@@ -0,0 +1,34 @@
+CREATE TABLE games (
+    id INTEGER PRIMARY KEY,
+    map_id INTEGER NOT NULL,
+    cursor_x INTEGER NOT NULL,
+    cursor_y INTEGER NOT NULL
+);
+INSERT INTO games VALUES (1, 2, 2, 1);
+CREATE TABLE maps (
+    id INTEGER PRIMARY KEY,
+    default_terrain TEXT NOT NULL,
+    hint_max_x INTEGER NOT NULL,
+    hint_max_y INTEGER NOT NULL
+);
+INSERT INTO maps VALUES (2, 'dirt', 4, 3);
+CREATE TABLE tiles (
+    id INTEGER PRIMARY KEY,
+    map_id INTEGER NOT NULL,
+    terrain TEXT NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL
+);
+CREATE UNIQUE INDEX tiles_by_map_position ON tiles (map_id, x, y);
+INSERT INTO tiles VALUES (1, 2, 'rock', 3, 2);
+CREATE TABLE characters (
+    id INTEGER PRIMARY KEY,
+    game_id INTEGER NOT NULL,
+    character TEXT NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL
+);
+CREATE UNIQUE INDEX characters_by_game_position ON characters (game_id, x, y);
+INSERT INTO characters VALUES (1, 2, 'knight', 0, 0);
+INSERT INTO characters VALUES (2, 2, 'mage', 1, 1);
+PRAGMA user_version = 1;
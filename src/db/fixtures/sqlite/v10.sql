@@ -0,0 +1,48 @@
+CREATE TABLE games (
+    id INTEGER PRIMARY KEY,
+    map_id INTEGER NOT NULL,
+    cursor_x INTEGER NOT NULL,
+    cursor_y INTEGER NOT NULL,
+    version INTEGER NOT NULL DEFAULT 0,
+    selected_unit_id INTEGER,
+    turn INTEGER NOT NULL DEFAULT 1,
+    active_faction TEXT NOT NULL DEFAULT 'player',
+    rng_seed INTEGER NOT NULL DEFAULT 0,
+    rng_draws INTEGER NOT NULL DEFAULT 0,
+    objective TEXT NOT NULL DEFAULT 'rout',
+    result TEXT
+);
+INSERT INTO games VALUES (1, 2, 2, 1, 3, 2, 4, 'enemy', 99, 5, 'survive-6', 'won');
+CREATE TABLE maps (
+    id INTEGER PRIMARY KEY,
+    default_terrain TEXT NOT NULL,
+    hint_max_x INTEGER NOT NULL,
+    hint_max_y INTEGER NOT NULL
+);
+INSERT INTO maps VALUES (2, 'dirt', 4, 3);
+CREATE TABLE tiles (
+    id INTEGER PRIMARY KEY,
+    map_id INTEGER NOT NULL,
+    terrain TEXT NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL
+);
+CREATE UNIQUE INDEX tiles_by_map_position ON tiles (map_id, x, y);
+INSERT INTO tiles VALUES (1, 2, 'rock', 3, 2);
+CREATE TABLE characters (
+    id INTEGER PRIMARY KEY,
+    game_id INTEGER NOT NULL,
+    character TEXT NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL,
+    name TEXT NOT NULL DEFAULT '',
+    faction TEXT NOT NULL DEFAULT 'player',
+    moved INTEGER NOT NULL DEFAULT 0,
+    acted INTEGER NOT NULL DEFAULT 0,
+    hp INTEGER NOT NULL DEFAULT 0,
+    behavior TEXT NOT NULL DEFAULT 'aggressive'
+);
+CREATE UNIQUE INDEX characters_by_game_position ON characters (game_id, x, y);
+INSERT INTO characters VALUES (1, 1, 'knight', 0, 0, 'Gawain', 'player', 1, 0, 7, 'defensive');
+INSERT INTO characters VALUES (2, 1, 'mage', 1, 1, 'Merlin', 'enemy', 0, 1, 3, 'guard');
+PRAGMA user_version = 10;
@@ -0,0 +1,79 @@
+CREATE TABLE games (
+    id INTEGER PRIMARY KEY,
+    map_id INTEGER NOT NULL,
+    cursor_x INTEGER NOT NULL,
+    cursor_y INTEGER NOT NULL,
+    version INTEGER NOT NULL DEFAULT 0,
+    selected_unit_id INTEGER,
+    turn INTEGER NOT NULL DEFAULT 1,
+    active_faction TEXT NOT NULL DEFAULT 'player',
+    rng_seed INTEGER NOT NULL DEFAULT 0,
+    rng_draws INTEGER NOT NULL DEFAULT 0,
+    objective TEXT NOT NULL DEFAULT 'rout',
+    result TEXT
+);
+INSERT INTO games VALUES (1, 2, 2, 1, 3, 2, 4, 'enemy', 99, 5, 'survive-6', 'won');
+CREATE TABLE maps (
+    id INTEGER PRIMARY KEY,
+    default_terrain TEXT NOT NULL,
+    hint_max_x INTEGER NOT NULL,
+    hint_max_y INTEGER NOT NULL,
+    cursor_x INTEGER NOT NULL DEFAULT 0,
+    cursor_y INTEGER NOT NULL DEFAULT 0,
+    name TEXT NOT NULL DEFAULT ''
+);
+INSERT INTO maps VALUES (2, 'dirt', 4, 3, 1, 2, 'Forest Pass');
+CREATE TABLE tiles (
+    id INTEGER PRIMARY KEY,
+    map_id INTEGER NOT NULL,
+    terrain TEXT NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL
+);
+CREATE UNIQUE INDEX tiles_by_map_position ON tiles (map_id, x, y);
+INSERT INTO tiles VALUES (1, 2, 'rock', 3, 2);
+CREATE TABLE characters (
+    id INTEGER PRIMARY KEY,
+    game_id INTEGER NOT NULL,
+    character TEXT NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL,
+    name TEXT NOT NULL DEFAULT '',
+    faction TEXT NOT NULL DEFAULT 'player',
+    moved INTEGER NOT NULL DEFAULT 0,
+    acted INTEGER NOT NULL DEFAULT 0,
+    hp INTEGER NOT NULL DEFAULT 0,
+    behavior TEXT NOT NULL DEFAULT 'aggressive'
+);
+CREATE UNIQUE INDEX characters_by_game_position ON characters (game_id, x, y);
+INSERT INTO characters VALUES (1, 1, 'knight', 0, 0, 'Gawain', 'player', 1, 0, 7, 'defensive');
+INSERT INTO characters VALUES (2, 1, 'mage', 1, 1, 'Merlin', 'enemy', 0, 1, 3, 'guard');
+CREATE TABLE events (
+    id INTEGER PRIMARY KEY,
+    game_id INTEGER NOT NULL,
+    turn INTEGER NOT NULL,
+    event TEXT NOT NULL
+);
+CREATE INDEX events_by_game ON events (game_id, id);
+INSERT INTO events VALUES (1, 1, 2, 'move/0/0/1/0');
+CREATE TABLE edits (
+    id INTEGER PRIMARY KEY,
+    game_id INTEGER NOT NULL,
+    undo TEXT NOT NULL,
+    redo TEXT NOT NULL,
+    undone INTEGER NOT NULL
+);
+CREATE INDEX edits_by_game ON edits (game_id, id);
+INSERT INTO edits VALUES (1, 1, 'unset-terrain/3/2', 'terrain/3/2/rock', 1);
+CREATE TABLE starting_units (
+    id INTEGER PRIMARY KEY,
+    map_id INTEGER NOT NULL,
+    character TEXT NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL,
+    faction TEXT NOT NULL,
+    behavior TEXT NOT NULL
+);
+CREATE UNIQUE INDEX starting_units_by_map_position ON starting_units (map_id, x, y);
+INSERT INTO starting_units VALUES (1, 2, 'thief', 2, 2, 'neutral', 'guard');
+PRAGMA user_version = 15;
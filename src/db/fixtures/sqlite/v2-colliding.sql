@@ -0,0 +1,29 @@
+CREATE TABLE games (
+    id INTEGER PRIMARY KEY,
+    map_id INTEGER NOT NULL,
+    cursor_x INTEGER NOT NULL,
+    cursor_y INTEGER NOT NULL,
+    version INTEGER NOT NULL DEFAULT 0
+);
+INSERT INTO games VALUES (1, 2, 0, 0, 0);
+INSERT INTO games VALUES (2, 1, 0, 0, 0);
+INSERT INTO games VALUES (3, 1, 0, 0, 0);
+CREATE TABLE maps (
+    id INTEGER PRIMARY KEY,
+    default_terrain TEXT NOT NULL,
+    hint_max_x INTEGER NOT NULL,
+    hint_max_y INTEGER NOT NULL
+);
+INSERT INTO maps VALUES (1, 'grass', 4, 4);
+INSERT INTO maps VALUES (2, 'grass', 4, 4);
+CREATE TABLE characters (
+    id INTEGER PRIMARY KEY,
+    game_id INTEGER NOT NULL,
+    character TEXT NOT NULL,
+    x INTEGER NOT NULL,
+    y INTEGER NOT NULL
+);
+CREATE UNIQUE INDEX characters_by_game_position ON characters (game_id, x, y);
+INSERT INTO characters VALUES (1, 1, 'knight', 0, 0);
+INSERT INTO characters VALUES (2, 2, 'mage', 0, 0);
+PRAGMA user_version = 2;
@@ -0,0 +1,198 @@
+use std::sync::Mutex;
+
+use super::common::DBError;
+use super::records::{
+    DBCharacter, DBEdit, DBEvent, DBGame, DBIdCounter, DBMap, DBStartingUnit, DBTileLine,
+};
+use super::storage::{next_id, Storage};
+use super::transaction::{self, TableWrites};
+
+/// Storage that only lives as long as the process, starting out empty.
+pub struct MemoryStorage {
+    games: Table<DBGame>,
+    maps: Table<DBMap>,
+    tile_lines: Table<DBTileLine>,
+    characters: Table<DBCharacter>,
+    events: Table<DBEvent>,
+    edits: Table<DBEdit>,
+    starting_units: Table<DBStartingUnit>,
+    id_counters: Table<DBIdCounter>,
+    write_lock: Mutex<()>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        MemoryStorage {
+            games: Table::new(),
+            maps: Table::new(),
+            tile_lines: Table::new(),
+            characters: Table::new(),
+            events: Table::new(),
+            edits: Table::new(),
+            starting_units: Table::new(),
+            id_counters: Table::new(),
+            write_lock: Mutex::new(()),
+        }
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn read_games(&self) -> Result<Vec<DBGame>, DBError> {
+        Ok(self.games.read())
+    }
+
+    fn write_games(&self, records: Vec<DBGame>) -> Result<(), DBError> {
+        self.games.write(records);
+        Ok(())
+    }
+
+    fn read_maps(&self) -> Result<Vec<DBMap>, DBError> {
+        Ok(self.maps.read())
+    }
+
+    fn write_maps(&self, records: Vec<DBMap>) -> Result<(), DBError> {
+        self.maps.write(records);
+        Ok(())
+    }
+
+    fn read_tile_lines(&self) -> Result<Vec<DBTileLine>, DBError> {
+        Ok(self.tile_lines.read())
+    }
+
+    fn write_tile_lines(&self, records: Vec<DBTileLine>) -> Result<(), DBError> {
+        self.tile_lines.write(records);
+        Ok(())
+    }
+
+    fn read_characters(&self) -> Result<Vec<DBCharacter>, DBError> {
+        Ok(self.characters.read())
+    }
+
+    fn write_characters(&self, records: Vec<DBCharacter>) -> Result<(), DBError> {
+        self.characters.write(records);
+        Ok(())
+    }
+
+    fn read_events(&self) -> Result<Vec<DBEvent>, DBError> {
+        Ok(self.events.read())
+    }
+
+    fn write_events(&self, records: Vec<DBEvent>) -> Result<(), DBError> {
+        self.events.write(records);
+        Ok(())
+    }
+
+    fn read_edits(&self) -> Result<Vec<DBEdit>, DBError> {
+        Ok(self.edits.read())
+    }
+
+    fn write_edits(&self, records: Vec<DBEdit>) -> Result<(), DBError> {
+        self.edits.write(records);
+        Ok(())
+    }
+
+    fn read_starting_units(&self) -> Result<Vec<DBStartingUnit>, DBError> {
+        Ok(self.starting_units.read())
+    }
+
+    fn write_starting_units(&self, records: Vec<DBStartingUnit>) -> Result<(), DBError> {
+        self.starting_units.write(records);
+        Ok(())
+    }
+
+    fn read_id_counters(&self) -> Result<Vec<DBIdCounter>, DBError> {
+        Ok(self.id_counters.read())
+    }
+
+    fn write_id_counters(&self, records: Vec<DBIdCounter>) -> Result<(), DBError> {
+        self.id_counters.write(records);
+        Ok(())
+    }
+
+    fn write_tables(&self, writes: TableWrites) -> Result<(), DBError> {
+        // Hold every table's lock before changing any, always in this order.
+        let mut games = self.games.records.lock().unwrap();
+        let mut maps = self.maps.records.lock().unwrap();
+        let mut tile_lines = self.tile_lines.records.lock().unwrap();
+        let mut characters = self.characters.records.lock().unwrap();
+        let mut events = self.events.records.lock().unwrap();
+        let mut edits = self.edits.records.lock().unwrap();
+        let mut starting_units = self.starting_units.records.lock().unwrap();
+        let mut id_counters = self.id_counters.records.lock().unwrap();
+
+        match writes.games {
+            Some(records) => *games = records,
+            None => (),
+        };
+        match writes.maps {
+            Some(records) => *maps = records,
+            None => (),
+        };
+        match writes.tile_lines {
+            Some(records) => *tile_lines = records,
+            None => (),
+        };
+        match writes.characters {
+            Some(records) => *characters = records,
+            None => (),
+        };
+        match writes.events {
+            Some(records) => *events = records,
+            None => (),
+        };
+        events.extend(writes.appended_events);
+        match writes.edits {
+            Some(records) => *edits = records,
+            None => (),
+        };
+        match writes.starting_units {
+            Some(records) => *starting_units = records,
+            None => (),
+        };
+        match writes.id_counters {
+            Some(records) => *id_counters = records,
+            None => (),
+        };
+        Ok(())
+    }
+
+    fn transaction(
+        &self,
+        f: &mut dyn FnMut(&dyn Storage) -> Result<(), DBError>,
+    ) -> Result<(), DBError> {
+        let _lock = self.write_lock.lock().unwrap();
+        transaction::staged_transaction(self, f)
+    }
+
+    fn next_event_id(&self) -> Result<u32, DBError> {
+        let events = self.events.records.lock().unwrap();
+        Ok(next_id(events.iter().map(|r| r.id)))
+    }
+
+    fn append_event(&self, record: DBEvent) -> Result<(), DBError> {
+        let mut events = self.events.records.lock().unwrap();
+        let id = next_id(events.iter().map(|r| r.id));
+        events.push(DBEvent { id: id, ..record });
+        Ok(())
+    }
+}
+
+struct Table<T> {
+    records: Mutex<Vec<T>>,
+}
+
+impl<T: Clone> Table<T> {
+    fn new() -> Self {
+        Table {
+            records: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn read(&self) -> Vec<T> {
+        self.records.lock().unwrap().clone()
+    }
+
+    fn write(&self, records: Vec<T>) {
+        *self.records.lock().unwrap() = records;
+    }
+}
@@ -0,0 +1,932 @@
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+
+use rusqlite::{params, Connection};
+
+use crate::models;
+
+use super::common::DBError;
+use super::engine;
+use super::records::DBSchema;
+
+/// The schema version this build reads and writes.
+///
+/// Bump it together with a new entry in `MIGRATIONS` whenever a record type
+/// in `records` changes shape.
+pub const SCHEMA_VERSION: u32 = 17;
+
+#[derive(Clone, Copy)]
+pub enum Table {
+    Games,
+    Maps,
+    TileLines,
+    Characters,
+    Events,
+    Edits,
+    StartingUnits,
+    IdCounters,
+}
+
+/// How to bring one table written at `to_version - 1` up to `to_version`.
+///
+/// `csv` may read other tables to work out the new rows, but only `table` is
+/// written back.
+struct Migration {
+    to_version: u32,
+    table: Table,
+    csv: fn(&mut CsvTables) -> Result<(), DBError>,
+    sqlite: &'static str,
+}
+
+/// Every schema change since version 1 (the original four csv tables), in
+/// order.
+const MIGRATIONS: &'static [Migration] = &[
+    // games get a version for optimistic locking
+    Migration {
+        to_version: 2,
+        table: Table::Games,
+        csv: |tables| {
+            tables.get(Table::Games)?.add_column("version", "0");
+            Ok(())
+        },
+        sqlite: "ALTER TABLE games ADD COLUMN version INTEGER NOT NULL DEFAULT 0",
+    },
+    // characters were stored under their game's map id; key them by the game
+    // that map belongs to (via negative ids, so no two rows ever collide
+    // half way through). Rows whose map no single game uses are left alone.
+    // A character landing on one left alone in its game fails the upgrade
+    // rather than losing either of them.
+    Migration {
+        to_version: 3,
+        table: Table::Characters,
+        csv: key_characters_by_game_csv,
+        sqlite: "UPDATE characters
+                SET game_id = -(SELECT id FROM games WHERE games.map_id = characters.game_id)
+                WHERE (SELECT COUNT(*) FROM games WHERE games.map_id = characters.game_id) = 1;
+            UPDATE characters SET game_id = -game_id WHERE game_id < 0",
+    },
+    // characters get a name, numbering each class within its game by id
+    Migration {
+        to_version: 4,
+        table: Table::Characters,
+        csv: name_characters_csv,
+        sqlite: "ALTER TABLE characters ADD COLUMN name TEXT NOT NULL DEFAULT '';
+            UPDATE characters SET name =
+                upper(substr(character, 1, 1)) || substr(character, 2) || ' ' || (
+                    SELECT COUNT(*) FROM characters AS earlier
+                    WHERE earlier.game_id = characters.game_id
+                        AND earlier.character = characters.character
+                        AND earlier.id <= characters.id
+                )",
+    },
+    // games remember which unit is about to move
+    Migration {
+        to_version: 5,
+        table: Table::Games,
+        csv: |tables| {
+            tables.get(Table::Games)?.add_column("selected_unit_id", "");
+            Ok(())
+        },
+        sqlite: "ALTER TABLE games ADD COLUMN selected_unit_id INTEGER",
+    },
+    // games track the turn and whose it is, starting on the player's first
+    Migration {
+        to_version: 6,
+        table: Table::Games,
+        csv: |tables| {
+            let games = tables.get(Table::Games)?;
+            games.add_column("turn", "1");
+            games.add_column("active_faction", "Player");
+            Ok(())
+        },
+        sqlite: "ALTER TABLE games ADD COLUMN turn INTEGER NOT NULL DEFAULT 1;
+            ALTER TABLE games ADD COLUMN active_faction TEXT NOT NULL DEFAULT 'player'",
+    },
+    // characters belong to a faction (the player's, until told otherwise) and
+    // remember whether they have moved and acted this turn
+    Migration {
+        to_version: 6,
+        table: Table::Characters,
+        csv: |tables| {
+            let characters = tables.get(Table::Characters)?;
+            characters.add_column("faction", "Player");
+            characters.add_column("moved", "false");
+            characters.add_column("acted", "false");
+            Ok(())
+        },
+        sqlite: "ALTER TABLE characters ADD COLUMN faction TEXT NOT NULL DEFAULT 'player';
+            ALTER TABLE characters ADD COLUMN moved INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE characters ADD COLUMN acted INTEGER NOT NULL DEFAULT 0",
+    },
+    // characters get hp, starting at full health for their class
+    Migration {
+        to_version: 7,
+        table: Table::Characters,
+        csv: give_characters_hp_csv,
+        sqlite: "ALTER TABLE characters ADD COLUMN hp INTEGER NOT NULL DEFAULT 0;
+            UPDATE characters SET hp = CASE character
+                WHEN 'knight' THEN 20
+                WHEN 'mage' THEN 14
+                WHEN 'thief' THEN 16
+                ELSE 1
+            END",
+    },
+    // games get a random number generator, seeded with their id
+    Migration {
+        to_version: 8,
+        table: Table::Games,
+        csv: seed_games_csv,
+        sqlite: "ALTER TABLE games ADD COLUMN rng_seed INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE games ADD COLUMN rng_draws INTEGER NOT NULL DEFAULT 0;
+            UPDATE games SET rng_seed = id",
+    },
+    // characters get a behavior for when the computer plays them
+    Migration {
+        to_version: 9,
+        table: Table::Characters,
+        csv: |tables| {
+            tables
+                .get(Table::Characters)?
+                .add_column("behavior", "Aggressive");
+            Ok(())
+        },
+        sqlite: "ALTER TABLE characters ADD COLUMN behavior TEXT NOT NULL DEFAULT 'aggressive'",
+    },
+    // games get an objective, routing the enemy until set otherwise, and a
+    // result once they are over
+    Migration {
+        to_version: 10,
+        table: Table::Games,
+        csv: |tables| {
+            let games = tables.get(Table::Games)?;
+            games.add_column("objective", "rout");
+            games.add_column("result", "");
+            Ok(())
+        },
+        sqlite: "ALTER TABLE games ADD COLUMN objective TEXT NOT NULL DEFAULT 'rout';
+            ALTER TABLE games ADD COLUMN result TEXT",
+    },
+    // games get a log of events; the csv file starts out empty like any
+    // other missing table
+    Migration {
+        to_version: 11,
+        table: Table::Events,
+        csv: |_tables| Ok(()),
+        sqlite: "CREATE TABLE IF NOT EXISTS events (
+                id INTEGER PRIMARY KEY,
+                game_id INTEGER NOT NULL,
+                turn INTEGER NOT NULL,
+                event TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS events_by_game ON events (game_id, id)",
+    },
+    // games get a history of edits that can be undone and redone
+    Migration {
+        to_version: 12,
+        table: Table::Edits,
+        csv: |_tables| Ok(()),
+        sqlite: "CREATE TABLE IF NOT EXISTS edits (
+                id INTEGER PRIMARY KEY,
+                game_id INTEGER NOT NULL,
+                undo TEXT NOT NULL,
+                redo TEXT NOT NULL,
+                undone INTEGER NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS edits_by_game ON edits (game_id, id)",
+    },
+    // maps get a cursor of their own, for editing them outside any game
+    Migration {
+        to_version: 13,
+        table: Table::Maps,
+        csv: |tables| {
+            let maps = tables.get(Table::Maps)?;
+            maps.add_column("cursor_x", "0");
+            maps.add_column("cursor_y", "0");
+            Ok(())
+        },
+        sqlite: "ALTER TABLE maps ADD COLUMN cursor_x INTEGER NOT NULL DEFAULT 0;
+            ALTER TABLE maps ADD COLUMN cursor_y INTEGER NOT NULL DEFAULT 0",
+    },
+    // maps get characters to start games added from them with
+    Migration {
+        to_version: 14,
+        table: Table::StartingUnits,
+        csv: |_tables| Ok(()),
+        sqlite: "CREATE TABLE IF NOT EXISTS starting_units (
+                id INTEGER PRIMARY KEY,
+                map_id INTEGER NOT NULL,
+                character TEXT NOT NULL,
+                x INTEGER NOT NULL,
+                y INTEGER NOT NULL,
+                faction TEXT NOT NULL,
+                behavior TEXT NOT NULL
+            );
+            CREATE UNIQUE INDEX IF NOT EXISTS starting_units_by_map_position
+                ON starting_units (map_id, x, y)",
+    },
+    // maps get a name, left empty for the ones already made
+    Migration {
+        to_version: 15,
+        table: Table::Maps,
+        csv: |tables| {
+            tables.get(Table::Maps)?.add_column("name", "");
+            Ok(())
+        },
+        sqlite: "ALTER TABLE maps ADD COLUMN name TEXT NOT NULL DEFAULT ''",
+    },
+    // maps get an anchor for the editor's brushes, unset to start with
+    Migration {
+        to_version: 16,
+        table: Table::Maps,
+        csv: |tables| {
+            let maps = tables.get(Table::Maps)?;
+            maps.add_column("anchor_x", "");
+            maps.add_column("anchor_y", "");
+            Ok(())
+        },
+        sqlite: "ALTER TABLE maps ADD COLUMN anchor_x INTEGER;
+            ALTER TABLE maps ADD COLUMN anchor_y INTEGER",
+    },
+    // the tables rows are deleted from stop handing a deleted row's id to a
+    // new one: csv keeps a counter per table, starting from the highest id
+    // each has now, and sqlite rebuilds them as AUTOINCREMENT, which does the
+    // same
+    Migration {
+        to_version: 17,
+        table: Table::IdCounters,
+        csv: count_ids_csv,
+        sqlite: "CREATE TABLE tiles_new (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                map_id INTEGER NOT NULL,
+                terrain TEXT NOT NULL,
+                x INTEGER NOT NULL,
+                y INTEGER NOT NULL
+            );
+            INSERT INTO tiles_new (id, map_id, terrain, x, y)
+                SELECT id, map_id, terrain, x, y FROM tiles;
+            DROP TABLE tiles;
+            ALTER TABLE tiles_new RENAME TO tiles;
+            CREATE UNIQUE INDEX tiles_by_map_position ON tiles (map_id, x, y);
+
+            CREATE TABLE characters_new (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                game_id INTEGER NOT NULL,
+                character TEXT NOT NULL,
+                x INTEGER NOT NULL,
+                y INTEGER NOT NULL,
+                name TEXT NOT NULL DEFAULT '',
+                faction TEXT NOT NULL DEFAULT 'player',
+                moved INTEGER NOT NULL DEFAULT 0,
+                acted INTEGER NOT NULL DEFAULT 0,
+                hp INTEGER NOT NULL DEFAULT 0,
+                behavior TEXT NOT NULL DEFAULT 'aggressive'
+            );
+            INSERT INTO characters_new (id, game_id, character, x, y, name, faction, moved,
+                    acted, hp, behavior)
+                SELECT id, game_id, character, x, y, name, faction, moved, acted, hp, behavior
+                FROM characters;
+            DROP TABLE characters;
+            ALTER TABLE characters_new RENAME TO characters;
+            CREATE UNIQUE INDEX characters_by_game_position ON characters (game_id, x, y);
+
+            CREATE TABLE edits_new (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                game_id INTEGER NOT NULL,
+                undo TEXT NOT NULL,
+                redo TEXT NOT NULL,
+                undone INTEGER NOT NULL
+            );
+            INSERT INTO edits_new (id, game_id, undo, redo, undone)
+                SELECT id, game_id, undo, redo, undone FROM edits;
+            DROP TABLE edits;
+            ALTER TABLE edits_new RENAME TO edits;
+            CREATE INDEX edits_by_game ON edits (game_id, id);
+
+            CREATE TABLE starting_units_new (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                map_id INTEGER NOT NULL,
+                character TEXT NOT NULL,
+                x INTEGER NOT NULL,
+                y INTEGER NOT NULL,
+                faction TEXT NOT NULL,
+                behavior TEXT NOT NULL
+            );
+            INSERT INTO starting_units_new (id, map_id, character, x, y, faction, behavior)
+                SELECT id, map_id, character, x, y, faction, behavior FROM starting_units;
+            DROP TABLE starting_units;
+            ALTER TABLE starting_units_new RENAME TO starting_units;
+            CREATE UNIQUE INDEX starting_units_by_map_position
+                ON starting_units (map_id, x, y)",
+    },
+];
+
+fn count_ids_csv(tables: &mut CsvTables) -> Result<(), DBError> {
+    let mut rows = Vec::new();
+    for &(table, name) in [
+        (Table::TileLines, "tiles"),
+        (Table::Characters, "characters"),
+        (Table::Edits, "edits"),
+        (Table::StartingUnits, "starting_units"),
+    ]
+    .iter()
+    {
+        let records = tables.get(table)?;
+        let last_id = match records.column("id") {
+            Some(id) => records
+                .rows
+                .iter()
+                .filter_map(|row| row[id].parse::<u32>().ok())
+                .max(),
+            None => None,
+        };
+        match last_id {
+            Some(last_id) => rows.push(vec![name.to_string(), last_id.to_string()]),
+            None => (),
+        };
+    }
+
+    let id_counters = tables.get(Table::IdCounters)?;
+    id_counters.headers = vec!["table".to_string(), "last_id".to_string()];
+    id_counters.rows = rows;
+    Ok(())
+}
+
+fn seed_games_csv(tables: &mut CsvTables) -> Result<(), DBError> {
+    let games = tables.get(Table::Games)?;
+    games.add_column("rng_seed", "0");
+    games.add_column("rng_draws", "0");
+    match (games.column("id"), games.column("rng_seed")) {
+        (Some(id), Some(rng_seed)) => {
+            for row in games.rows.iter_mut() {
+                row[rng_seed] = row[id].clone();
+            }
+        }
+        _ => (),
+    };
+    Ok(())
+}
+
+fn give_characters_hp_csv(tables: &mut CsvTables) -> Result<(), DBError> {
+    let characters = tables.get(Table::Characters)?;
+    characters.add_column("hp", "1");
+    let (character, hp) = match (characters.column("character"), characters.column("hp")) {
+        (Some(character), Some(hp)) => (character, hp),
+        _ => return Ok(()),
+    };
+    for row in characters.rows.iter_mut() {
+        // csv stores the class as its display name ("Knight").
+        match models::Character::all_values()
+            .into_iter()
+            .find(|class| class.display_string() == row[character])
+        {
+            Some(class) => row[hp] = class.max_hp().to_string(),
+            None => (),
+        };
+    }
+    Ok(())
+}
+
+fn name_characters_csv(tables: &mut CsvTables) -> Result<(), DBError> {
+    let characters = tables.get(Table::Characters)?;
+    characters.add_column("name", "");
+    let (id, game_id, character, name) = match (
+        characters.column("id"),
+        characters.column("game_id"),
+        characters.column("character"),
+        characters.column("name"),
+    ) {
+        (Some(id), Some(game_id), Some(character), Some(name)) => (id, game_id, character, name),
+        _ => return Ok(()),
+    };
+
+    let mut by_id: Vec<&mut Vec<String>> = characters.rows.iter_mut().collect();
+    by_id.sort_by_key(|row| row[id].parse::<u32>().unwrap_or(0));
+    let mut counts: BTreeMap<(String, String), u32> = BTreeMap::new();
+    for row in by_id.into_iter() {
+        let count = counts
+            .entry((row[game_id].clone(), row[character].clone()))
+            .or_insert(0);
+        *count += 1;
+        // csv stores the class as its display name already ("Knight").
+        row[name] = format!("{} {}", row[character], count);
+    }
+    Ok(())
+}
+
+/// The csv tables being migrated, each read the first time a migration asks
+/// for it.
+pub struct CsvTables<'a> {
+    engine: &'a engine::Engine,
+    file_name_for: &'a dyn Fn(Table) -> String,
+    tables: BTreeMap<String, engine::RawTable>,
+}
+
+impl<'a> CsvTables<'a> {
+    fn get(&mut self, table: Table) -> Result<&mut engine::RawTable, DBError> {
+        match self.tables.entry((self.file_name_for)(table)) {
+            Entry::Occupied(entry) => Ok(entry.into_mut()),
+            Entry::Vacant(entry) => {
+                let raw_table = self.engine.read_raw_table(entry.key())?;
+                Ok(entry.insert(raw_table))
+            }
+        }
+    }
+}
+
+fn key_characters_by_game_csv(tables: &mut CsvTables) -> Result<(), DBError> {
+    let mut games_by_map_id: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    {
+        let games = tables.get(Table::Games)?;
+        match (games.column("id"), games.column("map_id")) {
+            (Some(id), Some(map_id)) => {
+                for row in games.rows.iter() {
+                    games_by_map_id
+                        .entry(row[map_id].clone())
+                        .or_default()
+                        .push(row[id].clone());
+                }
+            }
+            _ => (),
+        };
+    }
+
+    let characters = tables.get(Table::Characters)?;
+    let (id, game_id, x, y) = match (
+        characters.column("id"),
+        characters.column("game_id"),
+        characters.column("x"),
+        characters.column("y"),
+    ) {
+        (Some(id), Some(game_id), Some(x), Some(y)) => (id, game_id, x, y),
+        _ => return Ok(()),
+    };
+    // Each row's new game id, if its map belongs to a single game.
+    let new_game_ids: Vec<Option<String>> = characters
+        .rows
+        .iter()
+        .map(|row| match games_by_map_id.get(&row[game_id]) {
+            Some(ids) if ids.len() == 1 => Some(ids[0].clone()),
+            _ => None,
+        })
+        .collect();
+    let left_alone = new_game_ids.iter().filter(|new| new.is_none()).count();
+
+    // Like sqlite's index on game and position, refuse to put two characters
+    // on the same tile of a game.
+    let mut placed: BTreeMap<(&str, &str, &str), &str> = BTreeMap::new();
+    for (row, new_game_id) in characters.rows.iter().zip(new_game_ids.iter()) {
+        let new_game_id = new_game_id.as_ref().unwrap_or(&row[game_id]);
+        match placed.insert((new_game_id, &row[x], &row[y]), &row[id]) {
+            Some(other_id) => {
+                return Err(DBError::Migrating {
+                    to_version: 3,
+                    reason: format!(
+                        "characters {} and {} would both be at ({}, {}) in game {}",
+                        other_id, row[id], row[x], row[y], new_game_id
+                    ),
+                })
+            }
+            None => (),
+        };
+    }
+
+    for (row, new_game_id) in characters.rows.iter_mut().zip(new_game_ids) {
+        match new_game_id {
+            Some(new_game_id) => row[game_id] = new_game_id,
+            None => (),
+        };
+    }
+    if left_alone > 0 {
+        println!(
+            "{} characters are on a map no single game uses, leaving their game id as is",
+            left_alone
+        );
+    }
+    Ok(())
+}
+
+/// Upgrades the csv tables to `SCHEMA_VERSION`, all in one transaction
+/// together with the new version in `schema_file_name`.
+///
+/// A missing version means the tables predate versioning (version 1). The
+/// caller should hold the engine's write lock.
+pub fn migrate_csv(
+    engine: &engine::Engine,
+    schema_file_name: &str,
+    file_name_for: &dyn Fn(Table) -> String,
+) -> Result<(), DBError> {
+    let from_version = match engine.read_db_records::<DBSchema>(schema_file_name)?.pop() {
+        Some(schema) => schema.version,
+        None => 1,
+    };
+    if from_version > SCHEMA_VERSION {
+        return Err(DBError::UnsupportedSchemaVersion {
+            found: from_version,
+            supported: SCHEMA_VERSION,
+        });
+    }
+    if from_version == SCHEMA_VERSION {
+        return Ok(());
+    }
+
+    let mut tables = CsvTables {
+        engine: engine,
+        file_name_for: file_name_for,
+        tables: BTreeMap::new(),
+    };
+    let mut migrated = Vec::new();
+    for migration in pending(from_version) {
+        (migration.csv)(&mut tables)?;
+        migrated.push(file_name_for(migration.table));
+    }
+
+    let mut transaction = engine.transaction();
+    for (file_name, table) in tables.tables.into_iter() {
+        if migrated.contains(&file_name) {
+            transaction.stage_raw_table(&file_name, table)?;
+        }
+    }
+    transaction.stage_replace_records(
+        schema_file_name,
+        vec![DBSchema {
+            version: SCHEMA_VERSION,
+        }],
+    )?;
+    transaction.commit()
+}
+
+/// Upgrades a sqlite database to `SCHEMA_VERSION`, keeping the version in
+/// `PRAGMA user_version`.
+///
+/// `conn` must already have the version 1 tables; a `user_version` of 0 is
+/// treated as version 1.
+pub fn migrate_sqlite(conn: &Connection) -> Result<(), DBError> {
+    // The version is read holding the write lock, so two processes starting
+    // together never both migrate.
+    conn.execute_batch("BEGIN IMMEDIATE")
+        .map_err(DBError::Sqlite)?;
+    let from_version = match conn.query_row("PRAGMA user_version", params![], |row| row.get(0)) {
+        Ok(version) => std::cmp::max(1, version),
+        Err(e) => {
+            let _ = conn.execute_batch("ROLLBACK");
+            return Err(DBError::Sqlite(e));
+        }
+    };
+    if from_version > SCHEMA_VERSION {
+        let _ = conn.execute_batch("ROLLBACK");
+        return Err(DBError::UnsupportedSchemaVersion {
+            found: from_version,
+            supported: SCHEMA_VERSION,
+        });
+    }
+    if from_version == SCHEMA_VERSION {
+        return conn.execute_batch("COMMIT").map_err(DBError::Sqlite);
+    }
+
+    for migration in pending(from_version) {
+        match conn.execute_batch(migration.sqlite) {
+            Ok(()) => (),
+            Err(e) => {
+                let _ = conn.execute_batch("ROLLBACK");
+                return Err(DBError::Migrating {
+                    to_version: migration.to_version,
+                    reason: e.to_string(),
+                });
+            }
+        };
+    }
+    match conn.execute_batch(&format!(
+        "PRAGMA user_version = {}; COMMIT;",
+        SCHEMA_VERSION
+    )) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            let _ = conn.execute_batch("ROLLBACK");
+            Err(DBError::Sqlite(e))
+        }
+    }
+}
+
+fn pending(from_version: u32) -> impl Iterator<Item = &'static Migration> {
+    MIGRATIONS
+        .iter()
+        .filter(move |migration| migration.to_version > from_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use super::*;
+    use crate::db::common::TempDir;
+    use crate::db::csv_storage::CsvStorage;
+    use crate::db::records::DBCharacter;
+    use crate::db::sqlite_storage::SqliteStorage;
+    use crate::db::storage::Storage;
+
+    /// Each past version's fixtures hold the same game, map, tile, characters
+    /// and (once there were tables for them) event, edit and starting unit,
+    /// as that version stored them: `csv/v{version}/` holds the csv tables
+    /// and `sqlite/v{version}.sql` builds the sqlite database.
+    fn fixtures_dir() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("src/db/fixtures")
+    }
+
+    /// A data directory holding the csv tables of fixture `name`.
+    fn csv_fixture(name: &str) -> TempDir {
+        let dir = TempDir::new(&format!("migrate-csv-{}", name));
+        for entry in fs::read_dir(fixtures_dir().join("csv").join(name)).unwrap() {
+            let entry = entry.unwrap();
+            fs::copy(entry.path(), dir.path().join(entry.file_name())).unwrap();
+        }
+        dir
+    }
+
+    /// A data directory holding the sqlite database of fixture `name`.
+    fn sqlite_fixture(name: &str) -> TempDir {
+        let dir = TempDir::new(&format!("migrate-sqlite-{}", name));
+        let sql = fs::read_to_string(fixtures_dir().join(format!("sqlite/{}.sql", name))).unwrap();
+        sqlite_connection(&dir).execute_batch(&sql).unwrap();
+        dir
+    }
+
+    fn sqlite_connection(dir: &TempDir) -> Connection {
+        Connection::open(dir.path().join("rpq.sqlite3")).unwrap()
+    }
+
+    fn sqlite_version(dir: &TempDir) -> u32 {
+        sqlite_connection(dir)
+            .query_row("PRAGMA user_version", params![], |row| row.get(0))
+            .unwrap()
+    }
+
+    #[test]
+    fn migrates_csv_tables_from_every_past_version() {
+        for version in 1..SCHEMA_VERSION {
+            let dir = csv_fixture(&format!("v{}", version));
+            let storage = CsvStorage::new(dir.path()).unwrap();
+            check_migrated(&storage, version);
+            check_ids_not_reused(&storage, version);
+            assert_eq!(
+                fs::read_to_string(dir.path().join("schema.csv")).unwrap(),
+                format!("version\n{}\n", SCHEMA_VERSION)
+            );
+        }
+    }
+
+    #[test]
+    fn migrates_sqlite_tables_from_every_past_version() {
+        for version in 1..SCHEMA_VERSION {
+            let dir = sqlite_fixture(&format!("v{}", version));
+            let storage = SqliteStorage::new(dir.path()).unwrap();
+            check_migrated(&storage, version);
+            check_ids_not_reused(&storage, version);
+            assert_eq!(sqlite_version(&dir), SCHEMA_VERSION);
+        }
+    }
+
+    #[test]
+    fn keys_csv_characters_by_game_around_a_shared_map() {
+        let dir = csv_fixture("v2-shared-map");
+        check_keyed_around_shared_map(&CsvStorage::new(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn keys_sqlite_characters_by_game_around_a_shared_map() {
+        let dir = sqlite_fixture("v2-shared-map");
+        check_keyed_around_shared_map(&SqliteStorage::new(dir.path()).unwrap());
+    }
+
+    /// Game 1 is the only game on map 2, and games 2 and 3 share map 1.
+    fn check_keyed_around_shared_map(storage: &dyn Storage) {
+        let mut characters = storage.read_characters().unwrap();
+        characters.sort_by_key(|character| character.id);
+        let keys: Vec<(u32, u32, u32, u32)> = characters
+            .iter()
+            .map(|character| (character.id, character.game_id, character.x, character.y))
+            .collect();
+        // The knight on the shared map and the thief on a map no game uses
+        // keep their game ids, while the mage moves to map 2's game.
+        assert_eq!(keys, vec![(1, 1, 0, 0), (2, 1, 1, 0), (3, 5, 2, 0)]);
+    }
+
+    #[test]
+    fn refuses_to_stack_csv_characters_on_one_tile() {
+        let dir = csv_fixture("v2-colliding");
+        let characters = fs::read_to_string(dir.path().join("characters.csv")).unwrap();
+
+        match CsvStorage::new(dir.path()) {
+            Err(DBError::Migrating { to_version, reason }) => {
+                assert_eq!(to_version, 3);
+                assert_eq!(
+                    reason,
+                    "characters 1 and 2 would both be at (0, 0) in game 1"
+                );
+            }
+            _ => panic!("expected the upgrade to schema version 3 to fail"),
+        };
+        assert_eq!(
+            fs::read_to_string(dir.path().join("characters.csv")).unwrap(),
+            characters
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("schema.csv")).unwrap(),
+            "version\n2\n"
+        );
+    }
+
+    #[test]
+    fn refuses_to_stack_sqlite_characters_on_one_tile() {
+        let dir = sqlite_fixture("v2-colliding");
+
+        match SqliteStorage::new(dir.path()) {
+            Err(DBError::Migrating { to_version, .. }) => assert_eq!(to_version, 3),
+            _ => panic!("expected the upgrade to schema version 3 to fail"),
+        };
+        assert_eq!(sqlite_version(&dir), 2);
+        let conn = sqlite_connection(&dir);
+        let mut statement = conn
+            .prepare("SELECT id, game_id, x, y FROM characters ORDER BY id")
+            .unwrap();
+        let keys: Vec<(u32, u32, u32, u32)> = statement
+            .query_map(params![], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+            })
+            .unwrap()
+            .map(|key| key.unwrap())
+            .collect();
+        assert_eq!(keys, vec![(1, 1, 0, 0), (2, 2, 0, 0)]);
+    }
+
+    /// Removes the fixture's mage, the character with the highest id, and
+    /// checks the next character added is not given its id.
+    fn check_ids_not_reused(storage: &dyn Storage, version: u32) {
+        storage.delete_character_at(1, 1, 1).unwrap();
+        storage
+            .set_character(DBCharacter {
+                id: 0,
+                game_id: 1,
+                character: models::Character::Thief,
+                x: 2,
+                y: 0,
+                name: "Robin".to_string(),
+                faction: models::Faction::Player,
+                behavior: models::Behavior::Aggressive,
+                hp: models::Character::Thief.max_hp(),
+                moved: false,
+                acted: false,
+            })
+            .unwrap();
+        let mut ids: Vec<u32> = storage
+            .read_characters_for_game_id(1)
+            .unwrap()
+            .iter()
+            .map(|r| r.id)
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec![1, 3], "character ids from version {}", version);
+    }
+
+    /// Checks every field of the fixture's records once brought up from
+    /// `version`: what that version stored is kept as it was, and whatever it
+    /// had no column for is filled in the way its migration says.
+    fn check_migrated(storage: &dyn Storage, version: u32) {
+        let games = storage.read_games().unwrap();
+        assert_eq!(games.len(), 1, "games from version {}", version);
+        let game = &games[0];
+        assert_eq!(game.id, 1);
+        assert_eq!(game.map_id, 2);
+        assert_eq!((game.cursor_x, game.cursor_y), (2, 1));
+        assert_eq!(game.version, if version >= 2 { 3 } else { 0 });
+        assert_eq!(
+            game.selected_unit_id,
+            if version >= 5 { Some(2) } else { None }
+        );
+        if version >= 6 {
+            assert_eq!(game.turn, 4);
+            assert!(game.active_faction == models::Faction::Enemy);
+        } else {
+            assert_eq!(game.turn, 1);
+            assert!(game.active_faction == models::Faction::Player);
+        }
+        if version >= 8 {
+            assert_eq!((game.rng_seed, game.rng_draws), (99, 5));
+        } else {
+            assert_eq!((game.rng_seed, game.rng_draws), (game.id, 0));
+        }
+        if version >= 10 {
+            assert!(game.objective == models::Objective::Survive { turns: 6 });
+            assert!(game.result == Some(models::GameResult::Won));
+        } else {
+            assert!(game.objective == models::Objective::Rout);
+            assert!(game.result.is_none());
+        }
+
+        let maps = storage.read_maps().unwrap();
+        assert_eq!(maps.len(), 1, "maps from version {}", version);
+        let map = &maps[0];
+        assert_eq!(map.id, 2);
+        assert!(map.default_terrain == models::Terrain::Dirt);
+        assert_eq!((map.hint_max_x, map.hint_max_y), (4, 3));
+        if version >= 13 {
+            assert_eq!((map.cursor_x, map.cursor_y), (1, 2));
+        } else {
+            assert_eq!((map.cursor_x, map.cursor_y), (0, 0));
+        }
+        assert_eq!(map.name, if version >= 15 { "Forest Pass" } else { "" });
+        assert_eq!((map.anchor_x, map.anchor_y), (None, None));
+
+        let tile_lines = storage.read_tile_lines().unwrap();
+        assert_eq!(tile_lines.len(), 1, "tiles from version {}", version);
+        let tile_line = &tile_lines[0];
+        assert_eq!((tile_line.id, tile_line.map_id), (1, 2));
+        assert!(tile_line.terrain == models::Terrain::Rock);
+        assert_eq!((tile_line.x, tile_line.y), (3, 2));
+
+        let mut characters = storage.read_characters().unwrap();
+        characters.sort_by_key(|character| character.id);
+        assert_eq!(characters.len(), 2, "characters from version {}", version);
+        let (knight, mage) = (&characters[0], &characters[1]);
+        assert_eq!((knight.id, knight.game_id), (1, 1));
+        assert!(matches!(knight.character, models::Character::Knight));
+        assert_eq!((knight.x, knight.y), (0, 0));
+        assert_eq!((mage.id, mage.game_id), (2, 1));
+        assert!(matches!(mage.character, models::Character::Mage));
+        assert_eq!((mage.x, mage.y), (1, 1));
+        if version >= 4 {
+            assert_eq!(
+                (knight.name.as_str(), mage.name.as_str()),
+                ("Gawain", "Merlin")
+            );
+        } else {
+            assert_eq!(
+                (knight.name.as_str(), mage.name.as_str()),
+                ("Knight 1", "Mage 1")
+            );
+        }
+        if version >= 6 {
+            assert!(knight.faction == models::Faction::Player);
+            assert_eq!((knight.moved, knight.acted), (true, false));
+            assert!(mage.faction == models::Faction::Enemy);
+            assert_eq!((mage.moved, mage.acted), (false, true));
+        } else {
+            for character in characters.iter() {
+                assert!(character.faction == models::Faction::Player);
+                assert_eq!((character.moved, character.acted), (false, false));
+            }
+        }
+        if version >= 7 {
+            assert_eq!((knight.hp, mage.hp), (7, 3));
+        } else {
+            assert_eq!(
+                (knight.hp, mage.hp),
+                (
+                    models::Character::Knight.max_hp(),
+                    models::Character::Mage.max_hp()
+                )
+            );
+        }
+        if version >= 9 {
+            assert!(knight.behavior == models::Behavior::Defensive);
+            assert!(mage.behavior == models::Behavior::Guard);
+        } else {
+            for character in characters.iter() {
+                assert!(character.behavior == models::Behavior::Aggressive);
+            }
+        }
+
+        let events = storage.read_events().unwrap();
+        if version >= 11 {
+            assert_eq!(events.len(), 1);
+            assert_eq!((events[0].id, events[0].game_id, events[0].turn), (1, 1, 2));
+            assert_eq!(events[0].event.log_string(), "move/0/0/1/0");
+        } else {
+            assert!(events.is_empty());
+        }
+
+        let edits = storage.read_edits().unwrap();
+        if version >= 12 {
+            assert_eq!(edits.len(), 1);
+            assert_eq!((edits[0].id, edits[0].game_id), (1, 1));
+            assert_eq!(edits[0].undo.log_string(), "unset-terrain/3/2");
+            assert_eq!(edits[0].redo.log_string(), "terrain/3/2/rock");
+            assert!(edits[0].undone);
+        } else {
+            assert!(edits.is_empty());
+        }
+
+        let starting_units = storage.read_starting_units().unwrap();
+        if version >= 14 {
+            assert_eq!(starting_units.len(), 1);
+            let starting_unit = &starting_units[0];
+            assert_eq!((starting_unit.id, starting_unit.map_id), (1, 2));
+            assert!(matches!(starting_unit.character, models::Character::Thief));
+            assert_eq!((starting_unit.x, starting_unit.y), (2, 2));
+            assert!(starting_unit.faction == models::Faction::Neutral);
+            assert!(starting_unit.behavior == models::Behavior::Guard);
+        } else {
+            assert!(starting_units.is_empty());
+        }
+    }
+}
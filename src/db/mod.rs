@@ -1,6 +1,13 @@
 mod common;
 mod concrete;
+mod csv_storage;
 mod engine;
+mod memory_storage;
+mod migrations;
+mod records;
+mod sqlite_storage;
+mod storage;
+mod transaction;
 
 pub use common::DBError;
-pub use concrete::DB;
+pub use concrete::{Backend, DB};
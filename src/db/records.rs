@@ -0,0 +1,152 @@
+use serde::{Deserialize, Serialize};
+
+use crate::models;
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DBGame {
+    pub id: u32,
+    pub map_id: u32,
+    pub cursor_x: u32,
+    pub cursor_y: u32,
+    /// Bumped on every update so writers working from a stale read can tell.
+    #[serde(default)]
+    pub version: u32,
+    /// The `DBCharacter` picked to be moved, if any.
+    #[serde(default)]
+    pub selected_unit_id: Option<u32>,
+    #[serde(default = "first_turn")]
+    pub turn: u32,
+    #[serde(default = "player_faction")]
+    pub active_faction: models::Faction,
+    /// Seeds the game's random number generator.
+    #[serde(default)]
+    pub rng_seed: u32,
+    /// How many numbers the game's generator has drawn so far.
+    #[serde(default)]
+    pub rng_draws: u32,
+    #[serde(default = "rout_objective")]
+    pub objective: models::Objective,
+    /// Empty until the game is won or lost.
+    #[serde(default)]
+    pub result: Option<models::GameResult>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DBMap {
+    pub id: u32,
+    pub default_terrain: models::Terrain,
+    pub hint_max_x: u32,
+    pub hint_max_y: u32,
+    /// Where the map editor's cursor is; games keep their own.
+    #[serde(default)]
+    pub cursor_x: u32,
+    #[serde(default)]
+    pub cursor_y: u32,
+    /// Empty until the map is given a name.
+    #[serde(default)]
+    pub name: String,
+    /// The corner or end that brushes paint from, shared by the map editor
+    /// and the edit page of the game the map belongs to. Empty until set.
+    #[serde(default)]
+    pub anchor_x: Option<u32>,
+    #[serde(default)]
+    pub anchor_y: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DBTileLine {
+    pub id: u32,
+    pub map_id: u32,
+    pub terrain: models::Terrain,
+    pub x: u32,
+    pub y: u32,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DBCharacter {
+    /// Kept for as long as a character stands at its position.
+    pub id: u32,
+    /// The game (never the map) the character is placed in.
+    pub game_id: u32,
+    pub character: models::Character,
+    pub x: u32,
+    pub y: u32,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default = "player_faction")]
+    pub faction: models::Faction,
+    #[serde(default = "aggressive_behavior")]
+    pub behavior: models::Behavior,
+    pub hp: u32,
+    #[serde(default)]
+    pub moved: bool,
+    #[serde(default)]
+    pub acted: bool,
+}
+
+/// A character a map starts its games with, placed in each game added from
+/// the map.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DBStartingUnit {
+    pub id: u32,
+    pub map_id: u32,
+    pub character: models::Character,
+    pub x: u32,
+    pub y: u32,
+    pub faction: models::Faction,
+    pub behavior: models::Behavior,
+}
+
+/// One entry in a game's log of everything done to it.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DBEvent {
+    /// Goes up with each event, across all games.
+    pub id: u32,
+    pub game_id: u32,
+    /// The game's turn when the event happened.
+    pub turn: u32,
+    pub event: models::Event,
+}
+
+/// One change made on a game's edit page, stored as the events that take it
+/// back and make it again.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DBEdit {
+    /// Goes up with each edit, across all games.
+    pub id: u32,
+    pub game_id: u32,
+    pub undo: models::Event,
+    pub redo: models::Event,
+    /// Whether the edit has been undone (and can be redone).
+    pub undone: bool,
+}
+
+/// The highest id a table has handed out, so that the ids of rows deleted
+/// since are never given to new ones.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DBIdCounter {
+    pub table: String,
+    pub last_id: u32,
+}
+
+/// The single row recording which schema version the tables are at.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DBSchema {
+    pub version: u32,
+}
+
+fn first_turn() -> u32 {
+    1
+}
+
+fn player_faction() -> models::Faction {
+    models::Faction::Player
+}
+
+fn rout_objective() -> models::Objective {
+    models::Objective::Rout
+}
+
+fn aggressive_behavior() -> models::Behavior {
+    models::Behavior::Aggressive
+}
@@ -0,0 +1,1269 @@
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use rusqlite::{params, Connection, Row, ToSql};
+
+use crate::models;
+
+use super::common::{Cause, DBError};
+use super::migrations;
+use super::records::{
+    DBCharacter, DBEdit, DBEvent, DBGame, DBIdCounter, DBMap, DBStartingUnit, DBTileLine,
+};
+use super::storage::Storage;
+use super::transaction::TableWrites;
+
+const SQLITE_DB_FILE_NAME: &'static str = "rpq.sqlite3";
+
+/// The tables as of schema version 1; `migrations` brings them up to date.
+const SCHEMA: &'static str = "
+    CREATE TABLE IF NOT EXISTS games (
+        id INTEGER PRIMARY KEY,
+        map_id INTEGER NOT NULL,
+        cursor_x INTEGER NOT NULL,
+        cursor_y INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS maps (
+        id INTEGER PRIMARY KEY,
+        default_terrain TEXT NOT NULL,
+        hint_max_x INTEGER NOT NULL,
+        hint_max_y INTEGER NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS tiles (
+        id INTEGER PRIMARY KEY,
+        map_id INTEGER NOT NULL,
+        terrain TEXT NOT NULL,
+        x INTEGER NOT NULL,
+        y INTEGER NOT NULL
+    );
+    CREATE UNIQUE INDEX IF NOT EXISTS tiles_by_map_position ON tiles (map_id, x, y);
+    CREATE TABLE IF NOT EXISTS characters (
+        id INTEGER PRIMARY KEY,
+        game_id INTEGER NOT NULL,
+        character TEXT NOT NULL,
+        x INTEGER NOT NULL,
+        y INTEGER NOT NULL
+    );
+    CREATE UNIQUE INDEX IF NOT EXISTS characters_by_game_position ON characters (game_id, x, y);
+";
+
+/// Storage backed by a single embedded sqlite file in a data directory.
+///
+/// Tiles and characters are indexed by their map/game id and position, and
+/// games and maps by id, so the row-level `Storage` operations touch only the
+/// rows they need.
+pub struct SqliteStorage {
+    conn: Mutex<Connection>,
+}
+
+impl SqliteStorage {
+    pub fn new(data_dir: &Path) -> Result<Self, DBError> {
+        let conn = Connection::open(data_dir.join(SQLITE_DB_FILE_NAME)).map_err(DBError::Sqlite)?;
+        // Wait for other processes' writes rather than failing straight away.
+        conn.busy_timeout(Duration::from_secs(5))
+            .map_err(DBError::Sqlite)?;
+        conn.execute_batch(SCHEMA).map_err(DBError::Sqlite)?;
+        migrations::migrate_sqlite(&conn)?;
+        Ok(SqliteStorage {
+            conn: Mutex::new(conn),
+        })
+    }
+
+    fn with_connection<T, F: FnOnce(&ConnectionStorage) -> Result<T, DBError>>(
+        &self,
+        f: F,
+    ) -> Result<T, DBError> {
+        let conn = self.conn.lock().unwrap_or_else(|e| e.into_inner());
+        f(&ConnectionStorage { conn: &conn })
+    }
+}
+
+impl Storage for SqliteStorage {
+    fn read_games(&self) -> Result<Vec<DBGame>, DBError> {
+        self.with_connection(|s| s.read_games())
+    }
+
+    fn write_games(&self, records: Vec<DBGame>) -> Result<(), DBError> {
+        self.with_connection(|s| s.write_games(records))
+    }
+
+    fn read_maps(&self) -> Result<Vec<DBMap>, DBError> {
+        self.with_connection(|s| s.read_maps())
+    }
+
+    fn write_maps(&self, records: Vec<DBMap>) -> Result<(), DBError> {
+        self.with_connection(|s| s.write_maps(records))
+    }
+
+    fn read_tile_lines(&self) -> Result<Vec<DBTileLine>, DBError> {
+        self.with_connection(|s| s.read_tile_lines())
+    }
+
+    fn write_tile_lines(&self, records: Vec<DBTileLine>) -> Result<(), DBError> {
+        self.with_connection(|s| s.write_tile_lines(records))
+    }
+
+    fn read_characters(&self) -> Result<Vec<DBCharacter>, DBError> {
+        self.with_connection(|s| s.read_characters())
+    }
+
+    fn write_characters(&self, records: Vec<DBCharacter>) -> Result<(), DBError> {
+        self.with_connection(|s| s.write_characters(records))
+    }
+
+    fn read_events(&self) -> Result<Vec<DBEvent>, DBError> {
+        self.with_connection(|s| s.read_events())
+    }
+
+    fn write_events(&self, records: Vec<DBEvent>) -> Result<(), DBError> {
+        self.with_connection(|s| s.write_events(records))
+    }
+
+    fn read_edits(&self) -> Result<Vec<DBEdit>, DBError> {
+        self.with_connection(|s| s.read_edits())
+    }
+
+    fn write_edits(&self, records: Vec<DBEdit>) -> Result<(), DBError> {
+        self.with_connection(|s| s.write_edits(records))
+    }
+
+    fn read_starting_units(&self) -> Result<Vec<DBStartingUnit>, DBError> {
+        self.with_connection(|s| s.read_starting_units())
+    }
+
+    fn write_starting_units(&self, records: Vec<DBStartingUnit>) -> Result<(), DBError> {
+        self.with_connection(|s| s.write_starting_units(records))
+    }
+
+    fn read_id_counters(&self) -> Result<Vec<DBIdCounter>, DBError> {
+        self.with_connection(|s| s.read_id_counters())
+    }
+
+    fn write_id_counters(&self, records: Vec<DBIdCounter>) -> Result<(), DBError> {
+        self.with_connection(|s| s.write_id_counters(records))
+    }
+
+    fn write_tables(&self, writes: TableWrites) -> Result<(), DBError> {
+        self.with_connection(|s| s.write_tables(writes))
+    }
+
+    fn transaction(
+        &self,
+        f: &mut dyn FnMut(&dyn Storage) -> Result<(), DBError>,
+    ) -> Result<(), DBError> {
+        self.with_connection(|s| s.transaction(f))
+    }
+
+    fn read_game(&self, id: u32) -> Result<Option<DBGame>, DBError> {
+        self.with_connection(|s| s.read_game(id))
+    }
+
+    fn insert_game(&self, record: DBGame) -> Result<DBGame, DBError> {
+        self.with_connection(|s| s.insert_game(record))
+    }
+
+    fn update_game(&self, record: DBGame) -> Result<(), DBError> {
+        self.with_connection(|s| s.update_game(record))
+    }
+
+    fn read_map(&self, id: u32) -> Result<Option<DBMap>, DBError> {
+        self.with_connection(|s| s.read_map(id))
+    }
+
+    fn insert_map(&self, record: DBMap) -> Result<DBMap, DBError> {
+        self.with_connection(|s| s.insert_map(record))
+    }
+
+    fn update_map(&self, record: DBMap) -> Result<(), DBError> {
+        self.with_connection(|s| s.update_map(record))
+    }
+
+    fn read_tile_lines_for_map_id(&self, map_id: u32) -> Result<Vec<DBTileLine>, DBError> {
+        self.with_connection(|s| s.read_tile_lines_for_map_id(map_id))
+    }
+
+    fn set_tile_line(&self, record: DBTileLine) -> Result<(), DBError> {
+        self.with_connection(|s| s.set_tile_line(record))
+    }
+
+    fn delete_tile_line_at(&self, map_id: u32, x: u32, y: u32) -> Result<(), DBError> {
+        self.with_connection(|s| s.delete_tile_line_at(map_id, x, y))
+    }
+
+    fn delete_tile_lines_outside(
+        &self,
+        map_id: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), DBError> {
+        self.with_connection(|s| s.delete_tile_lines_outside(map_id, width, height))
+    }
+
+    fn paint_tile_lines(
+        &self,
+        map_id: u32,
+        tiles: &[((u32, u32), Option<models::Terrain>)],
+    ) -> Result<(), DBError> {
+        self.with_connection(|s| s.paint_tile_lines(map_id, tiles))
+    }
+
+    fn read_characters_for_game_id(&self, game_id: u32) -> Result<Vec<DBCharacter>, DBError> {
+        self.with_connection(|s| s.read_characters_for_game_id(game_id))
+    }
+
+    fn set_character(&self, record: DBCharacter) -> Result<(), DBError> {
+        self.with_connection(|s| s.set_character(record))
+    }
+
+    fn put_character(&self, record: DBCharacter) -> Result<(), DBError> {
+        self.with_connection(|s| s.put_character(record))
+    }
+
+    fn update_character(&self, record: DBCharacter) -> Result<(), DBError> {
+        self.with_connection(|s| s.update_character(record))
+    }
+
+    fn delete_character_at(&self, game_id: u32, x: u32, y: u32) -> Result<(), DBError> {
+        self.with_connection(|s| s.delete_character_at(game_id, x, y))
+    }
+
+    fn read_events_for_game_id(&self, game_id: u32) -> Result<Vec<DBEvent>, DBError> {
+        self.with_connection(|s| s.read_events_for_game_id(game_id))
+    }
+
+    fn append_event(&self, record: DBEvent) -> Result<(), DBError> {
+        self.with_connection(|s| s.append_event(record))
+    }
+
+    fn read_edits_for_game_id(&self, game_id: u32) -> Result<Vec<DBEdit>, DBError> {
+        self.with_connection(|s| s.read_edits_for_game_id(game_id))
+    }
+
+    fn insert_edit(&self, record: DBEdit) -> Result<(), DBError> {
+        self.with_connection(|s| s.insert_edit(record))
+    }
+
+    fn update_edit(&self, record: DBEdit) -> Result<(), DBError> {
+        self.with_connection(|s| s.update_edit(record))
+    }
+
+    fn delete_undone_edits(&self, game_id: u32) -> Result<(), DBError> {
+        self.with_connection(|s| s.delete_undone_edits(game_id))
+    }
+
+    fn delete_edits_for_game_id(&self, game_id: u32) -> Result<(), DBError> {
+        self.with_connection(|s| s.delete_edits_for_game_id(game_id))
+    }
+
+    fn read_starting_units_for_map_id(&self, map_id: u32) -> Result<Vec<DBStartingUnit>, DBError> {
+        self.with_connection(|s| s.read_starting_units_for_map_id(map_id))
+    }
+
+    fn set_starting_unit(&self, record: DBStartingUnit) -> Result<(), DBError> {
+        self.with_connection(|s| s.set_starting_unit(record))
+    }
+
+    fn update_starting_unit(&self, record: DBStartingUnit) -> Result<(), DBError> {
+        self.with_connection(|s| s.update_starting_unit(record))
+    }
+
+    fn delete_starting_unit_at(&self, map_id: u32, x: u32, y: u32) -> Result<(), DBError> {
+        self.with_connection(|s| s.delete_starting_unit_at(map_id, x, y))
+    }
+
+    fn delete_starting_units_outside(
+        &self,
+        map_id: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), DBError> {
+        self.with_connection(|s| s.delete_starting_units_outside(map_id, width, height))
+    }
+}
+
+/// The sqlite queries behind `SqliteStorage`, run on a connection whose lock
+/// is already held.
+struct ConnectionStorage<'a> {
+    conn: &'a Connection,
+}
+
+impl<'a> ConnectionStorage<'a> {
+    fn query<T, F: FnMut(&Row) -> rusqlite::Result<T>>(
+        &self,
+        sql: &str,
+        params: &[&dyn ToSql],
+        f: F,
+    ) -> Result<Vec<T>, DBError> {
+        let mut stmt = self.conn.prepare(sql).map_err(DBError::Sqlite)?;
+        let rows = stmt.query_map(params, f).map_err(DBError::Sqlite)?;
+        rows.collect::<rusqlite::Result<Vec<T>>>()
+            .map_err(DBError::Sqlite)
+    }
+
+    fn execute(&self, sql: &str, params: &[&dyn ToSql]) -> Result<(), DBError> {
+        self.conn.execute(sql, params).map_err(DBError::Sqlite)?;
+        Ok(())
+    }
+
+    /// Runs an INSERT and returns the id sqlite assigned to the new row.
+    fn insert(&self, sql: &str, params: &[&dyn ToSql]) -> Result<u32, DBError> {
+        self.execute(sql, params)?;
+        Ok(self.conn.last_insert_rowid() as u32)
+    }
+
+    /// Runs `f` in a transaction, rolling back everything it did if it fails.
+    ///
+    /// The outermost call takes sqlite's write lock up front (so another
+    /// process cannot write between our reads and writes); calls made while a
+    /// transaction is already open nest inside it as savepoints.
+    fn in_transaction<T, F: FnOnce() -> Result<T, DBError>>(&self, f: F) -> Result<T, DBError> {
+        let (begin, commit, rollback) = if self.conn.is_autocommit() {
+            ("BEGIN IMMEDIATE", "COMMIT", "ROLLBACK")
+        } else {
+            (
+                "SAVEPOINT rpq",
+                "RELEASE rpq",
+                "ROLLBACK TO rpq; RELEASE rpq",
+            )
+        };
+        self.conn.execute_batch(begin).map_err(DBError::Sqlite)?;
+        match f() {
+            Ok(v) => {
+                self.conn.execute_batch(commit).map_err(DBError::Sqlite)?;
+                Ok(v)
+            }
+            Err(e) => {
+                let _ = self.conn.execute_batch(rollback);
+                Err(e)
+            }
+        }
+    }
+
+    /// Replaces every row of `table` with `records`.
+    fn replace_all<T, F: Fn(&T) -> Vec<&dyn ToSql>>(
+        &self,
+        table: &str,
+        insert_sql: &str,
+        records: Vec<T>,
+        to_params: F,
+    ) -> Result<(), DBError> {
+        self.in_transaction(|| {
+            self.execute(&format!("DELETE FROM {}", table), params![])?;
+            let mut stmt = self.conn.prepare(insert_sql).map_err(DBError::Sqlite)?;
+            for record in records.iter() {
+                stmt.execute(to_params(record)).map_err(DBError::Sqlite)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl<'a> Storage for ConnectionStorage<'a> {
+    fn read_games(&self) -> Result<Vec<DBGame>, DBError> {
+        games_from_rows(self.query(
+            "SELECT id, map_id, cursor_x, cursor_y, version, selected_unit_id, turn,
+                active_faction, rng_seed, rng_draws, objective, result FROM games",
+            params![],
+            game_row,
+        )?)
+    }
+
+    fn write_games(&self, records: Vec<DBGame>) -> Result<(), DBError> {
+        let enums = records
+            .iter()
+            .map(|r| {
+                (
+                    r.active_faction.url_frag_string(),
+                    r.objective.url_frag_string(),
+                    r.result.as_ref().map(|result| result.url_frag_string()),
+                )
+            })
+            .collect::<Vec<(String, String, Option<String>)>>();
+        self.replace_all(
+            "games",
+            "INSERT INTO games (id, map_id, cursor_x, cursor_y, version, selected_unit_id, turn,
+                active_faction, rng_seed, rng_draws, objective, result)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)",
+            records.iter().zip(enums.iter()).collect(),
+            |(r, (faction, objective, result))| {
+                vec![
+                    &r.id,
+                    &r.map_id,
+                    &r.cursor_x,
+                    &r.cursor_y,
+                    &r.version,
+                    &r.selected_unit_id,
+                    &r.turn,
+                    faction,
+                    &r.rng_seed,
+                    &r.rng_draws,
+                    objective,
+                    result,
+                ]
+            },
+        )
+    }
+
+    fn read_maps(&self) -> Result<Vec<DBMap>, DBError> {
+        maps_from_rows(self.query(
+            "SELECT id, default_terrain, hint_max_x, hint_max_y, cursor_x, cursor_y, name,
+                anchor_x, anchor_y FROM maps",
+            params![],
+            map_row,
+        )?)
+    }
+
+    fn write_maps(&self, records: Vec<DBMap>) -> Result<(), DBError> {
+        let terrains = records
+            .iter()
+            .map(|r| r.default_terrain.url_frag_string())
+            .collect::<Vec<String>>();
+        self.replace_all(
+            "maps",
+            "INSERT INTO maps (id, default_terrain, hint_max_x, hint_max_y, cursor_x, cursor_y,
+                name, anchor_x, anchor_y) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            records.iter().zip(terrains.iter()).collect(),
+            |(r, terrain)| {
+                vec![
+                    &r.id,
+                    *terrain,
+                    &r.hint_max_x,
+                    &r.hint_max_y,
+                    &r.cursor_x,
+                    &r.cursor_y,
+                    &r.name,
+                    &r.anchor_x,
+                    &r.anchor_y,
+                ]
+            },
+        )
+    }
+
+    fn read_tile_lines(&self) -> Result<Vec<DBTileLine>, DBError> {
+        tile_lines_from_rows(self.query(
+            "SELECT id, map_id, terrain, x, y FROM tiles",
+            params![],
+            positioned_row,
+        )?)
+    }
+
+    fn write_tile_lines(&self, records: Vec<DBTileLine>) -> Result<(), DBError> {
+        let terrains = records
+            .iter()
+            .map(|r| r.terrain.url_frag_string())
+            .collect::<Vec<String>>();
+        self.replace_all(
+            "tiles",
+            "INSERT INTO tiles (id, map_id, terrain, x, y) VALUES (?1, ?2, ?3, ?4, ?5)",
+            records.iter().zip(terrains.iter()).collect(),
+            |(r, terrain)| vec![&r.id, &r.map_id, *terrain, &r.x, &r.y],
+        )
+    }
+
+    fn read_characters(&self) -> Result<Vec<DBCharacter>, DBError> {
+        characters_from_rows(self.query(
+            "SELECT id, game_id, character, x, y, name, faction, moved, acted, hp, behavior
+                FROM characters",
+            params![],
+            character_row,
+        )?)
+    }
+
+    fn write_characters(&self, records: Vec<DBCharacter>) -> Result<(), DBError> {
+        let kinds = records
+            .iter()
+            .map(|r| {
+                (
+                    r.character.url_frag_string(),
+                    r.faction.url_frag_string(),
+                    r.behavior.url_frag_string(),
+                )
+            })
+            .collect::<Vec<(String, String, String)>>();
+        self.replace_all(
+            "characters",
+            "INSERT INTO characters (id, game_id, character, x, y, name, faction, moved, acted,
+                hp, behavior) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            records.iter().zip(kinds.iter()).collect(),
+            |(r, (character, faction, behavior))| {
+                vec![
+                    &r.id, &r.game_id, character, &r.x, &r.y, &r.name, faction, &r.moved, &r.acted,
+                    &r.hp, behavior,
+                ]
+            },
+        )
+    }
+
+    fn read_events(&self) -> Result<Vec<DBEvent>, DBError> {
+        events_from_rows(self.query(
+            "SELECT id, game_id, turn, event FROM events",
+            params![],
+            event_row,
+        )?)
+    }
+
+    fn write_events(&self, records: Vec<DBEvent>) -> Result<(), DBError> {
+        let events = records
+            .iter()
+            .map(|r| r.event.log_string())
+            .collect::<Vec<String>>();
+        self.replace_all(
+            "events",
+            "INSERT INTO events (id, game_id, turn, event) VALUES (?1, ?2, ?3, ?4)",
+            records.iter().zip(events.iter()).collect(),
+            |(r, event)| vec![&r.id, &r.game_id, &r.turn, *event],
+        )
+    }
+
+    fn read_edits(&self) -> Result<Vec<DBEdit>, DBError> {
+        edits_from_rows(self.query(
+            "SELECT id, game_id, undo, redo, undone FROM edits",
+            params![],
+            edit_row,
+        )?)
+    }
+
+    fn write_edits(&self, records: Vec<DBEdit>) -> Result<(), DBError> {
+        let events = records
+            .iter()
+            .map(|r| (r.undo.log_string(), r.redo.log_string()))
+            .collect::<Vec<(String, String)>>();
+        self.replace_all(
+            "edits",
+            "INSERT INTO edits (id, game_id, undo, redo, undone) VALUES (?1, ?2, ?3, ?4, ?5)",
+            records.iter().zip(events.iter()).collect(),
+            |(r, (undo, redo))| vec![&r.id, &r.game_id, undo, redo, &r.undone],
+        )
+    }
+
+    fn read_starting_units(&self) -> Result<Vec<DBStartingUnit>, DBError> {
+        starting_units_from_rows(self.query(
+            "SELECT id, map_id, character, x, y, faction, behavior FROM starting_units",
+            params![],
+            starting_unit_row,
+        )?)
+    }
+
+    fn write_starting_units(&self, records: Vec<DBStartingUnit>) -> Result<(), DBError> {
+        let kinds = records
+            .iter()
+            .map(|r| {
+                (
+                    r.character.url_frag_string(),
+                    r.faction.url_frag_string(),
+                    r.behavior.url_frag_string(),
+                )
+            })
+            .collect::<Vec<(String, String, String)>>();
+        self.replace_all(
+            "starting_units",
+            "INSERT INTO starting_units (id, map_id, character, x, y, faction, behavior)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+            records.iter().zip(kinds.iter()).collect(),
+            |(r, (character, faction, behavior))| {
+                vec![&r.id, &r.map_id, character, &r.x, &r.y, faction, behavior]
+            },
+        )
+    }
+
+    /// The counters behind the tables declared AUTOINCREMENT, which sqlite
+    /// keeps itself.
+    fn read_id_counters(&self) -> Result<Vec<DBIdCounter>, DBError> {
+        self.query("SELECT name, seq FROM sqlite_sequence", params![], |row| {
+            Ok(DBIdCounter {
+                table: row.get(0)?,
+                last_id: row.get(1)?,
+            })
+        })
+    }
+
+    fn write_id_counters(&self, records: Vec<DBIdCounter>) -> Result<(), DBError> {
+        self.replace_all(
+            "sqlite_sequence",
+            "INSERT INTO sqlite_sequence (name, seq) VALUES (?1, ?2)",
+            records.iter().collect(),
+            |r| vec![&r.table, &r.last_id],
+        )
+    }
+
+    fn write_tables(&self, writes: TableWrites) -> Result<(), DBError> {
+        self.in_transaction(|| {
+            match writes.games {
+                Some(records) => self.write_games(records)?,
+                None => (),
+            };
+            match writes.maps {
+                Some(records) => self.write_maps(records)?,
+                None => (),
+            };
+            match writes.tile_lines {
+                Some(records) => self.write_tile_lines(records)?,
+                None => (),
+            };
+            match writes.characters {
+                Some(records) => self.write_characters(records)?,
+                None => (),
+            };
+            match writes.events {
+                Some(records) => self.write_events(records)?,
+                None => (),
+            };
+            for record in writes.appended_events.into_iter() {
+                self.append_event(record)?;
+            }
+            match writes.edits {
+                Some(records) => self.write_edits(records)?,
+                None => (),
+            };
+            match writes.starting_units {
+                Some(records) => self.write_starting_units(records)?,
+                None => (),
+            };
+            match writes.id_counters {
+                Some(records) => self.write_id_counters(records)?,
+                None => (),
+            };
+            Ok(())
+        })
+    }
+
+    fn transaction(
+        &self,
+        f: &mut dyn FnMut(&dyn Storage) -> Result<(), DBError>,
+    ) -> Result<(), DBError> {
+        self.in_transaction(|| f(self))
+    }
+
+    fn read_game(&self, id: u32) -> Result<Option<DBGame>, DBError> {
+        Ok(games_from_rows(self.query(
+            "SELECT id, map_id, cursor_x, cursor_y, version, selected_unit_id, turn,
+                active_faction, rng_seed, rng_draws, objective, result FROM games WHERE id = ?1",
+            params![id],
+            game_row,
+        )?)?
+        .pop())
+    }
+
+    fn insert_game(&self, record: DBGame) -> Result<DBGame, DBError> {
+        let id = self.insert(
+            "INSERT INTO games (map_id, cursor_x, cursor_y, version, selected_unit_id, turn,
+                active_faction, rng_seed, rng_draws, objective, result)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                record.map_id,
+                record.cursor_x,
+                record.cursor_y,
+                record.version,
+                record.selected_unit_id,
+                record.turn,
+                record.active_faction.url_frag_string(),
+                record.rng_seed,
+                record.rng_draws,
+                record.objective.url_frag_string(),
+                record
+                    .result
+                    .as_ref()
+                    .map(|result| result.url_frag_string())
+            ],
+        )?;
+        Ok(DBGame { id: id, ..record })
+    }
+
+    fn update_game(&self, record: DBGame) -> Result<(), DBError> {
+        self.execute(
+            "UPDATE games SET map_id = ?2, cursor_x = ?3, cursor_y = ?4, version = ?5,
+                selected_unit_id = ?6, turn = ?7, active_faction = ?8, rng_seed = ?9,
+                rng_draws = ?10, objective = ?11, result = ?12 WHERE id = ?1",
+            params![
+                record.id,
+                record.map_id,
+                record.cursor_x,
+                record.cursor_y,
+                record.version,
+                record.selected_unit_id,
+                record.turn,
+                record.active_faction.url_frag_string(),
+                record.rng_seed,
+                record.rng_draws,
+                record.objective.url_frag_string(),
+                record
+                    .result
+                    .as_ref()
+                    .map(|result| result.url_frag_string())
+            ],
+        )
+    }
+
+    fn read_map(&self, id: u32) -> Result<Option<DBMap>, DBError> {
+        Ok(maps_from_rows(self.query(
+            "SELECT id, default_terrain, hint_max_x, hint_max_y, cursor_x, cursor_y, name,
+                anchor_x, anchor_y FROM maps WHERE id = ?1",
+            params![id],
+            map_row,
+        )?)?
+        .pop())
+    }
+
+    fn insert_map(&self, record: DBMap) -> Result<DBMap, DBError> {
+        let id = self.insert(
+            "INSERT INTO maps (default_terrain, hint_max_x, hint_max_y, cursor_x, cursor_y, name,
+                anchor_x, anchor_y) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                record.default_terrain.url_frag_string(),
+                record.hint_max_x,
+                record.hint_max_y,
+                record.cursor_x,
+                record.cursor_y,
+                record.name,
+                record.anchor_x,
+                record.anchor_y
+            ],
+        )?;
+        Ok(DBMap { id: id, ..record })
+    }
+
+    fn update_map(&self, record: DBMap) -> Result<(), DBError> {
+        self.execute(
+            "UPDATE maps SET default_terrain = ?2, hint_max_x = ?3, hint_max_y = ?4,
+                cursor_x = ?5, cursor_y = ?6, name = ?7, anchor_x = ?8, anchor_y = ?9
+                WHERE id = ?1",
+            params![
+                record.id,
+                record.default_terrain.url_frag_string(),
+                record.hint_max_x,
+                record.hint_max_y,
+                record.cursor_x,
+                record.cursor_y,
+                record.name,
+                record.anchor_x,
+                record.anchor_y
+            ],
+        )
+    }
+
+    fn read_tile_lines_for_map_id(&self, map_id: u32) -> Result<Vec<DBTileLine>, DBError> {
+        tile_lines_from_rows(self.query(
+            "SELECT id, map_id, terrain, x, y FROM tiles WHERE map_id = ?1",
+            params![map_id],
+            positioned_row,
+        )?)
+    }
+
+    fn set_tile_line(&self, record: DBTileLine) -> Result<(), DBError> {
+        self.execute(
+            "INSERT OR REPLACE INTO tiles (map_id, terrain, x, y) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                record.map_id,
+                record.terrain.url_frag_string(),
+                record.x,
+                record.y
+            ],
+        )
+    }
+
+    fn delete_tile_line_at(&self, map_id: u32, x: u32, y: u32) -> Result<(), DBError> {
+        self.execute(
+            "DELETE FROM tiles WHERE map_id = ?1 AND x = ?2 AND y = ?3",
+            params![map_id, x, y],
+        )
+    }
+
+    fn delete_tile_lines_outside(
+        &self,
+        map_id: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), DBError> {
+        self.execute(
+            "DELETE FROM tiles WHERE map_id = ?1 AND (x >= ?2 OR y >= ?3)",
+            params![map_id, width, height],
+        )
+    }
+
+    fn paint_tile_lines(
+        &self,
+        map_id: u32,
+        tiles: &[((u32, u32), Option<models::Terrain>)],
+    ) -> Result<(), DBError> {
+        for ((x, y), terrain) in tiles.iter() {
+            match terrain {
+                Some(terrain) => self.set_tile_line(DBTileLine {
+                    id: 0,
+                    map_id: map_id,
+                    terrain: terrain.clone(),
+                    x: *x,
+                    y: *y,
+                })?,
+                None => self.delete_tile_line_at(map_id, *x, *y)?,
+            };
+        }
+        Ok(())
+    }
+
+    fn read_characters_for_game_id(&self, game_id: u32) -> Result<Vec<DBCharacter>, DBError> {
+        characters_from_rows(self.query(
+            "SELECT id, game_id, character, x, y, name, faction, moved, acted, hp, behavior
+                FROM characters WHERE game_id = ?1",
+            params![game_id],
+            character_row,
+        )?)
+    }
+
+    fn set_character(&self, record: DBCharacter) -> Result<(), DBError> {
+        self.execute(
+            "INSERT INTO characters (game_id, character, x, y, name, faction, moved, acted, hp,
+                behavior) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
+                ON CONFLICT (game_id, x, y) DO UPDATE SET character = excluded.character,
+                    hp = excluded.hp, name = CASE WHEN character = excluded.character
+                        THEN name ELSE excluded.name END",
+            params![
+                record.game_id,
+                record.character.url_frag_string(),
+                record.x,
+                record.y,
+                record.name,
+                record.faction.url_frag_string(),
+                record.moved,
+                record.acted,
+                record.hp,
+                record.behavior.url_frag_string()
+            ],
+        )
+    }
+
+    fn put_character(&self, record: DBCharacter) -> Result<(), DBError> {
+        self.execute(
+            "INSERT OR REPLACE INTO characters (id, game_id, character, x, y, name, faction,
+                moved, acted, hp, behavior) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+            params![
+                record.id,
+                record.game_id,
+                record.character.url_frag_string(),
+                record.x,
+                record.y,
+                record.name,
+                record.faction.url_frag_string(),
+                record.moved,
+                record.acted,
+                record.hp,
+                record.behavior.url_frag_string()
+            ],
+        )
+    }
+
+    fn update_character(&self, record: DBCharacter) -> Result<(), DBError> {
+        self.execute(
+            "UPDATE characters SET game_id = ?2, character = ?3, x = ?4, y = ?5, name = ?6,
+                faction = ?7, moved = ?8, acted = ?9, hp = ?10, behavior = ?11 WHERE id = ?1",
+            params![
+                record.id,
+                record.game_id,
+                record.character.url_frag_string(),
+                record.x,
+                record.y,
+                record.name,
+                record.faction.url_frag_string(),
+                record.moved,
+                record.acted,
+                record.hp,
+                record.behavior.url_frag_string()
+            ],
+        )
+    }
+
+    fn delete_character_at(&self, game_id: u32, x: u32, y: u32) -> Result<(), DBError> {
+        self.execute(
+            "DELETE FROM characters WHERE game_id = ?1 AND x = ?2 AND y = ?3",
+            params![game_id, x, y],
+        )
+    }
+
+    fn read_events_for_game_id(&self, game_id: u32) -> Result<Vec<DBEvent>, DBError> {
+        events_from_rows(self.query(
+            "SELECT id, game_id, turn, event FROM events WHERE game_id = ?1 ORDER BY id",
+            params![game_id],
+            event_row,
+        )?)
+    }
+
+    fn append_event(&self, record: DBEvent) -> Result<(), DBError> {
+        self.execute(
+            "INSERT INTO events (game_id, turn, event) VALUES (?1, ?2, ?3)",
+            params![record.game_id, record.turn, record.event.log_string()],
+        )
+    }
+
+    fn read_edits_for_game_id(&self, game_id: u32) -> Result<Vec<DBEdit>, DBError> {
+        edits_from_rows(self.query(
+            "SELECT id, game_id, undo, redo, undone FROM edits WHERE game_id = ?1 ORDER BY id",
+            params![game_id],
+            edit_row,
+        )?)
+    }
+
+    fn insert_edit(&self, record: DBEdit) -> Result<(), DBError> {
+        self.execute(
+            "INSERT INTO edits (game_id, undo, redo, undone) VALUES (?1, ?2, ?3, ?4)",
+            params![
+                record.game_id,
+                record.undo.log_string(),
+                record.redo.log_string(),
+                record.undone
+            ],
+        )
+    }
+
+    fn update_edit(&self, record: DBEdit) -> Result<(), DBError> {
+        self.execute(
+            "UPDATE edits SET game_id = ?2, undo = ?3, redo = ?4, undone = ?5 WHERE id = ?1",
+            params![
+                record.id,
+                record.game_id,
+                record.undo.log_string(),
+                record.redo.log_string(),
+                record.undone
+            ],
+        )
+    }
+
+    fn delete_undone_edits(&self, game_id: u32) -> Result<(), DBError> {
+        self.execute(
+            "DELETE FROM edits WHERE game_id = ?1 AND undone",
+            params![game_id],
+        )
+    }
+
+    fn delete_edits_for_game_id(&self, game_id: u32) -> Result<(), DBError> {
+        self.execute("DELETE FROM edits WHERE game_id = ?1", params![game_id])
+    }
+
+    fn read_starting_units_for_map_id(&self, map_id: u32) -> Result<Vec<DBStartingUnit>, DBError> {
+        starting_units_from_rows(self.query(
+            "SELECT id, map_id, character, x, y, faction, behavior FROM starting_units
+                WHERE map_id = ?1",
+            params![map_id],
+            starting_unit_row,
+        )?)
+    }
+
+    fn set_starting_unit(&self, record: DBStartingUnit) -> Result<(), DBError> {
+        self.execute(
+            "INSERT OR REPLACE INTO starting_units (map_id, character, x, y, faction, behavior)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                record.map_id,
+                record.character.url_frag_string(),
+                record.x,
+                record.y,
+                record.faction.url_frag_string(),
+                record.behavior.url_frag_string()
+            ],
+        )
+    }
+
+    fn update_starting_unit(&self, record: DBStartingUnit) -> Result<(), DBError> {
+        self.execute(
+            "UPDATE starting_units SET map_id = ?2, character = ?3, x = ?4, y = ?5, faction = ?6,
+                behavior = ?7 WHERE id = ?1",
+            params![
+                record.id,
+                record.map_id,
+                record.character.url_frag_string(),
+                record.x,
+                record.y,
+                record.faction.url_frag_string(),
+                record.behavior.url_frag_string()
+            ],
+        )
+    }
+
+    fn delete_starting_unit_at(&self, map_id: u32, x: u32, y: u32) -> Result<(), DBError> {
+        self.execute(
+            "DELETE FROM starting_units WHERE map_id = ?1 AND x = ?2 AND y = ?3",
+            params![map_id, x, y],
+        )
+    }
+
+    fn delete_starting_units_outside(
+        &self,
+        map_id: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), DBError> {
+        self.execute(
+            "DELETE FROM starting_units WHERE map_id = ?1 AND (x >= ?2 OR y >= ?3)",
+            params![map_id, width, height],
+        )
+    }
+}
+
+type GameRow = (DBGame, String, String, Option<String>);
+
+/// A game row, with its active faction, objective and result still to be
+/// parsed.
+fn game_row(row: &Row) -> rusqlite::Result<GameRow> {
+    Ok((
+        DBGame {
+            id: row.get(0)?,
+            map_id: row.get(1)?,
+            cursor_x: row.get(2)?,
+            cursor_y: row.get(3)?,
+            version: row.get(4)?,
+            selected_unit_id: row.get(5)?,
+            turn: row.get(6)?,
+            active_faction: models::Faction::Player,
+            rng_seed: row.get(8)?,
+            rng_draws: row.get(9)?,
+            objective: models::Objective::Rout,
+            result: None,
+        },
+        row.get(7)?,
+        row.get(10)?,
+        row.get(11)?,
+    ))
+}
+
+fn games_from_rows(rows: Vec<GameRow>) -> Result<Vec<DBGame>, DBError> {
+    rows.into_iter()
+        .map(|(game, active_faction, objective, result)| {
+            Ok(DBGame {
+                active_faction: faction_from_sql("games", "active_faction", active_faction)?,
+                objective: models::Objective::parse_str(&objective)
+                    .ok_or_else(|| unknown_value("games", "objective", objective))?,
+                result: match result {
+                    Some(result) => Some(
+                        models::GameResult::parse_str(&result)
+                            .ok_or_else(|| unknown_value("games", "result", result))?,
+                    ),
+                    None => None,
+                },
+                ..game
+            })
+        })
+        .collect()
+}
+
+type MapRow = (
+    u32,
+    String,
+    u32,
+    u32,
+    u32,
+    u32,
+    String,
+    Option<u32>,
+    Option<u32>,
+);
+
+fn map_row(row: &Row) -> rusqlite::Result<MapRow> {
+    Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+        row.get(5)?,
+        row.get(6)?,
+        row.get(7)?,
+        row.get(8)?,
+    ))
+}
+
+fn maps_from_rows(rows: Vec<MapRow>) -> Result<Vec<DBMap>, DBError> {
+    rows.into_iter()
+        .map(
+            |(
+                id,
+                default_terrain,
+                hint_max_x,
+                hint_max_y,
+                cursor_x,
+                cursor_y,
+                name,
+                anchor_x,
+                anchor_y,
+            )| {
+                Ok(DBMap {
+                    id: id,
+                    default_terrain: terrain_from_sql("maps", "default_terrain", default_terrain)?,
+                    hint_max_x: hint_max_x,
+                    hint_max_y: hint_max_y,
+                    cursor_x: cursor_x,
+                    cursor_y: cursor_y,
+                    name: name,
+                    anchor_x: anchor_x,
+                    anchor_y: anchor_y,
+                })
+            },
+        )
+        .collect()
+}
+
+type PositionedRow = (u32, u32, String, u32, u32);
+
+/// A tile row: id, map id, terrain and position.
+fn positioned_row(row: &Row) -> rusqlite::Result<PositionedRow> {
+    Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+    ))
+}
+
+fn tile_lines_from_rows(rows: Vec<PositionedRow>) -> Result<Vec<DBTileLine>, DBError> {
+    rows.into_iter()
+        .map(|(id, map_id, terrain, x, y)| {
+            Ok(DBTileLine {
+                id: id,
+                map_id: map_id,
+                terrain: terrain_from_sql("tiles", "terrain", terrain)?,
+                x: x,
+                y: y,
+            })
+        })
+        .collect()
+}
+
+type CharacterRow = (DBCharacter, String, String, String);
+
+/// A character row, with its class, faction and behavior still to be parsed.
+fn character_row(row: &Row) -> rusqlite::Result<CharacterRow> {
+    Ok((
+        DBCharacter {
+            id: row.get(0)?,
+            game_id: row.get(1)?,
+            character: models::Character::Knight,
+            x: row.get(3)?,
+            y: row.get(4)?,
+            name: row.get(5)?,
+            faction: models::Faction::Player,
+            behavior: models::Behavior::Aggressive,
+            hp: row.get(9)?,
+            moved: row.get(7)?,
+            acted: row.get(8)?,
+        },
+        row.get(2)?,
+        row.get(6)?,
+        row.get(10)?,
+    ))
+}
+
+fn characters_from_rows(rows: Vec<CharacterRow>) -> Result<Vec<DBCharacter>, DBError> {
+    rows.into_iter()
+        .map(|(record, character, faction, behavior)| {
+            Ok(DBCharacter {
+                character: models::Character::parse_str(&character)
+                    .ok_or_else(|| unknown_value("characters", "character", character))?,
+                faction: faction_from_sql("characters", "faction", faction)?,
+                behavior: models::Behavior::parse_str(&behavior)
+                    .ok_or_else(|| unknown_value("characters", "behavior", behavior))?,
+                ..record
+            })
+        })
+        .collect()
+}
+
+type EventRow = (u32, u32, u32, String);
+
+fn event_row(row: &Row) -> rusqlite::Result<EventRow> {
+    Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?))
+}
+
+fn events_from_rows(rows: Vec<EventRow>) -> Result<Vec<DBEvent>, DBError> {
+    rows.into_iter()
+        .map(|(id, game_id, turn, event)| {
+            Ok(DBEvent {
+                id: id,
+                game_id: game_id,
+                turn: turn,
+                event: event_from_sql("events", "event", event)?,
+            })
+        })
+        .collect()
+}
+
+type EditRow = (u32, u32, String, String, bool);
+
+fn edit_row(row: &Row) -> rusqlite::Result<EditRow> {
+    Ok((
+        row.get(0)?,
+        row.get(1)?,
+        row.get(2)?,
+        row.get(3)?,
+        row.get(4)?,
+    ))
+}
+
+fn edits_from_rows(rows: Vec<EditRow>) -> Result<Vec<DBEdit>, DBError> {
+    rows.into_iter()
+        .map(|(id, game_id, undo, redo, undone)| {
+            Ok(DBEdit {
+                id: id,
+                game_id: game_id,
+                undo: event_from_sql("edits", "undo", undo)?,
+                redo: event_from_sql("edits", "redo", redo)?,
+                undone: undone,
+            })
+        })
+        .collect()
+}
+
+type StartingUnitRow = (DBStartingUnit, String, String, String);
+
+/// A starting unit row, with its class, faction and behavior still to be
+/// parsed.
+fn starting_unit_row(row: &Row) -> rusqlite::Result<StartingUnitRow> {
+    Ok((
+        DBStartingUnit {
+            id: row.get(0)?,
+            map_id: row.get(1)?,
+            character: models::Character::Knight,
+            x: row.get(3)?,
+            y: row.get(4)?,
+            faction: models::Faction::Player,
+            behavior: models::Behavior::Aggressive,
+        },
+        row.get(2)?,
+        row.get(5)?,
+        row.get(6)?,
+    ))
+}
+
+fn starting_units_from_rows(rows: Vec<StartingUnitRow>) -> Result<Vec<DBStartingUnit>, DBError> {
+    rows.into_iter()
+        .map(|(record, character, faction, behavior)| {
+            Ok(DBStartingUnit {
+                character: models::Character::parse_str(&character)
+                    .ok_or_else(|| unknown_value("starting_units", "character", character))?,
+                faction: faction_from_sql("starting_units", "faction", faction)?,
+                behavior: models::Behavior::parse_str(&behavior)
+                    .ok_or_else(|| unknown_value("starting_units", "behavior", behavior))?,
+                ..record
+            })
+        })
+        .collect()
+}
+
+fn event_from_sql(table: &str, field: &str, event: String) -> Result<models::Event, DBError> {
+    models::Event::parse_str(&event).ok_or_else(|| unknown_value(table, field, event))
+}
+
+fn terrain_from_sql(table: &str, field: &str, terrain: String) -> Result<models::Terrain, DBError> {
+    models::Terrain::parse_str(&terrain).ok_or_else(|| unknown_value(table, field, terrain))
+}
+
+fn faction_from_sql(table: &str, field: &str, faction: String) -> Result<models::Faction, DBError> {
+    models::Faction::parse_str(&faction).ok_or_else(|| unknown_value(table, field, faction))
+}
+
+fn unknown_value(table: &str, field: &str, value: String) -> DBError {
+    DBError::ParsingRecord {
+        table: table.to_string(),
+        row: None,
+        field: Some(field.to_string()),
+        cause: Cause::UnknownValue(value),
+    }
+}
@@ -0,0 +1,787 @@
+use std::collections::BTreeSet;
+
+use crate::models;
+
+use super::common::DBError;
+use super::records::{
+    DBCharacter, DBEdit, DBEvent, DBGame, DBIdCounter, DBMap, DBStartingUnit, DBTileLine,
+};
+use super::transaction::TableWrites;
+
+/// Where the records behind `DB` live.
+///
+/// Implementations only have to read and write whole tables; the row-level
+/// operations default to a read-modify-write of the table they touch, and
+/// backends that can do better (like sqlite) override them.
+pub trait Storage {
+    fn read_games(&self) -> Result<Vec<DBGame>, DBError>;
+    fn write_games(&self, records: Vec<DBGame>) -> Result<(), DBError>;
+
+    fn read_maps(&self) -> Result<Vec<DBMap>, DBError>;
+    fn write_maps(&self, records: Vec<DBMap>) -> Result<(), DBError>;
+
+    fn read_tile_lines(&self) -> Result<Vec<DBTileLine>, DBError>;
+    fn write_tile_lines(&self, records: Vec<DBTileLine>) -> Result<(), DBError>;
+
+    fn read_characters(&self) -> Result<Vec<DBCharacter>, DBError>;
+    fn write_characters(&self, records: Vec<DBCharacter>) -> Result<(), DBError>;
+
+    fn read_events(&self) -> Result<Vec<DBEvent>, DBError>;
+    fn write_events(&self, records: Vec<DBEvent>) -> Result<(), DBError>;
+
+    fn read_edits(&self) -> Result<Vec<DBEdit>, DBError>;
+    fn write_edits(&self, records: Vec<DBEdit>) -> Result<(), DBError>;
+
+    fn read_starting_units(&self) -> Result<Vec<DBStartingUnit>, DBError>;
+    fn write_starting_units(&self, records: Vec<DBStartingUnit>) -> Result<(), DBError>;
+
+    fn read_id_counters(&self) -> Result<Vec<DBIdCounter>, DBError>;
+    fn write_id_counters(&self, records: Vec<DBIdCounter>) -> Result<(), DBError>;
+
+    /// Replaces every table present in `writes` and adds its appended events,
+    /// all of them or none.
+    fn write_tables(&self, writes: TableWrites) -> Result<(), DBError>;
+
+    /// Runs `f` against a view of this storage whose writes are only kept if
+    /// `f` returns `Ok`, and then all together.
+    ///
+    /// Backends without transactions of their own can use
+    /// `transaction::staged_transaction`.
+    fn transaction(
+        &self,
+        f: &mut dyn FnMut(&dyn Storage) -> Result<(), DBError>,
+    ) -> Result<(), DBError>;
+
+    /// Hands out `count` ids for new rows of `table`, returning the first.
+    /// They come after `highest_id` (the highest id in the table now) and
+    /// after every id the table has handed out before, so a deleted row's id
+    /// is never given to another.
+    fn take_ids(&self, table: &str, highest_id: u32, count: u32) -> Result<u32, DBError> {
+        let mut records = self.read_id_counters()?;
+        let first_id = match records.iter().find(|r| r.table == table) {
+            Some(counter) => std::cmp::max(counter.last_id, highest_id) + 1,
+            None => highest_id + 1,
+        };
+        records.retain(|r| r.table != table);
+        records.push(DBIdCounter {
+            table: table.to_string(),
+            last_id: first_id + count - 1,
+        });
+        self.write_id_counters(records)?;
+        Ok(first_id)
+    }
+
+    fn read_game(&self, id: u32) -> Result<Option<DBGame>, DBError> {
+        Ok(self
+            .read_games()?
+            .into_iter()
+            .find(|record| record.id == id))
+    }
+
+    /// Stores a new game, ignoring `record.id` and returning the row as
+    /// stored with its freshly assigned id.
+    fn insert_game(&self, record: DBGame) -> Result<DBGame, DBError> {
+        let mut records = self.read_games()?;
+        let new_record = DBGame {
+            id: next_id(records.iter().map(|r| r.id)),
+            ..record
+        };
+        records.push(new_record.clone());
+        self.write_games(records)?;
+        Ok(new_record)
+    }
+
+    fn update_game(&self, record: DBGame) -> Result<(), DBError> {
+        let records = self
+            .read_games()?
+            .into_iter()
+            .map(|existing| {
+                if existing.id == record.id {
+                    record.clone()
+                } else {
+                    existing
+                }
+            })
+            .collect();
+        self.write_games(records)
+    }
+
+    fn read_map(&self, id: u32) -> Result<Option<DBMap>, DBError> {
+        Ok(self.read_maps()?.into_iter().find(|record| record.id == id))
+    }
+
+    /// Stores a new map, ignoring `record.id` and returning the row as stored
+    /// with its freshly assigned id.
+    fn insert_map(&self, record: DBMap) -> Result<DBMap, DBError> {
+        let mut records = self.read_maps()?;
+        let new_record = DBMap {
+            id: next_id(records.iter().map(|r| r.id)),
+            ..record
+        };
+        records.push(new_record.clone());
+        self.write_maps(records)?;
+        Ok(new_record)
+    }
+
+    fn update_map(&self, record: DBMap) -> Result<(), DBError> {
+        let records = self
+            .read_maps()?
+            .into_iter()
+            .map(|existing| {
+                if existing.id == record.id {
+                    record.clone()
+                } else {
+                    existing
+                }
+            })
+            .collect();
+        self.write_maps(records)
+    }
+
+    fn read_tile_lines_for_map_id(&self, map_id: u32) -> Result<Vec<DBTileLine>, DBError> {
+        Ok(self
+            .read_tile_lines()?
+            .into_iter()
+            .filter(|record| record.map_id == map_id)
+            .collect())
+    }
+
+    /// Stores `record` as the only tile at its map and position, replacing
+    /// any tile already there and assigning it a fresh id.
+    fn set_tile_line(&self, record: DBTileLine) -> Result<(), DBError> {
+        let records = self.read_tile_lines()?;
+        let new_record = DBTileLine {
+            id: self.take_ids("tiles", highest_id(records.iter().map(|r| r.id)), 1)?,
+            ..record
+        };
+        let mut records: Vec<DBTileLine> = records
+            .into_iter()
+            .filter(|r| {
+                !(r.map_id == new_record.map_id && r.x == new_record.x && r.y == new_record.y)
+            })
+            .collect();
+        records.push(new_record);
+        self.write_tile_lines(records)
+    }
+
+    fn delete_tile_line_at(&self, map_id: u32, x: u32, y: u32) -> Result<(), DBError> {
+        let records = self
+            .read_tile_lines()?
+            .into_iter()
+            .filter(|r| !(r.map_id == map_id && r.x == x && r.y == y))
+            .collect();
+        self.write_tile_lines(records)
+    }
+
+    /// Removes the map's tiles outside the `width` by `height` tiles in its
+    /// bottom left corner, all in one write.
+    fn delete_tile_lines_outside(
+        &self,
+        map_id: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), DBError> {
+        let records = self
+            .read_tile_lines()?
+            .into_iter()
+            .filter(|r| !(r.map_id == map_id && (r.x >= width || r.y >= height)))
+            .collect();
+        self.write_tile_lines(records)
+    }
+
+    /// Sets the terrain of the map at each of `tiles`' positions, or where
+    /// it is `None` goes back to the map's default, reading and writing the
+    /// table only once however many tiles there are.
+    fn paint_tile_lines(
+        &self,
+        map_id: u32,
+        tiles: &[((u32, u32), Option<models::Terrain>)],
+    ) -> Result<(), DBError> {
+        let painted: BTreeSet<(u32, u32)> = tiles.iter().map(|&(position, _)| position).collect();
+        let records = self.read_tile_lines()?;
+        let mut id = self.take_ids(
+            "tiles",
+            highest_id(records.iter().map(|r| r.id)),
+            tiles
+                .iter()
+                .filter(|(_, terrain)| terrain.is_some())
+                .count() as u32,
+        )?;
+        let mut records: Vec<DBTileLine> = records
+            .into_iter()
+            .filter(|r| !(r.map_id == map_id && painted.contains(&(r.x, r.y))))
+            .collect();
+        for (position, terrain) in tiles.iter() {
+            match terrain {
+                Some(terrain) => {
+                    records.push(DBTileLine {
+                        id: id,
+                        map_id: map_id,
+                        terrain: terrain.clone(),
+                        x: position.0,
+                        y: position.1,
+                    });
+                    id += 1;
+                }
+                None => (),
+            };
+        }
+        self.write_tile_lines(records)
+    }
+
+    fn read_characters_for_game_id(&self, game_id: u32) -> Result<Vec<DBCharacter>, DBError> {
+        Ok(self
+            .read_characters()?
+            .into_iter()
+            .filter(|record| record.game_id == game_id)
+            .collect())
+    }
+
+    /// Stores `record` as the only character at its game and position.
+    ///
+    /// A character already there keeps everything but its class and hp, which
+    /// it takes from `record`, along with `record`'s name if its class changes;
+    /// otherwise the record is added with a fresh id.
+    fn set_character(&self, record: DBCharacter) -> Result<(), DBError> {
+        let mut records = self.read_characters()?;
+        let new_record = match records
+            .iter()
+            .find(|r| r.game_id == record.game_id && r.x == record.x && r.y == record.y)
+        {
+            Some(existing) => DBCharacter {
+                name: if existing.character == record.character {
+                    existing.name.clone()
+                } else {
+                    record.name
+                },
+                character: record.character,
+                hp: record.hp,
+                ..existing.clone()
+            },
+            None => DBCharacter {
+                id: self.take_ids("characters", highest_id(records.iter().map(|r| r.id)), 1)?,
+                ..record
+            },
+        };
+        records.retain(|r| {
+            !(r.game_id == new_record.game_id && r.x == new_record.x && r.y == new_record.y)
+        });
+        records.push(new_record);
+        self.write_characters(records)
+    }
+
+    /// Stores `record` exactly as it is, id and all, replacing both any
+    /// character with its id and any at its game and position.
+    fn put_character(&self, record: DBCharacter) -> Result<(), DBError> {
+        let mut records = self.read_characters()?;
+        records.retain(|r| {
+            !(r.id == record.id
+                || (r.game_id == record.game_id && r.x == record.x && r.y == record.y))
+        });
+        records.push(record);
+        self.write_characters(records)
+    }
+
+    /// Replaces the character with `record.id`, wherever it now stands.
+    fn update_character(&self, record: DBCharacter) -> Result<(), DBError> {
+        let records = self
+            .read_characters()?
+            .into_iter()
+            .map(|existing| {
+                if existing.id == record.id {
+                    record.clone()
+                } else {
+                    existing
+                }
+            })
+            .collect();
+        self.write_characters(records)
+    }
+
+    fn delete_character_at(&self, game_id: u32, x: u32, y: u32) -> Result<(), DBError> {
+        let records = self
+            .read_characters()?
+            .into_iter()
+            .filter(|r| !(r.game_id == game_id && r.x == x && r.y == y))
+            .collect();
+        self.write_characters(records)
+    }
+
+    /// A game's events, oldest first.
+    fn read_events_for_game_id(&self, game_id: u32) -> Result<Vec<DBEvent>, DBError> {
+        let mut records: Vec<DBEvent> = self
+            .read_events()?
+            .into_iter()
+            .filter(|record| record.game_id == game_id)
+            .collect();
+        records.sort_by_key(|record| record.id);
+        Ok(records)
+    }
+
+    /// The id the next event appended will get.
+    fn next_event_id(&self) -> Result<u32, DBError> {
+        Ok(next_id(self.read_events()?.iter().map(|r| r.id)))
+    }
+
+    /// Adds `record` to the end of its game's log, ignoring `record.id`.
+    fn append_event(&self, record: DBEvent) -> Result<(), DBError> {
+        let mut records = self.read_events()?;
+        let new_record = DBEvent {
+            id: next_id(records.iter().map(|r| r.id)),
+            ..record
+        };
+        records.push(new_record);
+        self.write_events(records)
+    }
+
+    /// A game's edits, oldest first.
+    fn read_edits_for_game_id(&self, game_id: u32) -> Result<Vec<DBEdit>, DBError> {
+        let mut records: Vec<DBEdit> = self
+            .read_edits()?
+            .into_iter()
+            .filter(|record| record.game_id == game_id)
+            .collect();
+        records.sort_by_key(|record| record.id);
+        Ok(records)
+    }
+
+    /// Stores a new edit, ignoring `record.id`.
+    fn insert_edit(&self, record: DBEdit) -> Result<(), DBError> {
+        let mut records = self.read_edits()?;
+        let new_record = DBEdit {
+            id: self.take_ids("edits", highest_id(records.iter().map(|r| r.id)), 1)?,
+            ..record
+        };
+        records.push(new_record);
+        self.write_edits(records)
+    }
+
+    fn update_edit(&self, record: DBEdit) -> Result<(), DBError> {
+        let records = self
+            .read_edits()?
+            .into_iter()
+            .map(|existing| {
+                if existing.id == record.id {
+                    record.clone()
+                } else {
+                    existing
+                }
+            })
+            .collect();
+        self.write_edits(records)
+    }
+
+    /// Forgets the game's undone edits, which can no longer be redone once
+    /// something else is edited.
+    fn delete_undone_edits(&self, game_id: u32) -> Result<(), DBError> {
+        let records = self
+            .read_edits()?
+            .into_iter()
+            .filter(|r| !(r.game_id == game_id && r.undone))
+            .collect();
+        self.write_edits(records)
+    }
+
+    /// Forgets all of the game's edits, leaving nothing to undo or redo.
+    fn delete_edits_for_game_id(&self, game_id: u32) -> Result<(), DBError> {
+        let records = self.read_edits()?;
+        if !records.iter().any(|r| r.game_id == game_id) {
+            return Ok(());
+        }
+        self.write_edits(
+            records
+                .into_iter()
+                .filter(|r| r.game_id != game_id)
+                .collect(),
+        )
+    }
+
+    fn read_starting_units_for_map_id(&self, map_id: u32) -> Result<Vec<DBStartingUnit>, DBError> {
+        Ok(self
+            .read_starting_units()?
+            .into_iter()
+            .filter(|record| record.map_id == map_id)
+            .collect())
+    }
+
+    /// Stores `record` as the only starting unit at its map and position,
+    /// replacing any already there and assigning it a fresh id.
+    fn set_starting_unit(&self, record: DBStartingUnit) -> Result<(), DBError> {
+        let records = self.read_starting_units()?;
+        let new_record = DBStartingUnit {
+            id: self.take_ids(
+                "starting_units",
+                highest_id(records.iter().map(|r| r.id)),
+                1,
+            )?,
+            ..record
+        };
+        let mut records: Vec<DBStartingUnit> = records
+            .into_iter()
+            .filter(|r| {
+                !(r.map_id == new_record.map_id && r.x == new_record.x && r.y == new_record.y)
+            })
+            .collect();
+        records.push(new_record);
+        self.write_starting_units(records)
+    }
+
+    fn update_starting_unit(&self, record: DBStartingUnit) -> Result<(), DBError> {
+        let records = self
+            .read_starting_units()?
+            .into_iter()
+            .map(|existing| {
+                if existing.id == record.id {
+                    record.clone()
+                } else {
+                    existing
+                }
+            })
+            .collect();
+        self.write_starting_units(records)
+    }
+
+    fn delete_starting_unit_at(&self, map_id: u32, x: u32, y: u32) -> Result<(), DBError> {
+        let records = self
+            .read_starting_units()?
+            .into_iter()
+            .filter(|r| !(r.map_id == map_id && r.x == x && r.y == y))
+            .collect();
+        self.write_starting_units(records)
+    }
+
+    /// Removes the map's starting units outside the `width` by `height`
+    /// tiles in its bottom left corner, all in one write.
+    fn delete_starting_units_outside(
+        &self,
+        map_id: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<(), DBError> {
+        let records = self
+            .read_starting_units()?
+            .into_iter()
+            .filter(|r| !(r.map_id == map_id && (r.x >= width || r.y >= height)))
+            .collect();
+        self.write_starting_units(records)
+    }
+}
+
+pub fn next_id<I: Iterator<Item = u32>>(ids: I) -> u32 {
+    highest_id(ids) + 1
+}
+
+fn highest_id<I: Iterator<Item = u32>>(ids: I) -> u32 {
+    ids.fold(0, std::cmp::max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::common::TempDir;
+    use crate::db::csv_storage::CsvStorage;
+    use crate::db::memory_storage::MemoryStorage;
+    use crate::db::sqlite_storage::SqliteStorage;
+
+    #[test]
+    fn fresh_memory_storage_adds_reads_and_updates() {
+        check_add_read_update(&MemoryStorage::new());
+    }
+
+    #[test]
+    fn fresh_csv_storage_adds_reads_and_updates() {
+        let dir = TempDir::new("fresh-csv");
+        check_add_read_update(&CsvStorage::new(dir.path()).unwrap());
+        // And everything is still there when the tables are opened again.
+        check_read_back(&CsvStorage::new(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn memory_storage_renames_characters_that_change_class() {
+        check_renamed_on_class_change(&MemoryStorage::new());
+    }
+
+    #[test]
+    fn sqlite_storage_renames_characters_that_change_class() {
+        let dir = TempDir::new("rename-sqlite");
+        check_renamed_on_class_change(&SqliteStorage::new(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn memory_storage_appends_events() {
+        check_appends_events(&MemoryStorage::new());
+    }
+
+    #[test]
+    fn csv_storage_appends_events() {
+        let dir = TempDir::new("append-csv");
+        check_appends_events(&CsvStorage::new(dir.path()).unwrap());
+        // The header was written along with the first rows.
+        let events = CsvStorage::new(dir.path()).unwrap().read_events().unwrap();
+        assert_eq!(events.len(), 3);
+    }
+
+    #[test]
+    fn sqlite_storage_appends_events() {
+        let dir = TempDir::new("append-sqlite");
+        check_appends_events(&SqliteStorage::new(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn memory_storage_never_reuses_ids() {
+        check_never_reuses_ids(&MemoryStorage::new());
+    }
+
+    #[test]
+    fn csv_storage_never_reuses_ids() {
+        let dir = TempDir::new("ids-csv");
+        check_never_reuses_ids(&CsvStorage::new(dir.path()).unwrap());
+    }
+
+    #[test]
+    fn sqlite_storage_never_reuses_ids() {
+        let dir = TempDir::new("ids-sqlite");
+        check_never_reuses_ids(&SqliteStorage::new(dir.path()).unwrap());
+    }
+
+    /// Adds knights at (0, 0), (1, 0) and (2, 0), removing the one at (1, 0)
+    /// (the highest id at the time) before the third is added.
+    fn check_never_reuses_ids(storage: &dyn Storage) {
+        let knight = |x: u32| DBCharacter {
+            id: 0,
+            game_id: 1,
+            character: models::Character::Knight,
+            x: x,
+            y: 0,
+            name: format!("Knight {}", x + 1),
+            faction: models::Faction::Player,
+            behavior: models::Behavior::Aggressive,
+            hp: models::Character::Knight.max_hp(),
+            moved: false,
+            acted: false,
+        };
+        storage.set_character(knight(0)).unwrap();
+        storage
+            .transaction(&mut |tx| {
+                tx.set_character(knight(1))?;
+                tx.delete_character_at(1, 1, 0)
+            })
+            .unwrap();
+        storage.set_character(knight(2)).unwrap();
+
+        let mut ids: Vec<(u32, u32)> = storage
+            .read_characters_for_game_id(1)
+            .unwrap()
+            .iter()
+            .map(|r| (r.id, r.x))
+            .collect();
+        ids.sort();
+        assert_eq!(ids, vec![(1, 0), (3, 2)]);
+    }
+
+    fn event(game_id: u32, event: models::Event) -> DBEvent {
+        DBEvent {
+            id: 0,
+            game_id: game_id,
+            turn: 1,
+            event: event,
+        }
+    }
+
+    fn check_appends_events(storage: &dyn Storage) {
+        storage
+            .append_event(event(1, models::Event::Cursor { position: (1, 0) }))
+            .unwrap();
+
+        storage
+            .transaction(&mut |tx| {
+                tx.append_event(event(1, models::Event::EndTurn))?;
+                tx.append_event(event(2, models::Event::Deselect))?;
+                // Appended events are read back before they are stored.
+                let ids: Vec<u32> = tx
+                    .read_events_for_game_id(1)?
+                    .iter()
+                    .map(|r| r.id)
+                    .collect();
+                assert_eq!(ids, vec![1, 2]);
+                Ok(())
+            })
+            .unwrap();
+
+        // Nothing from a failed transaction is kept.
+        let failed = storage.transaction(&mut |tx| {
+            tx.append_event(event(1, models::Event::EndTurn))?;
+            Err(DBError::finding_record("events"))
+        });
+        assert!(failed.is_err());
+        assert_eq!(storage.next_event_id().unwrap(), 4);
+
+        let events = storage.read_events().unwrap();
+        let ids: Vec<(u32, u32)> = events.iter().map(|r| (r.id, r.game_id)).collect();
+        assert_eq!(ids, vec![(1, 1), (2, 1), (3, 2)]);
+        assert_eq!(events[1].event.log_string(), "end-turn");
+    }
+
+    fn check_renamed_on_class_change(storage: &dyn Storage) {
+        let knight = DBCharacter {
+            id: 0,
+            game_id: 1,
+            character: models::Character::Knight,
+            x: 1,
+            y: 1,
+            name: "Knight 1".to_string(),
+            faction: models::Faction::Enemy,
+            behavior: models::Behavior::Guard,
+            hp: 5,
+            moved: false,
+            acted: false,
+        };
+        storage.set_character(knight.clone()).unwrap();
+
+        // The same class again keeps the name it already had.
+        storage
+            .set_character(DBCharacter {
+                name: "Knight 2".to_string(),
+                hp: models::Character::Knight.max_hp(),
+                ..knight.clone()
+            })
+            .unwrap();
+        let characters = storage.read_characters_for_game_id(1).unwrap();
+        assert_eq!(characters.len(), 1);
+        assert_eq!(characters[0].name, "Knight 1");
+        assert_eq!(characters[0].hp, models::Character::Knight.max_hp());
+
+        // A new class takes the new name, and keeps the id, faction and
+        // behavior.
+        storage
+            .set_character(DBCharacter {
+                character: models::Character::Thief,
+                name: "Thief 1".to_string(),
+                faction: models::Faction::Player,
+                behavior: models::Behavior::Aggressive,
+                hp: models::Character::Thief.max_hp(),
+                ..knight
+            })
+            .unwrap();
+        let characters = storage.read_characters_for_game_id(1).unwrap();
+        assert_eq!(characters.len(), 1);
+        assert_eq!(characters[0].id, 1);
+        assert!(characters[0].character == models::Character::Thief);
+        assert_eq!(characters[0].name, "Thief 1");
+        assert!(characters[0].faction == models::Faction::Enemy);
+        assert!(characters[0].behavior == models::Behavior::Guard);
+        assert_eq!(characters[0].hp, models::Character::Thief.max_hp());
+    }
+
+    fn check_add_read_update(storage: &dyn Storage) {
+        assert!(storage.read_games().unwrap().is_empty());
+        assert!(storage.read_maps().unwrap().is_empty());
+        assert!(storage.read_tile_lines().unwrap().is_empty());
+        assert!(storage.read_characters().unwrap().is_empty());
+
+        let map = storage
+            .insert_map(DBMap {
+                id: 0,
+                default_terrain: models::Terrain::Grass,
+                hint_max_x: 4,
+                hint_max_y: 3,
+                cursor_x: 0,
+                cursor_y: 0,
+                name: "".to_string(),
+                anchor_x: None,
+                anchor_y: None,
+            })
+            .unwrap();
+        assert_eq!(map.id, 1);
+        let game = storage
+            .insert_game(DBGame {
+                id: 0,
+                map_id: map.id,
+                cursor_x: 0,
+                cursor_y: 0,
+                version: 0,
+                selected_unit_id: None,
+                turn: 1,
+                active_faction: models::Faction::Player,
+                rng_seed: 7,
+                rng_draws: 0,
+                objective: models::Objective::Rout,
+                result: None,
+            })
+            .unwrap();
+        assert_eq!(game.id, 1);
+
+        storage
+            .set_tile_line(DBTileLine {
+                id: 0,
+                map_id: map.id,
+                terrain: models::Terrain::Rock,
+                x: 2,
+                y: 1,
+            })
+            .unwrap();
+        storage
+            .set_character(DBCharacter {
+                id: 0,
+                game_id: game.id,
+                character: models::Character::Mage,
+                x: 1,
+                y: 2,
+                name: "Ada".to_string(),
+                faction: models::Faction::Enemy,
+                behavior: models::Behavior::Guard,
+                hp: models::Character::Mage.max_hp(),
+                moved: false,
+                acted: false,
+            })
+            .unwrap();
+
+        storage
+            .update_map(DBMap {
+                name: "Forest Pass".to_string(),
+                ..map
+            })
+            .unwrap();
+        storage
+            .update_game(DBGame {
+                cursor_x: 3,
+                cursor_y: 2,
+                turn: 2,
+                ..game
+            })
+            .unwrap();
+
+        check_read_back(storage);
+    }
+
+    fn check_read_back(storage: &dyn Storage) {
+        let maps = storage.read_maps().unwrap();
+        assert_eq!(maps.len(), 1);
+        assert_eq!(maps[0].id, 1);
+        assert!(maps[0].default_terrain == models::Terrain::Grass);
+        assert_eq!((maps[0].hint_max_x, maps[0].hint_max_y), (4, 3));
+        assert_eq!(maps[0].name, "Forest Pass");
+
+        let game = storage.read_game(1).unwrap().unwrap();
+        assert_eq!(game.map_id, 1);
+        assert_eq!((game.cursor_x, game.cursor_y), (3, 2));
+        assert_eq!(game.turn, 2);
+        assert_eq!(game.rng_seed, 7);
+        assert!(storage.read_game(2).unwrap().is_none());
+
+        let tile_lines = storage.read_tile_lines_for_map_id(1).unwrap();
+        assert_eq!(tile_lines.len(), 1);
+        assert!(tile_lines[0].terrain == models::Terrain::Rock);
+        assert_eq!((tile_lines[0].x, tile_lines[0].y), (2, 1));
+
+        let characters = storage.read_characters_for_game_id(1).unwrap();
+        assert_eq!(characters.len(), 1);
+        assert_eq!(characters[0].id, 1);
+        assert!(matches!(characters[0].character, models::Character::Mage));
+        assert_eq!((characters[0].x, characters[0].y), (1, 2));
+        assert_eq!(characters[0].name, "Ada");
+        assert!(characters[0].faction == models::Faction::Enemy);
+        assert!(characters[0].behavior == models::Behavior::Guard);
+        assert_eq!(characters[0].hp, models::Character::Mage.max_hp());
+    }
+}
@@ -0,0 +1,299 @@
+use std::cell::{Cell, RefCell};
+
+use super::common::DBError;
+use super::records::{
+    DBCharacter, DBEdit, DBEvent, DBGame, DBIdCounter, DBMap, DBStartingUnit, DBTileLine,
+};
+use super::storage::{next_id, Storage};
+
+/// Whole-table replacements to be stored together; tables left as `None` are
+/// untouched.
+#[derive(Default)]
+pub struct TableWrites {
+    pub games: Option<Vec<DBGame>>,
+    pub maps: Option<Vec<DBMap>>,
+    pub tile_lines: Option<Vec<DBTileLine>>,
+    pub characters: Option<Vec<DBCharacter>>,
+    pub events: Option<Vec<DBEvent>>,
+    /// Events to add after whatever is in the events table, ids already
+    /// given. Only ever set when `events` is `None`.
+    pub appended_events: Vec<DBEvent>,
+    pub edits: Option<Vec<DBEdit>>,
+    pub starting_units: Option<Vec<DBStartingUnit>>,
+    pub id_counters: Option<Vec<DBIdCounter>>,
+}
+
+/// `Storage::transaction` for backends that can only replace whole tables.
+///
+/// `f` runs against a view that keeps every table it writes in memory (and
+/// reads those tables back from there), then all written tables are handed to
+/// `base.write_tables` at once. If `f` fails nothing is written.
+pub fn staged_transaction(
+    base: &dyn Storage,
+    f: &mut dyn FnMut(&dyn Storage) -> Result<(), DBError>,
+) -> Result<(), DBError> {
+    let staged = StagedStorage {
+        base: base,
+        writes: RefCell::new(TableWrites::default()),
+        event_ids: Cell::new(None),
+    };
+    f(&staged)?;
+    base.write_tables(staged.writes.into_inner())
+}
+
+struct StagedStorage<'a> {
+    base: &'a dyn Storage,
+    writes: RefCell<TableWrites>,
+    /// The id the next appended event gets, once worked out.
+    event_ids: Cell<Option<u32>>,
+}
+
+impl<'a> Storage for StagedStorage<'a> {
+    fn read_games(&self) -> Result<Vec<DBGame>, DBError> {
+        match &self.writes.borrow().games {
+            Some(records) => Ok(records.clone()),
+            None => self.base.read_games(),
+        }
+    }
+
+    fn write_games(&self, records: Vec<DBGame>) -> Result<(), DBError> {
+        self.writes.borrow_mut().games = Some(records);
+        Ok(())
+    }
+
+    fn read_maps(&self) -> Result<Vec<DBMap>, DBError> {
+        match &self.writes.borrow().maps {
+            Some(records) => Ok(records.clone()),
+            None => self.base.read_maps(),
+        }
+    }
+
+    fn write_maps(&self, records: Vec<DBMap>) -> Result<(), DBError> {
+        self.writes.borrow_mut().maps = Some(records);
+        Ok(())
+    }
+
+    fn read_tile_lines(&self) -> Result<Vec<DBTileLine>, DBError> {
+        match &self.writes.borrow().tile_lines {
+            Some(records) => Ok(records.clone()),
+            None => self.base.read_tile_lines(),
+        }
+    }
+
+    fn write_tile_lines(&self, records: Vec<DBTileLine>) -> Result<(), DBError> {
+        self.writes.borrow_mut().tile_lines = Some(records);
+        Ok(())
+    }
+
+    fn read_characters(&self) -> Result<Vec<DBCharacter>, DBError> {
+        match &self.writes.borrow().characters {
+            Some(records) => Ok(records.clone()),
+            None => self.base.read_characters(),
+        }
+    }
+
+    fn write_characters(&self, records: Vec<DBCharacter>) -> Result<(), DBError> {
+        self.writes.borrow_mut().characters = Some(records);
+        Ok(())
+    }
+
+    fn read_events(&self) -> Result<Vec<DBEvent>, DBError> {
+        let writes = self.writes.borrow();
+        match &writes.events {
+            Some(records) => Ok(records.clone()),
+            None => {
+                let mut records = self.base.read_events()?;
+                records.extend(writes.appended_events.iter().cloned());
+                Ok(records)
+            }
+        }
+    }
+
+    fn write_events(&self, records: Vec<DBEvent>) -> Result<(), DBError> {
+        let mut writes = self.writes.borrow_mut();
+        writes.events = Some(records);
+        writes.appended_events.clear();
+        self.event_ids.set(None);
+        Ok(())
+    }
+
+    fn read_edits(&self) -> Result<Vec<DBEdit>, DBError> {
+        match &self.writes.borrow().edits {
+            Some(records) => Ok(records.clone()),
+            None => self.base.read_edits(),
+        }
+    }
+
+    fn write_edits(&self, records: Vec<DBEdit>) -> Result<(), DBError> {
+        self.writes.borrow_mut().edits = Some(records);
+        Ok(())
+    }
+
+    fn read_starting_units(&self) -> Result<Vec<DBStartingUnit>, DBError> {
+        match &self.writes.borrow().starting_units {
+            Some(records) => Ok(records.clone()),
+            None => self.base.read_starting_units(),
+        }
+    }
+
+    fn write_starting_units(&self, records: Vec<DBStartingUnit>) -> Result<(), DBError> {
+        self.writes.borrow_mut().starting_units = Some(records);
+        Ok(())
+    }
+
+    fn read_id_counters(&self) -> Result<Vec<DBIdCounter>, DBError> {
+        match &self.writes.borrow().id_counters {
+            Some(records) => Ok(records.clone()),
+            None => self.base.read_id_counters(),
+        }
+    }
+
+    fn write_id_counters(&self, records: Vec<DBIdCounter>) -> Result<(), DBError> {
+        self.writes.borrow_mut().id_counters = Some(records);
+        Ok(())
+    }
+
+    fn write_tables(&self, writes: TableWrites) -> Result<(), DBError> {
+        let mut staged = self.writes.borrow_mut();
+        self.event_ids.set(None);
+        if writes.games.is_some() {
+            staged.games = writes.games;
+        }
+        if writes.maps.is_some() {
+            staged.maps = writes.maps;
+        }
+        if writes.tile_lines.is_some() {
+            staged.tile_lines = writes.tile_lines;
+        }
+        if writes.characters.is_some() {
+            staged.characters = writes.characters;
+        }
+        if writes.events.is_some() {
+            staged.events = writes.events;
+            staged.appended_events.clear();
+        }
+        for record in writes.appended_events.into_iter() {
+            match &mut staged.events {
+                Some(records) => records.push(record),
+                None => staged.appended_events.push(record),
+            };
+        }
+        if writes.edits.is_some() {
+            staged.edits = writes.edits;
+        }
+        if writes.starting_units.is_some() {
+            staged.starting_units = writes.starting_units;
+        }
+        if writes.id_counters.is_some() {
+            staged.id_counters = writes.id_counters;
+        }
+        Ok(())
+    }
+
+    /// Nested transactions join the one already being staged.
+    fn transaction(
+        &self,
+        f: &mut dyn FnMut(&dyn Storage) -> Result<(), DBError>,
+    ) -> Result<(), DBError> {
+        f(self)
+    }
+
+    // Lookups on tables that have not been written yet go to the base
+    // storage, which may have a faster way to make them.
+
+    fn read_game(&self, id: u32) -> Result<Option<DBGame>, DBError> {
+        match &self.writes.borrow().games {
+            Some(records) => Ok(records.iter().find(|r| r.id == id).cloned()),
+            None => self.base.read_game(id),
+        }
+    }
+
+    fn read_map(&self, id: u32) -> Result<Option<DBMap>, DBError> {
+        match &self.writes.borrow().maps {
+            Some(records) => Ok(records.iter().find(|r| r.id == id).cloned()),
+            None => self.base.read_map(id),
+        }
+    }
+
+    fn read_tile_lines_for_map_id(&self, map_id: u32) -> Result<Vec<DBTileLine>, DBError> {
+        match &self.writes.borrow().tile_lines {
+            Some(records) => Ok(records
+                .iter()
+                .filter(|r| r.map_id == map_id)
+                .cloned()
+                .collect()),
+            None => self.base.read_tile_lines_for_map_id(map_id),
+        }
+    }
+
+    fn read_characters_for_game_id(&self, game_id: u32) -> Result<Vec<DBCharacter>, DBError> {
+        match &self.writes.borrow().characters {
+            Some(records) => Ok(records
+                .iter()
+                .filter(|r| r.game_id == game_id)
+                .cloned()
+                .collect()),
+            None => self.base.read_characters_for_game_id(game_id),
+        }
+    }
+
+    fn read_events_for_game_id(&self, game_id: u32) -> Result<Vec<DBEvent>, DBError> {
+        let writes = self.writes.borrow();
+        match &writes.events {
+            Some(records) => {
+                let mut records: Vec<DBEvent> = records
+                    .iter()
+                    .filter(|r| r.game_id == game_id)
+                    .cloned()
+                    .collect();
+                records.sort_by_key(|r| r.id);
+                Ok(records)
+            }
+            None => {
+                let mut records = self.base.read_events_for_game_id(game_id)?;
+                records.extend(
+                    writes
+                        .appended_events
+                        .iter()
+                        .filter(|r| r.game_id == game_id)
+                        .cloned(),
+                );
+                Ok(records)
+            }
+        }
+    }
+
+    /// Worked out once per transaction, and counted on from there.
+    fn next_event_id(&self) -> Result<u32, DBError> {
+        match self.event_ids.get() {
+            Some(id) => return Ok(id),
+            None => (),
+        };
+        let id = {
+            let writes = self.writes.borrow();
+            match &writes.events {
+                Some(records) => next_id(records.iter().map(|r| r.id)),
+                None => std::cmp::max(
+                    self.base.next_event_id()?,
+                    next_id(writes.appended_events.iter().map(|r| r.id)),
+                ),
+            }
+        };
+        self.event_ids.set(Some(id));
+        Ok(id)
+    }
+
+    /// Keeps the event to be appended on its own, so backends that can add
+    /// rows to a table without rewriting it do so.
+    fn append_event(&self, record: DBEvent) -> Result<(), DBError> {
+        let id = self.next_event_id()?;
+        self.event_ids.set(Some(id + 1));
+        let record = DBEvent { id: id, ..record };
+        let mut writes = self.writes.borrow_mut();
+        match &mut writes.events {
+            Some(records) => records.push(record),
+            None => writes.appended_events.push(record),
+        };
+        Ok(())
+    }
+}
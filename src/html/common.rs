@@ -79,6 +79,23 @@ pub fn internal_server_error<'a>() -> elements::Body<'a> {
     .into_element()])
 }
 
+pub fn internal_server_error_with_details<'a, T: Into<String>>(details: T) -> elements::Body<'a> {
+    elements::Body::style_less(vec![
+        elements::H1::style_less(vec![htmldsl::text("internal server error")]).into_element(),
+        elements::P::style_less(vec![htmldsl::text(details)]).into_element(),
+    ])
+}
+
+pub fn conflict<'a>() -> elements::Body<'a> {
+    elements::Body::style_less(vec![
+        elements::H1::style_less(vec![htmldsl::text("conflict")]).into_element(),
+        elements::P::style_less(vec![htmldsl::text(
+            "this was changed by someone else in the meantime, reload and try again",
+        )])
+        .into_element(),
+    ])
+}
+
 pub fn bad_request<'a, T: Into<String>>(message: T) -> elements::Body<'a> {
     elements::Body::style_less(vec![
         elements::H1::style_less(vec![htmldsl::text("bad request")]).into_element(),
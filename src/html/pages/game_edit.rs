@@ -34,5 +34,18 @@ pub fn page<'a>(game: models::Game) -> elements::Body<'a> {
         util::cursor_form_button(game.id, models::Direction::Up, true),
         util::cursor_form_button(game.id, models::Direction::Down, true),
         util::cursor_form_button(game.id, models::Direction::Right, true),
+        elements::Div::style_less(vec![
+            util::edit_form_button(game.id, "undo", "undo"),
+            util::edit_form_button(game.id, "redo", "redo"),
+        ])
+        .into_element(),
+        util::build_brush_buttons(&format!("/games/{}/edit", game.id), game.map.anchor)
+            .into_element(),
+        elements::P::style_less(vec![
+            htmldsl::text("Objective: "),
+            htmldsl::text(game.objective_text()),
+            util::build_objective_setting_buttons(game.id).into_element(),
+        ])
+        .into_element(),
     ])
 }
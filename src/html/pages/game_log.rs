@@ -0,0 +1,64 @@
+use htmldsl::attributes;
+use htmldsl::elements;
+use htmldsl::units;
+use htmldsl::TagRenderableIntoElement;
+
+use crate::html::shared;
+use crate::html::util;
+use crate::models;
+use crate::replay;
+
+/// Every event in the game's log, oldest first, with links to replay the
+/// game to the end of each turn.
+pub fn page<'a>(game_id: u32, log: Vec<models::LogEntry>) -> elements::Body<'a> {
+    elements::Body::style_less(vec![
+        shared::index_link(),
+        shared::games_link(),
+        elements::H3::style_less(vec![
+            elements::A::style_less(
+                attributes::Href {
+                    value: units::SourceValue::new(format!("/games/{}", game_id)),
+                },
+                vec![htmldsl::text("this game")],
+            )
+            .into_element(),
+            htmldsl::text(" log"),
+        ])
+        .into_element(),
+        elements::P::style_less(
+            vec![htmldsl::text("Replay: ")]
+                .into_iter()
+                .chain((0..=replay::last_turn(&log)).map(|turn| {
+                    util::replay_link(
+                        game_id,
+                        turn,
+                        if turn == 0 {
+                            "start ".into()
+                        } else {
+                            format!("turn {} ", turn)
+                        },
+                    )
+                }))
+                .collect(),
+        )
+        .into_element(),
+        elements::Div::style_less(if log.is_empty() {
+            vec![
+                elements::P::style_less(vec![htmldsl::text("Nothing has happened yet")])
+                    .into_element(),
+            ]
+        } else {
+            log.into_iter()
+                .map(|entry| {
+                    elements::P::style_less(vec![htmldsl::text(format!(
+                        "Turn {}: {}",
+                        entry.turn,
+                        entry.event.display_string()
+                    ))])
+                    .into_element()
+                })
+                .collect()
+        })
+        .into_element(),
+    ])
+}
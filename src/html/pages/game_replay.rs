@@ -0,0 +1,56 @@
+use htmldsl::attributes;
+use htmldsl::elements;
+use htmldsl::units;
+use htmldsl::TagRenderableIntoElement;
+
+use crate::html::shared;
+use crate::html::util;
+use crate::models;
+
+/// The game as replayed to the end of `turn`, with links to step a turn
+/// back or on, up to `last_turn`.
+pub fn page<'a>(game: models::Game, turn: u32, last_turn: u32) -> elements::Body<'a> {
+    let mut steps = Vec::new();
+    if turn > 0 {
+        steps.push(util::replay_link(game.id, turn - 1, "earlier ".into()));
+    }
+    if turn < last_turn {
+        steps.push(util::replay_link(game.id, turn + 1, "later".into()));
+    }
+
+    elements::Body::style_less(vec![
+        shared::index_link(),
+        shared::games_link(),
+        elements::H3::style_less(vec![
+            elements::A::style_less(
+                attributes::Href {
+                    value: units::SourceValue::new(format!("/games/{}", game.id)),
+                },
+                vec![htmldsl::text("this game")],
+            )
+            .into_element(),
+            elements::A::style_less(
+                attributes::Href {
+                    value: units::SourceValue::new(format!("/games/{}/log", game.id)),
+                },
+                vec![htmldsl::text("log")],
+            )
+            .into_element(),
+            htmldsl::text(if turn == 0 {
+                " replay: the start".into()
+            } else {
+                format!(
+                    " replay: end of turn {}{}",
+                    turn,
+                    match &game.result {
+                        Some(result) => format!(" ({})", result.display_string()),
+                        None => "".into(),
+                    }
+                )
+            }),
+        ])
+        .into_element(),
+        game.into_html(false),
+        elements::P::style_less(steps).into_element(),
+    ])
+}
@@ -0,0 +1,70 @@
+use htmldsl::attributes;
+use htmldsl::elements;
+use htmldsl::units;
+use htmldsl::TagRenderableIntoElement;
+
+use crate::html::shared;
+use crate::models;
+
+/// What is shown instead of the map once the game is won or lost, with
+/// `notice` (like the attack that ended it) at the bottom.
+pub fn page<'a>(game: models::Game, notice: Option<String>) -> elements::Body<'a> {
+    let result = match &game.result {
+        Some(result) => result.display_string(),
+        None => "Still playing".into(),
+    };
+    let survivors = game
+        .units
+        .values()
+        .filter(|unit| unit.faction == models::Faction::Player)
+        .map(|unit| format!("{} ({}/{} hp)", unit.name, unit.hp, unit.class.max_hp()))
+        .collect::<Vec<String>>();
+
+    elements::Body::style_less(vec![
+        shared::index_link(),
+        shared::games_link(),
+        elements::H3::style_less(vec![
+            elements::A::style_less(
+                attributes::Href {
+                    value: units::SourceValue::new(format!("/games/{}", game.id)),
+                },
+                vec![htmldsl::text("this game")],
+            )
+            .into_element(),
+            elements::A::style_less(
+                attributes::Href {
+                    value: units::SourceValue::new(format!("/games/{}/edit", game.id)),
+                },
+                vec![htmldsl::text("edit")],
+            )
+            .into_element(),
+            elements::A::style_less(
+                attributes::Href {
+                    value: units::SourceValue::new(format!("/games/{}/log", game.id)),
+                },
+                vec![htmldsl::text("log")],
+            )
+            .into_element(),
+        ])
+        .into_element(),
+        elements::H2::style_less(vec![htmldsl::text(result)]).into_element(),
+        elements::P::style_less(vec![htmldsl::text(format!(
+            "Objective: {}",
+            game.objective_text()
+        ))])
+        .into_element(),
+        elements::P::style_less(vec![htmldsl::text(format!("Over on turn {}", game.turn))])
+            .into_element(),
+        elements::P::style_less(vec![htmldsl::text(if survivors.is_empty() {
+            "No units left standing".into()
+        } else {
+            format!("Left standing: {}", survivors.join(", "))
+        })])
+        .into_element(),
+        elements::P::style_less(match notice {
+            Some(notice) => vec![htmldsl::text(notice)],
+            None => Vec::new(),
+        })
+        .into_element(),
+    ])
+}
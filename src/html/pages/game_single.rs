@@ -3,11 +3,22 @@ use htmldsl::elements;
 use htmldsl::units;
 use htmldsl::TagRenderableIntoElement;
 
+use crate::html::pages::game_result;
 use crate::html::shared;
 use crate::html::util;
 use crate::models;
 
 pub fn page<'a>(game: models::Game) -> elements::Body<'a> {
+    page_with_notice(game, None)
+}
+
+/// The game page, with `notice` (like how an attack went) under the controls,
+/// or once the game is over its results.
+pub fn page_with_notice<'a>(game: models::Game, notice: Option<String>) -> elements::Body<'a> {
+    if game.result.is_some() {
+        return game_result::page(game, notice);
+    }
+
     elements::Body::style_less(vec![
         shared::index_link(),
         shared::games_link(),
@@ -26,6 +37,20 @@ pub fn page<'a>(game: models::Game) -> elements::Body<'a> {
                 vec![htmldsl::text("edit")],
             )
             .into_element(),
+            elements::A::style_less(
+                attributes::Href {
+                    value: units::SourceValue::new(format!("/games/{}/log", game.id)),
+                },
+                vec![htmldsl::text("log")],
+            )
+            .into_element(),
+            htmldsl::text(format!(
+                " turn {}, {} phase ({}) ",
+                game.turn,
+                game.active_faction.display_string(),
+                game.objective_text()
+            )),
+            util::end_turn_form_button(game.id),
         ])
         .into_element(),
         game.into_html(false),
@@ -33,5 +58,19 @@ pub fn page<'a>(game: models::Game) -> elements::Body<'a> {
         util::cursor_form_button(game.id, models::Direction::Up, false),
         util::cursor_form_button(game.id, models::Direction::Down, false),
         util::cursor_form_button(game.id, models::Direction::Right, false),
+        elements::Div::style_less(match game.selected_unit {
+            Some(_) => vec![
+                util::unit_form_button(game.id, "move", "move here"),
+                util::unit_form_button(game.id, "attack", "attack here"),
+                util::unit_form_button(game.id, "deselect", "cancel"),
+            ],
+            None => vec![util::unit_form_button(game.id, "select", "select unit")],
+        })
+        .into_element(),
+        elements::P::style_less(match notice {
+            Some(notice) => vec![htmldsl::text(notice)],
+            None => Vec::new(),
+        })
+        .into_element(),
     ])
 }
@@ -0,0 +1,247 @@
+use htmldsl::attributes;
+use htmldsl::elements;
+use htmldsl::styles;
+use htmldsl::units;
+use htmldsl::TagRenderableIntoElement;
+
+use crate::models;
+
+use crate::html::shared;
+use crate::html::util;
+
+/// The map editor, with its own cursor, for the map's terrain and the units
+/// its games start with. Maps belonging to game `game_id` are only shown,
+/// with a link to edit them from the game instead.
+pub fn page<'a>(
+    map: models::Map,
+    starting_units: Vec<models::StartingUnit>,
+    game_id: Option<u32>,
+) -> elements::Body<'a> {
+    let terrain = map.at(&map.cursor);
+    let o_unit = starting_units
+        .iter()
+        .find(|unit| unit.position == map.cursor);
+    let edit_path = format!("/maps/{}/edit", map.id);
+
+    elements::Body::style_less(vec![
+        shared::index_link(),
+        shared::maps_link(),
+        elements::H3::style_less(vec![
+            elements::A::style_less(
+                attributes::Href {
+                    value: units::SourceValue::new(format!("/maps/{}", map.id)),
+                },
+                vec![htmldsl::text("view map")],
+            )
+            .into_element(),
+            elements::A::style_less(
+                attributes::Href {
+                    value: units::SourceValue::new(edit_path.clone()),
+                },
+                vec![htmldsl::text("editing")],
+            )
+            .into_element(),
+        ])
+        .into_element(),
+        elements::Table::style_less(
+            None,
+            elements::Tbody::style_less(vec![elements::Tr::style_less(vec![
+                elements::Td::style_less(vec![map.into_html(
+                    starting_units
+                        .iter()
+                        .map(|unit| (&unit.position, unit.class.clone().into_html())),
+                    Some(map.cursor),
+                    map.anchor.into_iter().collect(),
+                )]),
+                elements::Td::style_less(vec![
+                    elements::P::style_less(util::maybe_append(
+                        vec![
+                            terrain.clone().into_html().into_element(),
+                            htmldsl::text("Terrain: "),
+                            htmldsl::text(terrain.display_string()),
+                        ],
+                        match game_id {
+                            Some(_) => None,
+                            None => {
+                                Some(util::build_terrain_adding_buttons(&edit_path).into_element())
+                            }
+                        },
+                    ))
+                    .into_element(),
+                    elements::P::style_less(util::maybe_append(
+                        vec![
+                            htmldsl::text("Starting unit: "),
+                            htmldsl::text(match o_unit {
+                                Some(v) => format!(
+                                    "{} ({}{})",
+                                    v.class.display_string(),
+                                    v.faction.display_string(),
+                                    if v.faction == models::Faction::Player {
+                                        "".into()
+                                    } else {
+                                        format!(", {}", v.behavior.display_string())
+                                    }
+                                ),
+                                None => "--".into(),
+                            }),
+                        ],
+                        match game_id {
+                            Some(_) => None,
+                            None => Some(
+                                elements::Div::style_less(vec![
+                                    util::build_character_adding_buttons(&edit_path).into_element(),
+                                    util::build_faction_setting_buttons(&edit_path).into_element(),
+                                    util::build_behavior_setting_buttons(&edit_path).into_element(),
+                                ])
+                                .into_element(),
+                            ),
+                        },
+                    ))
+                    .into_element(),
+                ]),
+            ])]),
+        )
+        .into_element(),
+        util::map_cursor_form_button(map.id, models::Direction::Left),
+        util::map_cursor_form_button(map.id, models::Direction::Up),
+        util::map_cursor_form_button(map.id, models::Direction::Down),
+        util::map_cursor_form_button(map.id, models::Direction::Right),
+        match game_id {
+            Some(game_id) => elements::P::style_less(vec![
+                htmldsl::text("This map belongs to a game, so it is edited from "),
+                elements::A::style_less(
+                    attributes::Href {
+                        value: units::SourceValue::new(format!("/games/{}/edit", game_id)),
+                    },
+                    vec![htmldsl::text("the game's edit page")],
+                )
+                .into_element(),
+            ])
+            .into_element(),
+            None => elements::Div::style_less(vec![
+                util::build_brush_buttons(&edit_path, map.anchor).into_element(),
+                properties(&map, &starting_units),
+            ])
+            .into_element(),
+        },
+    ])
+}
+
+/// The map's name, size and default terrain, with a form and buttons to
+/// change them and a warning of what shrinking it would remove.
+fn properties(map: &models::Map, starting_units: &[models::StartingUnit]) -> htmldsl::Element {
+    let (width, height) = (map.hint_max_x, map.hint_max_y);
+    let removed_by = |outside: &dyn Fn(&(u32, u32)) -> bool| {
+        (
+            map.specified_terrain
+                .keys()
+                .filter(|position| outside(position))
+                .count(),
+            starting_units
+                .iter()
+                .filter(|unit| outside(&unit.position))
+                .count(),
+        )
+    };
+    // A map is never less than 1 tile wide or high, so at 1 there is no
+    // narrower or shorter.
+    let shrinks = vec![
+        (
+            width > 1,
+            removed_by(&|&(x, _)| x + 1 >= width),
+            "Narrower",
+            "right edge",
+        ),
+        (
+            height > 1,
+            removed_by(&|&(_, y)| y + 1 >= height),
+            "Shorter",
+            "top",
+        ),
+    ];
+
+    elements::Div::style_less(vec![
+        elements::P::style_less(vec![
+            htmldsl::text(format!("Name: {} ", map.display_name())),
+            elements::Form {
+                formmethod: attributes::Formmethod {
+                    inner: units::FormmethodValue::Post,
+                },
+                action: Some(attributes::Action {
+                    value: units::SourceValue::new(format!("/maps/{}/edit/name", map.id)),
+                }),
+                inputs: vec![elements::Input {
+                    type_: attributes::Type {
+                        inner: units::InputType::Text,
+                    },
+                    name: attributes::Name {
+                        value: units::NameValue::new("name".into()),
+                    },
+                    value: attributes::Value {
+                        value: units::TextValue::new(map.name.clone()),
+                    },
+                    styles: attributes::StyleAttr::empty(),
+                }],
+                button: elements::Button::style_less(htmldsl::text("rename")),
+                styles: attributes::StyleAttr::new(vec![&styles::Display::Inline]),
+            }
+            .into_element(),
+        ])
+        .into_element(),
+        elements::P::style_less(
+            vec![htmldsl::text(format!("Size: {} by {} ", width, height))]
+                .into_iter()
+                .chain(
+                    vec![
+                        (width > 1, width.saturating_sub(1), height, "narrower"),
+                        (true, width + 1, height, "wider"),
+                        (height > 1, width, height.saturating_sub(1), "shorter"),
+                        (true, width, height + 1, "taller"),
+                    ]
+                    .into_iter()
+                    .filter(|(shown, _, _, _)| *shown)
+                    .map(|(_, new_width, new_height, label)| {
+                        util::map_edit_form_button(
+                            map.id,
+                            &format!("size/{}/{}", new_width, new_height),
+                            label,
+                        )
+                    }),
+                )
+                .collect(),
+        )
+        .into_element(),
+        elements::P::style_less(
+            shrinks
+                .into_iter()
+                .filter(|(shown, (tile_count, unit_count), _, _)| {
+                    *shown && tile_count + unit_count > 0
+                })
+                .map(|(_, (tile_count, unit_count), label, edge)| {
+                    htmldsl::text(format!(
+                        "{} removes {} tiles and {} units from the {}. ",
+                        label, tile_count, unit_count, edge
+                    ))
+                })
+                .collect(),
+        )
+        .into_element(),
+        elements::P::style_less(
+            vec![
+                htmldsl::text("Default terrain: "),
+                map.default_terrain.clone().into_html().into_element(),
+            ]
+            .into_iter()
+            .chain(models::Terrain::all_values().into_iter().map(|terrain| {
+                util::map_edit_form_button(
+                    map.id,
+                    &format!("default/{}", terrain.url_frag_string()),
+                    &terrain.display_string(),
+                )
+            }))
+            .collect(),
+        )
+        .into_element(),
+    ])
+    .into_element()
+}
@@ -19,7 +19,11 @@ pub fn page<'a>(maps: Vec<models::Map>) -> elements::Body<'a> {
                         attributes::Href {
                             value: units::SourceValue::new(format!("/maps/{}", map.id)),
                         },
-                        vec![htmldsl::text(format!("map: {}", map.id))],
+                        vec![htmldsl::text(if map.name.is_empty() {
+                            format!("map: {}", map.id)
+                        } else {
+                            format!("map: {} ({})", map.id, map.name)
+                        })],
                     )
                     .into_element()
                 })
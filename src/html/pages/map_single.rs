@@ -1,5 +1,6 @@
 use htmldsl::attributes;
 use htmldsl::elements;
+use htmldsl::styles;
 use htmldsl::units;
 use htmldsl::TagRenderableIntoElement;
 
@@ -7,18 +8,45 @@ use crate::models;
 
 use crate::html::shared;
 
-pub fn page<'a>(map: models::Map) -> elements::Body<'a> {
+pub fn page<'a>(map: models::Map, starting_units: Vec<models::StartingUnit>) -> elements::Body<'a> {
     elements::Body::style_less(vec![
         shared::index_link(),
         shared::maps_link(),
-        elements::H3::style_less(vec![elements::A::style_less(
-            attributes::Href {
-                value: units::SourceValue::new(format!("/maps/{}", map.id)),
-            },
-            vec![htmldsl::text("this map")],
-        )
-        .into_element()])
+        elements::H3::style_less(vec![
+            elements::A::style_less(
+                attributes::Href {
+                    value: units::SourceValue::new(format!("/maps/{}", map.id)),
+                },
+                vec![htmldsl::text(map.display_name())],
+            )
+            .into_element(),
+            elements::A::style_less(
+                attributes::Href {
+                    value: units::SourceValue::new(format!("/maps/{}/edit", map.id)),
+                },
+                vec![htmldsl::text("edit")],
+            )
+            .into_element(),
+            elements::Form {
+                formmethod: attributes::Formmethod {
+                    inner: units::FormmethodValue::Post,
+                },
+                action: Some(attributes::Action {
+                    value: units::SourceValue::new(format!("/maps/{}/games", map.id)),
+                }),
+                inputs: Vec::new(),
+                button: elements::Button::style_less(htmldsl::text("new game on this map")),
+                styles: attributes::StyleAttr::new(vec![&styles::Display::Inline]),
+            }
+            .into_element(),
+        ])
         .into_element(),
-        map.into_html(Vec::new().into_iter(), None),
+        map.into_html(
+            starting_units
+                .iter()
+                .map(|unit| (&unit.position, unit.class.clone().into_html())),
+            None,
+            Vec::new(),
+        ),
     ])
 }
@@ -1,6 +1,10 @@
 pub mod game_edit;
 pub mod game_list;
+pub mod game_log;
+pub mod game_replay;
+pub mod game_result;
 pub mod game_single;
 pub mod index;
+pub mod map_edit;
 pub mod map_list;
 pub mod map_single;
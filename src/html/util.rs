@@ -20,6 +20,10 @@ fn current_selection_marker<'a>() -> elements::Img<'a> {
     elements::Img::style_less_with_src("/images/marker.png".to_string())
 }
 
+fn reachable_marker<'a>() -> elements::Img<'a> {
+    elements::Img::style_less_with_src("/images/reachable.png".to_string())
+}
+
 fn absolute_hover<'a, T: TagRenderableStyleSetter<'a>>(element: T) -> T {
     element.add_style(vec![
         &styles::Display::Block,
@@ -63,27 +67,42 @@ impl models::Character {
     }
 }
 
+/// A tile's terrain, what stands on it, whether the cursor is on it and
+/// whether the selected unit can reach it.
+type RenderedTile = (models::Terrain, Option<elements::Img<'static>>, bool, bool);
+
 impl models::Map {
+    /// The map's name, or its id if it has none.
+    pub fn display_name(&self) -> String {
+        if self.name.is_empty() {
+            format!("map {}", self.id)
+        } else {
+            self.name.clone()
+        }
+    }
+
     pub fn into_html<'a, T: Iterator<Item = (&'a (u32, u32), elements::Img<'static>)>>(
         &self,
         overlay_elements: T,
         current_selection: Option<(u32, u32)>,
+        highlighted: Vec<(u32, u32)>,
     ) -> htmldsl::Element {
         let (max_x, max_y) = self.maxes();
-        let mut empty_rendered_map: Vec<
-            Vec<(models::Terrain, Option<elements::Img<'static>>, bool)>,
-        > = (0..max_y)
+        let mut empty_rendered_map: Vec<Vec<RenderedTile>> = (0..max_y)
             .into_iter()
             .map(|_| {
                 (0..max_x)
                     .into_iter()
-                    .map(|_| (self.default_terrain.clone(), None, false))
+                    .map(|_| (self.default_terrain.clone(), None, false, false))
                     .collect()
             })
             .collect();
         for ((x, y), terrain) in self.specified_terrain.iter() {
             empty_rendered_map[(max_y - *y - 1) as usize][*x as usize].0 = terrain.clone();
         }
+        for (x, y) in highlighted.into_iter() {
+            empty_rendered_map[(max_y - y - 1) as usize][x as usize].3 = true;
+        }
         for (&(x, y), overlay_img) in overlay_elements.into_iter() {
             empty_rendered_map[(max_y - y - 1) as usize][x as usize].1 = Some(overlay_img);
         }
@@ -104,11 +123,21 @@ impl models::Map {
                                     elements::Td::style_less(vec![elements::Div::style_less(
                                         maybe_append(
                                             maybe_append(
-                                                vec![data
-                                                    .0
-                                                    .into_html()
-                                                    .add_style(vec![&styles::Display::Block])
-                                                    .into_element()],
+                                                maybe_append(
+                                                    vec![data
+                                                        .0
+                                                        .into_html()
+                                                        .add_style(vec![&styles::Display::Block])
+                                                        .into_element()],
+                                                    if data.3 {
+                                                        Some(
+                                                            absolute_hover(reachable_marker())
+                                                                .into_element(),
+                                                        )
+                                                    } else {
+                                                        None
+                                                    },
+                                                ),
                                                 data.1.map(|x| absolute_hover(x).into_element()),
                                             ),
                                             if data.2 {
@@ -135,9 +164,21 @@ impl models::Map {
 }
 
 impl models::Game {
+    /// The game's objective, naming the boss if it has one.
+    pub fn objective_text(&self) -> String {
+        match &self.objective {
+            models::Objective::DefeatBoss { unit_id } => match self.units.get(unit_id) {
+                Some(unit) => format!("Defeat {}", unit.name),
+                None => self.objective.display_string(),
+            },
+            objective => objective.display_string(),
+        }
+    }
+
     pub fn into_html(&self, edit: bool) -> htmldsl::Element {
+        let edit_path = format!("/games/{}/edit", self.id);
         let terrain = self.map.at(&self.current_selection);
-        let o_character = self.character_at(&self.current_selection);
+        let o_unit = self.unit_at(&self.current_selection);
 
         let hover_info = elements::Div::style_less(vec![
             elements::P::style_less(maybe_append(
@@ -147,7 +188,7 @@ impl models::Game {
                     htmldsl::text(terrain.display_string()),
                 ],
                 if edit {
-                    Some(build_terrain_adding_buttons(self.id).into_element())
+                    Some(build_terrain_adding_buttons(&edit_path).into_element())
                 } else {
                     None
                 },
@@ -155,24 +196,56 @@ impl models::Game {
             .into_element(),
             elements::P::style_less(maybe_append(
                 vec![
-                    o_character
-                        .clone()
-                        .map_or(current_selection_marker().into_element(), |x| {
-                            x.into_html().into_element()
-                        }),
+                    o_unit.map_or(current_selection_marker().into_element(), |x| {
+                        x.class.clone().into_html().into_element()
+                    }),
                     htmldsl::text("Character: "),
-                    htmldsl::text(match o_character {
-                        Some(v) => v.display_string(),
+                    htmldsl::text(match o_unit {
+                        Some(v) => format!(
+                            "{} ({}, {}{}) {}/{} hp{}",
+                            v.name,
+                            v.class.display_string(),
+                            v.faction.display_string(),
+                            if v.faction == models::Faction::Player {
+                                "".into()
+                            } else {
+                                format!(", {}", v.behavior.display_string())
+                            },
+                            v.hp,
+                            v.class.max_hp(),
+                            if v.acted {
+                                ", done"
+                            } else if v.moved {
+                                ", moved"
+                            } else {
+                                ""
+                            }
+                        ),
                         None => "--".into(),
                     }),
                 ],
                 if edit {
-                    Some(build_character_adding_buttons(self.id).into_element())
+                    Some(
+                        elements::Div::style_less(vec![
+                            build_character_adding_buttons(&edit_path).into_element(),
+                            build_faction_setting_buttons(&edit_path).into_element(),
+                            build_behavior_setting_buttons(&edit_path).into_element(),
+                        ])
+                        .into_element(),
+                    )
                 } else {
                     None
                 },
             ))
             .into_element(),
+            elements::P::style_less(vec![
+                htmldsl::text("Selected: "),
+                htmldsl::text(match self.selected_unit() {
+                    Some(v) => format!("{} ({} movement)", v.name, v.class.movement()),
+                    None => "--".into(),
+                }),
+            ])
+            .into_element(),
         ])
         .add_style(vec![
             &styles::Display::InlineBlock,
@@ -198,10 +271,15 @@ impl models::Game {
             None,
             elements::Tbody::style_less(vec![elements::Tr::style_less(vec![
                 elements::Td::style_less(vec![self.map.into_html(
-                    self.characters
-                        .iter()
-                        .map(|(k, v)| (k, v.clone().into_html())),
+                    self.units
+                        .values()
+                        .map(|unit| (&unit.position, unit.class.clone().into_html())),
                     Some(self.current_selection),
+                    match self.selected_unit() {
+                        Some(unit) if !edit => self.reachable_tiles(unit).into_keys().collect(),
+                        _ if edit => self.map.anchor.into_iter().collect(),
+                        _ => Vec::new(),
+                    },
                 )]),
                 elements::Td::style_less(vec![hover_info]),
             ])]),
@@ -210,7 +288,9 @@ impl models::Game {
     }
 }
 
-fn build_terrain_adding_buttons<'a>(game_id: u32) -> elements::Div<'a> {
+/// Buttons posting each terrain (or its removal) to the editor at
+/// `edit_path`, like `/games/1/edit` or `/maps/1/edit`.
+pub fn build_terrain_adding_buttons<'a>(edit_path: &str) -> elements::Div<'a> {
     elements::Div::style_less(
         models::Terrain::all_values()
             .into_iter()
@@ -221,8 +301,8 @@ fn build_terrain_adding_buttons<'a>(game_id: u32) -> elements::Div<'a> {
                     },
                     action: Some(attributes::Action {
                         value: units::SourceValue::new(format!(
-                            "/games/{}/edit/terrain/{}",
-                            game_id,
+                            "{}/terrain/{}",
+                            edit_path,
                             x.url_frag_string()
                         )),
                     }),
@@ -238,10 +318,7 @@ fn build_terrain_adding_buttons<'a>(game_id: u32) -> elements::Div<'a> {
                         inner: units::FormmethodValue::Post,
                     },
                     action: Some(attributes::Action {
-                        value: units::SourceValue::new(format!(
-                            "/games/{}/edit/unset/terrain",
-                            game_id,
-                        )),
+                        value: units::SourceValue::new(format!("{}/unset/terrain", edit_path)),
                     }),
                     inputs: Vec::new(),
                     button: elements::Button::style_less(htmldsl::text("delete")),
@@ -254,7 +331,9 @@ fn build_terrain_adding_buttons<'a>(game_id: u32) -> elements::Div<'a> {
     )
 }
 
-fn build_character_adding_buttons<'a>(game_id: u32) -> elements::Div<'a> {
+/// Buttons posting each class of character (or its removal) to the editor at
+/// `edit_path`.
+pub fn build_character_adding_buttons<'a>(edit_path: &str) -> elements::Div<'a> {
     elements::Div::style_less(
         models::Character::all_values()
             .into_iter()
@@ -265,8 +344,8 @@ fn build_character_adding_buttons<'a>(game_id: u32) -> elements::Div<'a> {
                     },
                     action: Some(attributes::Action {
                         value: units::SourceValue::new(format!(
-                            "/games/{}/edit/character/{}",
-                            game_id,
+                            "{}/character/{}",
+                            edit_path,
                             x.url_frag_string()
                         )),
                     }),
@@ -282,10 +361,7 @@ fn build_character_adding_buttons<'a>(game_id: u32) -> elements::Div<'a> {
                         inner: units::FormmethodValue::Post,
                     },
                     action: Some(attributes::Action {
-                        value: units::SourceValue::new(format!(
-                            "/games/{}/edit/unset/character",
-                            game_id,
-                        )),
+                        value: units::SourceValue::new(format!("{}/unset/character", edit_path)),
                     }),
                     inputs: Vec::new(),
                     button: elements::Button::style_less(htmldsl::text("delete")),
@@ -298,6 +374,149 @@ fn build_character_adding_buttons<'a>(game_id: u32) -> elements::Div<'a> {
     )
 }
 
+pub fn build_faction_setting_buttons<'a>(edit_path: &str) -> elements::Div<'a> {
+    elements::Div::style_less(
+        models::Faction::all_values()
+            .into_iter()
+            .map(|x| {
+                elements::Form {
+                    formmethod: attributes::Formmethod {
+                        inner: units::FormmethodValue::Post,
+                    },
+                    action: Some(attributes::Action {
+                        value: units::SourceValue::new(format!(
+                            "{}/faction/{}",
+                            edit_path,
+                            x.url_frag_string()
+                        )),
+                    }),
+                    inputs: Vec::new(),
+                    button: elements::Button::style_less(htmldsl::text(x.display_string())),
+                    styles: attributes::StyleAttr::new(vec![&styles::Display::Inline]),
+                }
+                .into_element()
+            })
+            .collect(),
+    )
+}
+
+pub fn build_behavior_setting_buttons<'a>(edit_path: &str) -> elements::Div<'a> {
+    elements::Div::style_less(
+        models::Behavior::all_values()
+            .into_iter()
+            .map(|x| {
+                elements::Form {
+                    formmethod: attributes::Formmethod {
+                        inner: units::FormmethodValue::Post,
+                    },
+                    action: Some(attributes::Action {
+                        value: units::SourceValue::new(format!(
+                            "{}/behavior/{}",
+                            edit_path,
+                            x.url_frag_string()
+                        )),
+                    }),
+                    inputs: Vec::new(),
+                    button: elements::Button::style_less(htmldsl::text(x.display_string())),
+                    styles: attributes::StyleAttr::new(vec![&styles::Display::Inline]),
+                }
+                .into_element()
+            })
+            .collect(),
+    )
+}
+
+/// The anchor, with buttons to put it under the cursor or clear it, and a
+/// row of terrain buttons for each brush, posting to the editor at
+/// `edit_path`. Brushes that paint from the anchor wait until there is one.
+pub fn build_brush_buttons<'a>(edit_path: &str, anchor: Option<(u32, u32)>) -> elements::Div<'a> {
+    let post_button = |action: String, label: String| {
+        elements::Form {
+            formmethod: attributes::Formmethod {
+                inner: units::FormmethodValue::Post,
+            },
+            action: Some(attributes::Action {
+                value: units::SourceValue::new(format!("{}/{}", edit_path, action)),
+            }),
+            inputs: Vec::new(),
+            button: elements::Button::style_less(htmldsl::text(label)),
+            styles: attributes::StyleAttr::new(vec![&styles::Display::Inline]),
+        }
+        .into_element()
+    };
+
+    elements::Div::style_less(
+        vec![elements::P::style_less(vec![
+            htmldsl::text(match anchor {
+                Some(position) => format!("Anchor: {:?} ", position),
+                None => "Anchor: -- ".into(),
+            }),
+            post_button("anchor".into(), "anchor here".into()),
+            post_button("unset/anchor".into(), "clear".into()),
+        ])
+        .into_element()]
+        .into_iter()
+        .chain(
+            models::Brush::all_values()
+                .into_iter()
+                .filter(|brush| anchor.is_some() || !brush.needs_anchor())
+                .map(|brush| {
+                    elements::P::style_less(
+                        vec![htmldsl::text(format!("{}: ", brush.display_string()))]
+                            .into_iter()
+                            .chain(models::Terrain::all_values().into_iter().map(|terrain| {
+                                post_button(
+                                    format!(
+                                        "paint/{}/{}",
+                                        brush.url_frag_string(),
+                                        terrain.url_frag_string()
+                                    ),
+                                    terrain.display_string(),
+                                )
+                            }))
+                            .collect(),
+                    )
+                    .into_element()
+                }),
+        )
+        .collect(),
+    )
+}
+
+/// Buttons for each kind of objective; reaching a tile and defeating a boss
+/// take the tile and unit under the cursor (a boss cannot be the player's).
+pub fn build_objective_setting_buttons<'a>(game_id: u32) -> elements::Div<'a> {
+    elements::Div::style_less(
+        vec![
+            ("rout".to_string(), "rout".to_string()),
+            ("survive-5".into(), "survive 5 turns".into()),
+            ("survive-10".into(), "survive 10 turns".into()),
+            ("survive-15".into(), "survive 15 turns".into()),
+            ("reach".into(), "reach this tile".into()),
+            ("boss".into(), "defeat this unit".into()),
+        ]
+        .into_iter()
+        .map(|(url_frag, label)| {
+            elements::Form {
+                formmethod: attributes::Formmethod {
+                    inner: units::FormmethodValue::Post,
+                },
+                action: Some(attributes::Action {
+                    value: units::SourceValue::new(format!(
+                        "/games/{}/edit/objective/{}",
+                        game_id, url_frag
+                    )),
+                }),
+                inputs: Vec::new(),
+                button: elements::Button::style_less(htmldsl::text(label)),
+                styles: attributes::StyleAttr::new(vec![&styles::Display::Inline]),
+            }
+            .into_element()
+        })
+        .collect(),
+    )
+}
+
 pub fn cursor_form_button(game_id: u32, dir: models::Direction, edit: bool) -> htmldsl::Element {
     let (url_frag, symbol) = dir.form_strings();
     elements::Form {
@@ -317,3 +536,92 @@ pub fn cursor_form_button(game_id: u32, dir: models::Direction, edit: bool) -> h
     }
     .into_element()
 }
+
+pub fn map_cursor_form_button(map_id: u32, dir: models::Direction) -> htmldsl::Element {
+    let (url_frag, symbol) = dir.form_strings();
+    elements::Form {
+        formmethod: attributes::Formmethod {
+            inner: units::FormmethodValue::Post,
+        },
+        action: Some(attributes::Action {
+            value: units::SourceValue::new(format!("/maps/{}/edit/cursor/{}", map_id, url_frag)),
+        }),
+        inputs: Vec::new(),
+        button: elements::Button::style_less(htmldsl::text(symbol)),
+        styles: attributes::StyleAttr::new(vec![&styles::Display::Inline]),
+    }
+    .into_element()
+}
+
+pub fn unit_form_button(game_id: u32, action: &str, label: &str) -> htmldsl::Element {
+    elements::Form {
+        formmethod: attributes::Formmethod {
+            inner: units::FormmethodValue::Post,
+        },
+        action: Some(attributes::Action {
+            value: units::SourceValue::new(format!("/games/{}/unit/{}", game_id, action)),
+        }),
+        inputs: Vec::new(),
+        button: elements::Button::style_less(htmldsl::text(label.to_string())),
+        styles: attributes::StyleAttr::new(vec![&styles::Display::Inline]),
+    }
+    .into_element()
+}
+
+/// A button posting to `/games/{game_id}/edit/{action}`, like "undo".
+pub fn edit_form_button(game_id: u32, action: &str, label: &str) -> htmldsl::Element {
+    elements::Form {
+        formmethod: attributes::Formmethod {
+            inner: units::FormmethodValue::Post,
+        },
+        action: Some(attributes::Action {
+            value: units::SourceValue::new(format!("/games/{}/edit/{}", game_id, action)),
+        }),
+        inputs: Vec::new(),
+        button: elements::Button::style_less(htmldsl::text(label.to_string())),
+        styles: attributes::StyleAttr::new(vec![&styles::Display::Inline]),
+    }
+    .into_element()
+}
+
+/// A button posting to `/maps/{map_id}/edit/{action}`, like "size/10/8".
+pub fn map_edit_form_button(map_id: u32, action: &str, label: &str) -> htmldsl::Element {
+    elements::Form {
+        formmethod: attributes::Formmethod {
+            inner: units::FormmethodValue::Post,
+        },
+        action: Some(attributes::Action {
+            value: units::SourceValue::new(format!("/maps/{}/edit/{}", map_id, action)),
+        }),
+        inputs: Vec::new(),
+        button: elements::Button::style_less(htmldsl::text(label.to_string())),
+        styles: attributes::StyleAttr::new(vec![&styles::Display::Inline]),
+    }
+    .into_element()
+}
+
+/// A link to the game as it stood at the end of `turn`.
+pub fn replay_link(game_id: u32, turn: u32, label: String) -> htmldsl::Element {
+    elements::A::style_less(
+        attributes::Href {
+            value: units::SourceValue::new(format!("/games/{}/replay/{}", game_id, turn)),
+        },
+        vec![htmldsl::text(label)],
+    )
+    .into_element()
+}
+
+pub fn end_turn_form_button(game_id: u32) -> htmldsl::Element {
+    elements::Form {
+        formmethod: attributes::Formmethod {
+            inner: units::FormmethodValue::Post,
+        },
+        action: Some(attributes::Action {
+            value: units::SourceValue::new(format!("/games/{}/turn/end", game_id)),
+        }),
+        inputs: Vec::new(),
+        button: elements::Button::style_less(htmldsl::text("end turn")),
+        styles: attributes::StyleAttr::new(vec![&styles::Display::Inline]),
+    }
+    .into_element()
+}
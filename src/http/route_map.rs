@@ -1,13 +1,23 @@
+use std::sync::Arc;
+
 use hyper::{header, Body, Method, Request, Response};
 
+use crate::config::Config;
+use crate::db;
+
 use super::routes;
 use super::util;
 
 /// This is our service handler. It receives a Request, routes on its
 /// path, and returns a Future of a Response.
-pub async fn service_handler(req: Request<Body>) -> Result<Response<Body>, hyper::Error> {
-    let path_frags = req
-        .uri()
+pub async fn service_handler(
+    config: Arc<Config>,
+    db: Arc<db::DB>,
+    req: Request<Body>,
+) -> Result<Response<Body>, hyper::Error> {
+    let (parts, body) = req.into_parts();
+    let path_frags = parts
+        .uri
         .path()
         .split('/')
         .filter(|x| !x.is_empty())
@@ -15,33 +25,121 @@ pub async fn service_handler(req: Request<Body>) -> Result<Response<Body>, hyper
 
     println!(
         "responding to: {} ({:?}) ({})",
-        req.uri().path(),
+        parts.uri.path(),
         path_frags,
-        req.method()
+        parts.method
     );
-    match (req.method(), path_frags.as_slice()) {
+    match (&parts.method, path_frags.as_slice()) {
         // Serve hard-coded images
-        (&Method::GET, ["images", name]) => routes::image_serve::handle_get(name),
+        (&Method::GET, ["images", name]) => {
+            routes::image_serve::handle_get(&config.images_dir, name)
+        }
 
-        (method, frags) => handle_pages(method, frags),
+        // Rename a map from the posted form, whose body has to be read first
+        (&Method::POST, ["maps", map_id, "edit", "name"]) => {
+            let form = hyper::body::to_bytes(body).await?;
+            routes::map_edit::handle_post_set_name(&db, map_id, &form).map(html_content_type)
+        }
+
+        (method, frags) => handle_pages(&db, method, frags),
     }
 }
 
-fn handle_pages(method: &Method, frags: &[&str]) -> Result<Response<Body>, hyper::Error> {
+fn handle_pages(
+    db: &db::DB,
+    method: &Method,
+    frags: &[&str],
+) -> Result<Response<Body>, hyper::Error> {
+    route_pages(db, method, frags).map(html_content_type)
+}
+
+fn html_content_type(mut resp: Response<Body>) -> Response<Body> {
+    resp.headers_mut().insert(
+        header::CONTENT_TYPE,
+        header::HeaderValue::from_static("text/html"),
+    );
+    resp
+}
+
+fn route_pages(
+    db: &db::DB,
+    method: &Method,
+    frags: &[&str],
+) -> Result<Response<Body>, hyper::Error> {
     match (method, frags) {
         // Serve some instructions at /
         (&Method::GET, []) => routes::index::handle_get(),
 
-        (&Method::GET, ["maps"]) => routes::map_list::handle_get(),
+        (&Method::GET, ["maps"]) => routes::map_list::handle_get(db),
+        (&Method::POST, ["maps"]) => routes::map_list::handle_post(db),
 
-        (&Method::GET, ["maps", map_id]) => routes::map_single::handle_get(map_id),
+        (&Method::GET, ["maps", map_id]) => routes::map_single::handle_get(db, map_id),
+        (&Method::POST, ["maps", map_id, "games"]) => {
+            routes::game_list::handle_post_from_map(db, map_id)
+        }
+        (&Method::GET, ["maps", map_id, "edit"]) => routes::map_edit::handle_get(db, map_id),
+        (&Method::POST, ["maps", map_id, "edit", "character", character_str]) => {
+            routes::map_edit::handle_post_set_value(
+                db,
+                map_id,
+                util::TerrainOrCharacter::Character,
+                character_str,
+            )
+        }
+        (&Method::POST, ["maps", map_id, "edit", "terrain", terrain_str]) => {
+            routes::map_edit::handle_post_set_value(
+                db,
+                map_id,
+                util::TerrainOrCharacter::Terrain,
+                terrain_str,
+            )
+        }
+        (&Method::POST, ["maps", map_id, "edit", "faction", faction_str]) => {
+            routes::map_edit::handle_post_set_faction(db, map_id, faction_str)
+        }
+        (&Method::POST, ["maps", map_id, "edit", "behavior", behavior_str]) => {
+            routes::map_edit::handle_post_set_behavior(db, map_id, behavior_str)
+        }
+        (&Method::POST, ["maps", map_id, "edit", "default", terrain_str]) => {
+            routes::map_edit::handle_post_set_default_terrain(db, map_id, terrain_str)
+        }
+        (&Method::POST, ["maps", map_id, "edit", "size", width_str, height_str]) => {
+            routes::map_edit::handle_post_set_size(db, map_id, width_str, height_str)
+        }
+        (&Method::POST, ["maps", map_id, "edit", "anchor"]) => {
+            routes::map_edit::handle_post_anchor(db, map_id, true)
+        }
+        (&Method::POST, ["maps", map_id, "edit", "paint", brush_str, terrain_str]) => {
+            routes::map_edit::handle_post_paint(db, map_id, brush_str, terrain_str)
+        }
+        (&Method::POST, ["maps", map_id, "edit", "unset", "anchor"]) => {
+            routes::map_edit::handle_post_anchor(db, map_id, false)
+        }
+        (&Method::POST, ["maps", map_id, "edit", "unset", "character"]) => {
+            routes::map_edit::handle_post_unset_value(
+                db,
+                map_id,
+                util::TerrainOrCharacter::Character,
+            )
+        }
+        (&Method::POST, ["maps", map_id, "edit", "unset", "terrain"]) => {
+            routes::map_edit::handle_post_unset_value(db, map_id, util::TerrainOrCharacter::Terrain)
+        }
+        (&Method::POST, ["maps", map_id, "edit", "cursor", direction]) => {
+            routes::map_edit::handle_post_cursor(db, map_id, direction)
+        }
 
-        (&Method::GET, ["games"]) => routes::game_list::handle_get(),
-        (&Method::POST, ["games"]) => routes::game_list::handle_post(),
-        (&Method::GET, ["games", game_id]) => routes::game_single::handle_get(game_id),
-        (&Method::GET, ["games", game_id, "edit"]) => routes::game_edit::handle_get(game_id),
+        (&Method::GET, ["games"]) => routes::game_list::handle_get(db),
+        (&Method::POST, ["games"]) => routes::game_list::handle_post(db),
+        (&Method::GET, ["games", game_id]) => routes::game_single::handle_get(db, game_id),
+        (&Method::GET, ["games", game_id, "log"]) => routes::game_log::handle_get(db, game_id),
+        (&Method::GET, ["games", game_id, "replay", turn_str]) => {
+            routes::game_replay::handle_get(db, game_id, turn_str)
+        }
+        (&Method::GET, ["games", game_id, "edit"]) => routes::game_edit::handle_get(db, game_id),
         (&Method::POST, ["games", game_id, "edit", "character", character_str]) => {
             routes::game_edit::handle_post_set_value(
+                db,
                 game_id,
                 util::TerrainOrCharacter::Character,
                 character_str,
@@ -49,35 +147,80 @@ fn handle_pages(method: &Method, frags: &[&str]) -> Result<Response<Body>, hyper
         }
         (&Method::POST, ["games", game_id, "edit", "terrain", terrain_str]) => {
             routes::game_edit::handle_post_set_value(
+                db,
                 game_id,
                 util::TerrainOrCharacter::Terrain,
                 terrain_str,
             )
         }
+        (&Method::POST, ["games", game_id, "edit", "faction", faction_str]) => {
+            routes::game_edit::handle_post_set_faction(db, game_id, faction_str)
+        }
+        (&Method::POST, ["games", game_id, "edit", "behavior", behavior_str]) => {
+            routes::game_edit::handle_post_set_behavior(db, game_id, behavior_str)
+        }
+        (&Method::POST, ["games", game_id, "edit", "objective", objective_str]) => {
+            routes::game_edit::handle_post_set_objective(db, game_id, objective_str)
+        }
 
+        (&Method::POST, ["games", game_id, "edit", "undo"]) => {
+            routes::game_edit::handle_post_undo(db, game_id, false)
+        }
+        (&Method::POST, ["games", game_id, "edit", "redo"]) => {
+            routes::game_edit::handle_post_undo(db, game_id, true)
+        }
+
+        (&Method::POST, ["games", game_id, "edit", "anchor"]) => {
+            routes::game_edit::handle_post_anchor(db, game_id, true)
+        }
+        (&Method::POST, ["games", game_id, "edit", "paint", brush_str, terrain_str]) => {
+            routes::game_edit::handle_post_paint(db, game_id, brush_str, terrain_str)
+        }
+
+        (&Method::POST, ["games", game_id, "edit", "unset", "anchor"]) => {
+            routes::game_edit::handle_post_anchor(db, game_id, false)
+        }
         (&Method::POST, ["games", game_id, "edit", "unset", "character"]) => {
-            routes::game_edit::handle_post_unset_value(game_id, util::TerrainOrCharacter::Character)
+            routes::game_edit::handle_post_unset_value(
+                db,
+                game_id,
+                util::TerrainOrCharacter::Character,
+            )
         }
         (&Method::POST, ["games", game_id, "edit", "unset", "terrain"]) => {
-            routes::game_edit::handle_post_unset_value(game_id, util::TerrainOrCharacter::Terrain)
+            routes::game_edit::handle_post_unset_value(
+                db,
+                game_id,
+                util::TerrainOrCharacter::Terrain,
+            )
         }
 
         (&Method::POST, ["games", game_id, "edit", "cursor", direction]) => {
-            routes::cursor_move::handle_post(game_id, direction, true)
+            routes::cursor_move::handle_post(db, game_id, direction, true)
         }
 
         (&Method::POST, ["games", game_id, "cursor", direction]) => {
-            routes::cursor_move::handle_post(game_id, direction, false)
+            routes::cursor_move::handle_post(db, game_id, direction, false)
+        }
+
+        (&Method::POST, ["games", game_id, "unit", "select"]) => {
+            routes::unit_move::handle_post_select(db, game_id)
+        }
+        (&Method::POST, ["games", game_id, "unit", "deselect"]) => {
+            routes::unit_move::handle_post_deselect(db, game_id)
+        }
+        (&Method::POST, ["games", game_id, "unit", "move"]) => {
+            routes::unit_move::handle_post_move(db, game_id)
+        }
+        (&Method::POST, ["games", game_id, "unit", "attack"]) => {
+            routes::unit_attack::handle_post(db, game_id)
+        }
+
+        (&Method::POST, ["games", game_id, "turn", "end"]) => {
+            routes::turn_end::handle_post(db, game_id)
         }
 
         // Return the 404 Not Found for other routes.
         _ => util::not_found_response(frags),
     }
-    .map(|mut resp| {
-        resp.headers_mut().insert(
-            header::CONTENT_TYPE,
-            header::HeaderValue::from_static("text/html"),
-        );
-        resp
-    })
 }
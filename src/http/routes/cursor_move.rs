@@ -7,12 +7,11 @@ use crate::models;
 use crate::http::util;
 
 pub fn handle_post(
+    db: &db::DB,
     game_id_str: &str,
     direction_str: &str,
     edit: bool,
 ) -> Result<Response<Body>, hyper::Error> {
-    let db = db::DB::new();
-
     let game_id = match game_id_str.parse::<u32>() {
         Ok(v) => v,
         Err(_e) => return util::bad_request_response("must supply map id as u32"),
@@ -20,7 +19,7 @@ pub fn handle_post(
 
     let mut game = match db.get_game(game_id) {
         Ok(d) => d,
-        Err(e) => return util::internal_server_error(e),
+        Err(e) => return util::db_error_page(e),
     };
 
     let direction = match models::Direction::parse(direction_str) {
@@ -32,9 +31,9 @@ pub fn handle_post(
 
     game.move_cursor(direction);
 
-    match db.update_game_cursor(game.id, game.current_selection) {
+    match db.update_game_cursor(game.id, game.version, game.current_selection) {
         Ok(()) => (),
-        Err(e) => return util::internal_server_error(e),
+        Err(e) => return util::db_error_page(e),
     };
 
     Ok(Response::new(Body::from(html::common::render_page(
@@ -6,9 +6,7 @@ use crate::models;
 
 use crate::http::util;
 
-pub fn handle_get(game_id_str: &str) -> Result<Response<Body>, hyper::Error> {
-    let db = db::DB::new();
-
+pub fn handle_get(db: &db::DB, game_id_str: &str) -> Result<Response<Body>, hyper::Error> {
     let game_id = match game_id_str.parse::<u32>() {
         Ok(v) => v,
         Err(_e) => return util::bad_request_response("must supply map id as u32"),
@@ -25,12 +23,11 @@ pub fn handle_get(game_id_str: &str) -> Result<Response<Body>, hyper::Error> {
 }
 
 pub fn handle_post_set_value(
+    db: &db::DB,
     game_id_str: &str,
     value_type: util::TerrainOrCharacter,
     value_value: &str,
 ) -> Result<Response<Body>, hyper::Error> {
-    let db = db::DB::new();
-
     let game_id = match game_id_str.parse::<u32>() {
         Ok(v) => v,
         Err(_e) => return util::bad_request_response("must supply map id as u32"),
@@ -56,15 +53,196 @@ pub fn handle_post_set_value(
         Err(e) => return util::db_error_page(e),
     };
 
-    handle_get(game_id_str)
+    handle_get(db, game_id_str)
+}
+
+pub fn handle_post_set_faction(
+    db: &db::DB,
+    game_id_str: &str,
+    faction_str: &str,
+) -> Result<Response<Body>, hyper::Error> {
+    let game_id = match game_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    let faction = match models::Faction::parse_str(faction_str) {
+        Some(v) => v,
+        None => return util::bad_request_response("faction in path invalid"),
+    };
+
+    match db.update_game_faction(game_id, faction) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, game_id_str)
+}
+
+pub fn handle_post_set_behavior(
+    db: &db::DB,
+    game_id_str: &str,
+    behavior_str: &str,
+) -> Result<Response<Body>, hyper::Error> {
+    let game_id = match game_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    let behavior = match models::Behavior::parse_str(behavior_str) {
+        Some(v) => v,
+        None => return util::bad_request_response("behavior in path invalid"),
+    };
+
+    match db.update_game_behavior(game_id, behavior) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, game_id_str)
+}
+
+/// Sets the game's objective from `objective_str`, which is either what
+/// `Objective::url_frag_string` gives or "reach" (the tile under the cursor)
+/// or "boss" (the unit under the cursor).
+pub fn handle_post_set_objective(
+    db: &db::DB,
+    game_id_str: &str,
+    objective_str: &str,
+) -> Result<Response<Body>, hyper::Error> {
+    let game_id = match game_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    let game = match db.get_game(game_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
+    let objective = match objective_str {
+        "reach" => models::Objective::Reach {
+            position: game.current_selection,
+        },
+        "boss" => match game.unit_at(&game.current_selection) {
+            Some(unit) if unit.faction == models::Faction::Player => {
+                return util::bad_request_response("the boss cannot be a player unit")
+            }
+            Some(unit) => models::Objective::DefeatBoss { unit_id: unit.id },
+            None => return util::bad_request_response("no unit under the cursor"),
+        },
+        _ => match models::Objective::parse_str(objective_str) {
+            Some(v) => v,
+            None => return util::bad_request_response("objective in path invalid"),
+        },
+    };
+
+    match db.update_game_objective(game_id, objective) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, game_id_str)
+}
+
+/// Undoes the game's latest edit, or with `redo` makes the earliest undone
+/// one again. With nothing to undo or redo the edit page is just shown again.
+pub fn handle_post_undo(
+    db: &db::DB,
+    game_id_str: &str,
+    redo: bool,
+) -> Result<Response<Body>, hyper::Error> {
+    let game_id = match game_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    match if redo {
+        db.redo_game_edit(game_id)
+    } else {
+        db.undo_game_edit(game_id)
+    } {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, game_id_str)
+}
+
+/// Puts the paint anchor under the cursor, or without `set` clears it.
+pub fn handle_post_anchor(
+    db: &db::DB,
+    game_id_str: &str,
+    set: bool,
+) -> Result<Response<Body>, hyper::Error> {
+    let game_id = match game_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    let game = match db.get_game(game_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
+    match db.update_game_anchor(
+        game.id,
+        if set {
+            Some(game.current_selection)
+        } else {
+            None
+        },
+    ) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, game_id_str)
+}
+
+pub fn handle_post_paint(
+    db: &db::DB,
+    game_id_str: &str,
+    brush_str: &str,
+    terrain_str: &str,
+) -> Result<Response<Body>, hyper::Error> {
+    let game_id = match game_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    let brush = match models::Brush::parse_str(brush_str) {
+        Some(v) => v,
+        None => return util::bad_request_response("brush in path invalid"),
+    };
+
+    let terrain = match models::Terrain::parse_str(terrain_str) {
+        Some(v) => v,
+        None => return util::bad_request_response("terrain in path invalid"),
+    };
+
+    let game = match db.get_game(game_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
+    if brush.needs_anchor() && game.map.anchor.is_none() {
+        return util::bad_request_response("brush needs an anchor to paint from");
+    }
+
+    match db.paint_game_terrain(game_id, brush, terrain) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, game_id_str)
 }
 
 pub fn handle_post_unset_value(
+    db: &db::DB,
     game_id_str: &str,
     value_type: util::TerrainOrCharacter,
 ) -> Result<Response<Body>, hyper::Error> {
-    let db = db::DB::new();
-
     let game_id = match game_id_str.parse::<u32>() {
         Ok(v) => v,
         Err(_e) => return util::bad_request_response("must supply map id as u32"),
@@ -78,5 +256,5 @@ pub fn handle_post_unset_value(
         Err(e) => return util::db_error_page(e),
     };
 
-    handle_get(game_id_str)
+    handle_get(db, game_id_str)
 }
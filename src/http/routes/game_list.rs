@@ -5,8 +5,7 @@ use crate::html;
 
 use crate::http::util;
 
-pub fn handle_get() -> Result<Response<Body>, hyper::Error> {
-    let db = db::DB::new();
+pub fn handle_get(db: &db::DB) -> Result<Response<Body>, hyper::Error> {
     let games = match db.get_games() {
         Ok(d) => d,
         Err(e) => return util::db_error_page(e),
@@ -17,12 +16,26 @@ pub fn handle_get() -> Result<Response<Body>, hyper::Error> {
     ))))
 }
 
-pub fn handle_post() -> Result<Response<Body>, hyper::Error> {
-    let db = db::DB::new();
+pub fn handle_post(db: &db::DB) -> Result<Response<Body>, hyper::Error> {
     match db.add_game() {
         Ok(()) => (),
         Err(e) => return util::db_error_page(e),
     };
 
-    handle_get()
+    handle_get(db)
+}
+
+/// Adds a game on a copy of the map with `map_id_str`.
+pub fn handle_post_from_map(db: &db::DB, map_id_str: &str) -> Result<Response<Body>, hyper::Error> {
+    let map_id = match map_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    match db.add_game_from_map(map_id) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db)
 }
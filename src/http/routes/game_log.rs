@@ -0,0 +1,22 @@
+use hyper::{Body, Response};
+
+use crate::db;
+use crate::html;
+
+use crate::http::util;
+
+pub fn handle_get(db: &db::DB, game_id_str: &str) -> Result<Response<Body>, hyper::Error> {
+    let game_id = match game_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply game id as u32"),
+    };
+
+    let log = match db.get_game_log(game_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
+    Ok(Response::new(Body::from(html::common::render_page(
+        html::pages::game_log::page(game_id, log),
+    ))))
+}
@@ -5,9 +5,7 @@ use crate::html;
 
 use crate::http::util;
 
-pub fn handle_get(game_id_str: &str) -> Result<Response<Body>, hyper::Error> {
-    let db = db::DB::new();
-
+pub fn handle_get(db: &db::DB, game_id_str: &str) -> Result<Response<Body>, hyper::Error> {
     let game_id = match game_id_str.parse::<u32>() {
         Ok(v) => v,
         Err(_e) => return util::bad_request_response("must supply game id as u32"),
@@ -1,10 +1,11 @@
 use hyper::{Body, Response};
 use std::fs::File;
 use std::io::{ErrorKind, Read};
+use std::path::{Path, PathBuf};
 
 use crate::http::util;
 
-pub fn handle_get(file_name: &str) -> Result<Response<Body>, hyper::Error> {
+pub fn handle_get(images_dir: &Path, file_name: &str) -> Result<Response<Body>, hyper::Error> {
     let (name, suffix) = match file_name.split('.').collect::<Vec<&str>>().as_slice() {
         &[name, suffix] => (name, suffix),
         _ => return util::bad_request_response("images must be 'file.ext'"),
@@ -19,7 +20,7 @@ pub fn handle_get(file_name: &str) -> Result<Response<Body>, hyper::Error> {
         Ok(()) => (),
         Err(e) => return util::bad_request_response(format!("image file invalid: {}", e)),
     };
-    serve_file(format!("images/{}.{}", name, ext.extension()))
+    serve_file(images_dir.join(format!("{}.{}", name, ext.extension())))
 }
 
 pub enum ImageFileType {
@@ -47,7 +48,7 @@ fn is_alpha_numeric_underscore(c: char) -> bool {
     return c.is_ascii_alphanumeric() || c == '_';
 }
 
-pub fn serve_file(path: String) -> Result<Response<Body>, hyper::Error> {
+pub fn serve_file(path: PathBuf) -> Result<Response<Body>, hyper::Error> {
     let mut f = match File::open(path) {
         Ok(file) => file,
         Err(e) => match e.kind() {
@@ -0,0 +1,314 @@
+use hyper::{Body, Response};
+
+use crate::db;
+use crate::html;
+use crate::models;
+
+use crate::http::util;
+
+/// The most tiles a map can be wide or high, which keeps its page a sensible
+/// size.
+const MAX_MAP_SIZE: u32 = 100;
+
+pub fn handle_get(db: &db::DB, map_id_str: &str) -> Result<Response<Body>, hyper::Error> {
+    let map_id = match map_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    let map = match db.get_map(map_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
+    let starting_units = match db.get_map_starting_units(map_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
+    let game_id = match db.get_map_game_id(map_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
+    Ok(Response::new(Body::from(html::common::render_page(
+        html::pages::map_edit::page(map, starting_units, game_id),
+    ))))
+}
+
+pub fn handle_post_set_value(
+    db: &db::DB,
+    map_id_str: &str,
+    value_type: util::TerrainOrCharacter,
+    value_value: &str,
+) -> Result<Response<Body>, hyper::Error> {
+    let map_id = match map_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    match match value_type {
+        util::TerrainOrCharacter::Terrain => db.update_map_terrain(
+            map_id,
+            match models::Terrain::parse_str(value_value) {
+                Some(v) => v,
+                None => return util::bad_request_response("terrain in path invalid"),
+            },
+        ),
+        util::TerrainOrCharacter::Character => db.update_map_character(
+            map_id,
+            match models::Character::parse_str(value_value) {
+                Some(v) => v,
+                None => return util::bad_request_response("character in path invalid"),
+            },
+        ),
+    } {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, map_id_str)
+}
+
+pub fn handle_post_set_faction(
+    db: &db::DB,
+    map_id_str: &str,
+    faction_str: &str,
+) -> Result<Response<Body>, hyper::Error> {
+    let map_id = match map_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    let faction = match models::Faction::parse_str(faction_str) {
+        Some(v) => v,
+        None => return util::bad_request_response("faction in path invalid"),
+    };
+
+    match db.update_map_faction(map_id, faction) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, map_id_str)
+}
+
+pub fn handle_post_set_behavior(
+    db: &db::DB,
+    map_id_str: &str,
+    behavior_str: &str,
+) -> Result<Response<Body>, hyper::Error> {
+    let map_id = match map_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    let behavior = match models::Behavior::parse_str(behavior_str) {
+        Some(v) => v,
+        None => return util::bad_request_response("behavior in path invalid"),
+    };
+
+    match db.update_map_behavior(map_id, behavior) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, map_id_str)
+}
+
+pub fn handle_post_unset_value(
+    db: &db::DB,
+    map_id_str: &str,
+    value_type: util::TerrainOrCharacter,
+) -> Result<Response<Body>, hyper::Error> {
+    let map_id = match map_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    match match value_type {
+        util::TerrainOrCharacter::Terrain => db.unset_map_terrain(map_id),
+        util::TerrainOrCharacter::Character => db.unset_map_character(map_id),
+    } {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, map_id_str)
+}
+
+/// Names the map whatever was entered in the form's `name` field.
+pub fn handle_post_set_name(
+    db: &db::DB,
+    map_id_str: &str,
+    form: &[u8],
+) -> Result<Response<Body>, hyper::Error> {
+    let map_id = match map_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    let name = match util::form_value(form, "name") {
+        Some(v) => v,
+        None => return util::bad_request_response("name in form missing or invalid"),
+    };
+
+    match db.update_map_name(map_id, name) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, map_id_str)
+}
+
+pub fn handle_post_set_default_terrain(
+    db: &db::DB,
+    map_id_str: &str,
+    terrain_str: &str,
+) -> Result<Response<Body>, hyper::Error> {
+    let map_id = match map_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    let terrain = match models::Terrain::parse_str(terrain_str) {
+        Some(v) => v,
+        None => return util::bad_request_response("terrain in path invalid"),
+    };
+
+    match db.update_map_default_terrain(map_id, terrain) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, map_id_str)
+}
+
+/// Resizes the map to `width_str` by `height_str` tiles, trimming whatever
+/// is left outside.
+pub fn handle_post_set_size(
+    db: &db::DB,
+    map_id_str: &str,
+    width_str: &str,
+    height_str: &str,
+) -> Result<Response<Body>, hyper::Error> {
+    let map_id = match map_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    let size = match (width_str.parse::<u32>(), height_str.parse::<u32>()) {
+        (Ok(width), Ok(height))
+            if (1..=MAX_MAP_SIZE).contains(&width) && (1..=MAX_MAP_SIZE).contains(&height) =>
+        {
+            (width, height)
+        }
+        _ => {
+            return util::bad_request_response(format!(
+                "width and height must be from 1 to {}",
+                MAX_MAP_SIZE
+            ))
+        }
+    };
+
+    match db.update_map_size(map_id, size) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, map_id_str)
+}
+
+/// Puts the paint anchor under the cursor, or without `set` clears it.
+pub fn handle_post_anchor(
+    db: &db::DB,
+    map_id_str: &str,
+    set: bool,
+) -> Result<Response<Body>, hyper::Error> {
+    let map_id = match map_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    let map = match db.get_map(map_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
+    match db.update_map_anchor(map.id, if set { Some(map.cursor) } else { None }) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, map_id_str)
+}
+
+pub fn handle_post_paint(
+    db: &db::DB,
+    map_id_str: &str,
+    brush_str: &str,
+    terrain_str: &str,
+) -> Result<Response<Body>, hyper::Error> {
+    let map_id = match map_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    let brush = match models::Brush::parse_str(brush_str) {
+        Some(v) => v,
+        None => return util::bad_request_response("brush in path invalid"),
+    };
+
+    let terrain = match models::Terrain::parse_str(terrain_str) {
+        Some(v) => v,
+        None => return util::bad_request_response("terrain in path invalid"),
+    };
+
+    let map = match db.get_map(map_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
+    if brush.needs_anchor() && map.anchor.is_none() {
+        return util::bad_request_response("brush needs an anchor to paint from");
+    }
+
+    match db.paint_map_terrain(map.id, brush, terrain) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, map_id_str)
+}
+
+pub fn handle_post_cursor(
+    db: &db::DB,
+    map_id_str: &str,
+    direction_str: &str,
+) -> Result<Response<Body>, hyper::Error> {
+    let map_id = match map_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply map id as u32"),
+    };
+
+    let mut map = match db.get_map(map_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
+    let direction = match models::Direction::parse(direction_str) {
+        Some(d) => d,
+        None => {
+            return util::bad_request_response("direction must be one of right, up, left, down")
+        }
+    };
+
+    map.move_cursor(direction);
+
+    match db.update_map_cursor(map.id, map.cursor) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db, map_id_str)
+}
@@ -5,8 +5,7 @@ use crate::html;
 
 use crate::http::util;
 
-pub fn handle_get() -> Result<Response<Body>, hyper::Error> {
-    let db = db::DB::new();
+pub fn handle_get(db: &db::DB) -> Result<Response<Body>, hyper::Error> {
     let games = match db.get_maps() {
         Ok(d) => d,
         Err(e) => return util::db_error_page(e),
@@ -16,3 +15,12 @@ pub fn handle_get() -> Result<Response<Body>, hyper::Error> {
         html::pages::map_list::page(games),
     ))))
 }
+
+pub fn handle_post(db: &db::DB) -> Result<Response<Body>, hyper::Error> {
+    match db.add_map() {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    handle_get(db)
+}
@@ -5,9 +5,7 @@ use crate::html;
 
 use crate::http::util;
 
-pub fn handle_get(map_id_str: &str) -> Result<Response<Body>, hyper::Error> {
-    let db = db::DB::new();
-
+pub fn handle_get(db: &db::DB, map_id_str: &str) -> Result<Response<Body>, hyper::Error> {
     let map_id = match map_id_str.parse::<u32>() {
         Ok(v) => v,
         Err(_e) => return util::bad_request_response("must supply map id as u32"),
@@ -18,7 +16,12 @@ pub fn handle_get(map_id_str: &str) -> Result<Response<Body>, hyper::Error> {
         Err(e) => return util::db_error_page(e),
     };
 
+    let starting_units = match db.get_map_starting_units(map_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
     Ok(Response::new(Body::from(html::common::render_page(
-        html::pages::map_single::page(map),
+        html::pages::map_single::page(map, starting_units),
     ))))
 }
@@ -1,8 +1,14 @@
 pub mod cursor_move;
 pub mod game_edit;
 pub mod game_list;
+pub mod game_log;
+pub mod game_replay;
 pub mod game_single;
 pub mod image_serve;
 pub mod index;
+pub mod map_edit;
 pub mod map_list;
 pub mod map_single;
+pub mod turn_end;
+pub mod unit_attack;
+pub mod unit_move;
@@ -0,0 +1,48 @@
+use hyper::{Body, Response};
+
+use crate::ai;
+use crate::db;
+use crate::html;
+
+use crate::http::util;
+
+pub fn handle_post(db: &db::DB, game_id_str: &str) -> Result<Response<Body>, hyper::Error> {
+    let game_id = match game_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply game id as u32"),
+    };
+
+    let mut game = match db.get_game(game_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
+    if game.result.is_some() {
+        return util::bad_request_response("the game is over");
+    }
+
+    game.end_turn();
+    let attacks = ai::play_computer_turns(&mut game);
+
+    match db.end_game_turn(game.version, &game) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    Ok(Response::new(Body::from(html::common::render_page(
+        html::pages::game_single::page_with_notice(
+            game,
+            if attacks.is_empty() {
+                None
+            } else {
+                Some(
+                    attacks
+                        .iter()
+                        .map(|attack| attack.display_string())
+                        .collect::<Vec<String>>()
+                        .join("; "),
+                )
+            },
+        ),
+    ))))
+}
@@ -0,0 +1,32 @@
+use hyper::{Body, Response};
+
+use crate::db;
+use crate::html;
+
+use crate::http::util;
+
+pub fn handle_post(db: &db::DB, game_id_str: &str) -> Result<Response<Body>, hyper::Error> {
+    let game_id = match game_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply game id as u32"),
+    };
+
+    let mut game = match db.get_game(game_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
+    let attack = match game.attack_with_selected_unit() {
+        Ok(attack) => attack,
+        Err(e) => return util::bad_request_response(e.display_string()),
+    };
+
+    match db.attack_game_unit(game.version, &game, &attack) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    Ok(Response::new(Body::from(html::common::render_page(
+        html::pages::game_single::page_with_notice(game, Some(attack.display_string())),
+    ))))
+}
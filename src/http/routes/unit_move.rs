@@ -0,0 +1,85 @@
+use hyper::{Body, Response};
+
+use crate::db;
+use crate::html;
+use crate::models;
+
+use crate::http::util;
+
+pub fn handle_post_select(db: &db::DB, game_id_str: &str) -> Result<Response<Body>, hyper::Error> {
+    let game_id = match game_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply game id as u32"),
+    };
+
+    let mut game = match db.get_game(game_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
+    match game.select_unit() {
+        Ok(()) => (),
+        Err(e) => return util::bad_request_response(e.display_string()),
+    };
+
+    match db.update_game_selected_unit(game.id, game.version, game.selected_unit) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    render(game)
+}
+
+pub fn handle_post_deselect(
+    db: &db::DB,
+    game_id_str: &str,
+) -> Result<Response<Body>, hyper::Error> {
+    let game_id = match game_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply game id as u32"),
+    };
+
+    let mut game = match db.get_game(game_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
+    game.deselect_unit();
+
+    match db.update_game_selected_unit(game.id, game.version, game.selected_unit) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    render(game)
+}
+
+pub fn handle_post_move(db: &db::DB, game_id_str: &str) -> Result<Response<Body>, hyper::Error> {
+    let game_id = match game_id_str.parse::<u32>() {
+        Ok(v) => v,
+        Err(_e) => return util::bad_request_response("must supply game id as u32"),
+    };
+
+    let mut game = match db.get_game(game_id) {
+        Ok(d) => d,
+        Err(e) => return util::db_error_page(e),
+    };
+
+    let unit = match game.move_selected_unit() {
+        Ok(unit) => unit,
+        Err(e) => return util::bad_request_response(e.display_string()),
+    };
+
+    match db.move_game_unit(game.version, &game, &unit) {
+        Ok(()) => (),
+        Err(e) => return util::db_error_page(e),
+    };
+
+    render(game)
+}
+
+fn render(game: models::Game) -> Result<Response<Body>, hyper::Error> {
+    Ok(Response::new(Body::from(html::common::render_page(
+        html::pages::game_single::page(game),
+    ))))
+}
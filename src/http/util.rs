@@ -10,13 +10,33 @@ pub enum TerrainOrCharacter {
 
 pub fn db_error_page(e: db::DBError) -> Result<Response<Body>, hyper::Error> {
     match e {
-        db::DBError::FindingTable(_) => internal_server_error(e),
-        db::DBError::ParsingRecord(_) => internal_server_error(e),
-        db::DBError::FindingRecord(r) => not_found_response(r),
-        db::DBError::Internal(_) => internal_server_error(e),
+        db::DBError::FindingTable { .. } => db_internal_server_error(e),
+        db::DBError::ParsingRecord { .. } => db_internal_server_error(e),
+        db::DBError::WritingRecord { .. } => db_internal_server_error(e),
+        db::DBError::FindingRecord { table } => not_found_response(table),
+        db::DBError::Conflict { table } => conflict_response(table),
+        db::DBError::UnsupportedSchemaVersion { .. } => db_internal_server_error(e),
+        db::DBError::Migrating { .. } => db_internal_server_error(e),
+        db::DBError::InvalidValue { reason, .. } => bad_request_response(reason),
+        db::DBError::Io { .. } => db_internal_server_error(e),
+        db::DBError::Sqlite(_) => db_internal_server_error(e),
     }
 }
 
+/// Like `internal_server_error`, but debug builds also show what went wrong
+/// on the page.
+fn db_internal_server_error(e: db::DBError) -> Result<Response<Body>, hyper::Error> {
+    println!("internal server error: {} ({:?})", e, e);
+    let body = if cfg!(debug_assertions) {
+        html::common::internal_server_error_with_details(e.to_string())
+    } else {
+        html::common::internal_server_error()
+    };
+    let mut internal_server_error = Response::new(Body::from(html::common::render_page(body)));
+    *internal_server_error.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+    Ok(internal_server_error)
+}
+
 pub fn internal_server_error<T: std::fmt::Debug>(
     log_message: T,
 ) -> Result<Response<Body>, hyper::Error> {
@@ -39,6 +59,17 @@ pub fn not_found_response<T: std::fmt::Debug>(
     Ok(not_found)
 }
 
+pub fn conflict_response<T: std::fmt::Debug>(
+    log_message: T,
+) -> Result<Response<Body>, hyper::Error> {
+    println!("conflict: {:?}", log_message);
+    let mut conflict = Response::new(Body::from(html::common::render_page(
+        html::common::conflict(),
+    )));
+    *conflict.status_mut() = StatusCode::CONFLICT;
+    Ok(conflict)
+}
+
 pub fn bad_request_response<T: Into<String>>(message: T) -> Result<Response<Body>, hyper::Error> {
     let mut not_found = Response::new(Body::from(html::common::render_page(
         html::common::bad_request(message),
@@ -46,3 +77,46 @@ pub fn bad_request_response<T: Into<String>>(message: T) -> Result<Response<Body
     *not_found.status_mut() = StatusCode::BAD_REQUEST;
     Ok(not_found)
 }
+
+/// Undoes the percent-encoding of a url path segment or form value, like
+/// `My%20Map`, or `None` if it is badly encoded or not utf-8.
+pub fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut rest = s.bytes();
+    while let Some(byte) = rest.next() {
+        if byte == b'%' {
+            let hex = [rest.next()?, rest.next()?];
+            bytes.push(u8::from_str_radix(std::str::from_utf8(&hex).ok()?, 16).ok()?);
+        } else {
+            bytes.push(byte);
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// The value of `field` in a posted form body (like `name=Forest+Pass`), or
+/// `None` if it is missing or badly encoded.
+pub fn form_value(body: &[u8], field: &str) -> Option<String> {
+    std::str::from_utf8(body)
+        .ok()?
+        .split('&')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(key, _)| *key == field)
+        .and_then(|(_, value)| percent_decode(&value.replace('+', " ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_a_posted_form_value() {
+        assert_eq!(
+            form_value(b"name=Forest+Pass%21&other=1", "name"),
+            Some("Forest Pass!".to_string())
+        );
+        assert_eq!(form_value(b"name=", "name"), Some("".to_string()));
+        assert_eq!(form_value(b"other=1", "name"), None);
+        assert_eq!(form_value(b"name=%zz", "name"), None);
+    }
+}
@@ -1,17 +1,57 @@
+mod ai;
+mod combat;
+mod config;
 mod db;
 mod html;
 mod http;
 mod models;
+mod painting;
+mod pathfinding;
+mod replay;
+mod rng;
+
+use std::sync::Arc;
 
 use hyper::service::{make_service_fn, service_fn};
 use hyper::Server;
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
-    let addr = ([127, 0, 0, 1], 3000).into();
+    let config = match config::Config::load() {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    };
+    std::fs::create_dir_all(&config.data_dir)?;
+    let db = match db::DB::new(config.storage, &config.data_dir) {
+        Ok(db) => Arc::new(db),
+        Err(e) => {
+            eprintln!("could not open {:?} storage: {}", config.storage, e);
+            std::process::exit(1);
+        }
+    };
+
+    let addr = config.socket_addr();
+
+    println!(
+        "Using {:?} storage in {} and images from {}",
+        config.storage,
+        config.data_dir.display(),
+        config.images_dir.display()
+    );
 
-    let service =
-        make_service_fn(|_| async { Ok::<_, hyper::Error>(service_fn(http::service_handler)) });
+    let service_config = config.clone();
+    let service = make_service_fn(move |_| {
+        let config = service_config.clone();
+        let db = db.clone();
+        async move {
+            Ok::<_, hyper::Error>(service_fn(move |req| {
+                http::service_handler(config.clone(), db.clone(), req)
+            }))
+        }
+    });
 
     let server = Server::bind(&addr).serve(service);
 
@@ -1,7 +1,12 @@
 use std::collections::BTreeMap;
+use std::convert::TryFrom;
 
 use serde::{Deserialize, Serialize};
 
+use crate::combat;
+use crate::pathfinding;
+use crate::rng::Rng;
+
 #[derive(Debug)]
 pub enum Direction {
     Right,
@@ -31,7 +36,7 @@ impl Direction {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub enum Terrain {
     Grass,
     Dirt,
@@ -61,6 +66,35 @@ impl Terrain {
         .into()
     }
 
+    /// What it takes out of a unit's movement budget to step onto this
+    /// terrain.
+    pub fn movement_cost(&self) -> u32 {
+        match self {
+            Terrain::Grass => 1,
+            Terrain::Dirt => 2,
+            Terrain::Rock => 3,
+        }
+    }
+
+    /// Taken off the damage done to a unit standing on this terrain.
+    pub fn defense_bonus(&self) -> u32 {
+        match self {
+            Terrain::Grass => 0,
+            Terrain::Dirt => 1,
+            Terrain::Rock => 3,
+        }
+    }
+
+    /// Taken off the chance (in percent) of hitting a unit standing on this
+    /// terrain.
+    pub fn avoid(&self) -> u32 {
+        match self {
+            Terrain::Grass => 0,
+            Terrain::Dirt => 5,
+            Terrain::Rock => 20,
+        }
+    }
+
     pub fn display_string(&self) -> String {
         match self {
             Terrain::Grass => "Grass",
@@ -71,7 +105,58 @@ impl Terrain {
     }
 }
 
-#[derive(Serialize, Deserialize, Clone)]
+/// The shapes the editor paints terrain in: between the anchor and the
+/// cursor, or for `Fill` outward from the cursor.
+#[derive(Clone, Copy)]
+pub enum Brush {
+    Rectangle,
+    Outline,
+    Line,
+    Fill,
+}
+
+impl Brush {
+    pub fn all_values() -> Vec<Self> {
+        vec![Brush::Rectangle, Brush::Outline, Brush::Line, Brush::Fill]
+    }
+
+    pub fn parse_str(s: &str) -> Option<Self> {
+        match s {
+            "rectangle" => Some(Brush::Rectangle),
+            "outline" => Some(Brush::Outline),
+            "line" => Some(Brush::Line),
+            "fill" => Some(Brush::Fill),
+            _ => None,
+        }
+    }
+
+    pub fn url_frag_string(&self) -> String {
+        match self {
+            Brush::Rectangle => "rectangle",
+            Brush::Outline => "outline",
+            Brush::Line => "line",
+            Brush::Fill => "fill",
+        }
+        .into()
+    }
+
+    pub fn display_string(&self) -> String {
+        match self {
+            Brush::Rectangle => "Rectangle",
+            Brush::Outline => "Outline",
+            Brush::Line => "Line",
+            Brush::Fill => "Fill",
+        }
+        .into()
+    }
+
+    /// Whether the brush paints from the anchor, which has to be set first.
+    pub fn needs_anchor(&self) -> bool {
+        !matches!(self, Brush::Fill)
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
 pub enum Character {
     Knight,
     Mage,
@@ -101,6 +186,50 @@ impl Character {
         .into()
     }
 
+    /// How much terrain movement cost a unit of this class can cover in one
+    /// move.
+    pub fn movement(&self) -> u32 {
+        match self {
+            Character::Knight => 4,
+            Character::Mage => 3,
+            Character::Thief => 6,
+        }
+    }
+
+    pub fn max_hp(&self) -> u32 {
+        match self {
+            Character::Knight => 20,
+            Character::Mage => 14,
+            Character::Thief => 16,
+        }
+    }
+
+    pub fn attack(&self) -> u32 {
+        match self {
+            Character::Knight => 7,
+            Character::Mage => 9,
+            Character::Thief => 6,
+        }
+    }
+
+    pub fn defense(&self) -> u32 {
+        match self {
+            Character::Knight => 5,
+            Character::Mage => 1,
+            Character::Thief => 3,
+        }
+    }
+
+    /// The furthest (in steps left, right, up or down) a unit of this class
+    /// can attack from.
+    pub fn range(&self) -> u32 {
+        match self {
+            Character::Knight => 1,
+            Character::Mage => 2,
+            Character::Thief => 1,
+        }
+    }
+
     pub fn display_string(&self) -> String {
         match self {
             Character::Knight => "Knight",
@@ -109,6 +238,500 @@ impl Character {
         }
         .into()
     }
+
+    /// The first of "Knight 1", "Knight 2", ... not among `taken`.
+    pub fn next_unit_name(&self, taken: &[&str]) -> String {
+        (1..)
+            .map(|n| format!("{} {}", self.display_string(), n))
+            .find(|name| !taken.contains(&name.as_str()))
+            .unwrap_or_default()
+    }
+}
+
+/// Which side a unit fights for; each takes its turn in this order.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum Faction {
+    Player,
+    Enemy,
+    Neutral,
+}
+
+impl Faction {
+    pub fn all_values() -> Vec<Self> {
+        vec![Faction::Player, Faction::Enemy, Faction::Neutral]
+    }
+
+    pub fn parse_str(s: &str) -> Option<Self> {
+        match s {
+            "player" => Some(Faction::Player),
+            "enemy" => Some(Faction::Enemy),
+            "neutral" => Some(Faction::Neutral),
+            _ => None,
+        }
+    }
+
+    pub fn url_frag_string(&self) -> String {
+        match self {
+            Faction::Player => "player",
+            Faction::Enemy => "enemy",
+            Faction::Neutral => "neutral",
+        }
+        .into()
+    }
+
+    /// Whether units of the two factions go after each other. Neutral units
+    /// go after no one, though they can still be attacked.
+    pub fn is_hostile_to(&self, other: &Faction) -> bool {
+        matches!(
+            (self, other),
+            (Faction::Player, Faction::Enemy) | (Faction::Enemy, Faction::Player)
+        )
+    }
+
+    pub fn display_string(&self) -> String {
+        match self {
+            Faction::Player => "Player",
+            Faction::Enemy => "Enemy",
+            Faction::Neutral => "Neutral",
+        }
+        .into()
+    }
+}
+
+/// How a unit not under the player's control plays its turn.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum Behavior {
+    /// Heads for the nearest unit it is hostile to and attacks it.
+    Aggressive,
+    /// Attacks anything it can reach this turn, and otherwise holds still.
+    Defensive,
+    /// Never moves, only attacks what is already in range.
+    Guard,
+}
+
+impl Behavior {
+    pub fn all_values() -> Vec<Self> {
+        vec![Behavior::Aggressive, Behavior::Defensive, Behavior::Guard]
+    }
+
+    pub fn parse_str(s: &str) -> Option<Self> {
+        match s {
+            "aggressive" => Some(Behavior::Aggressive),
+            "defensive" => Some(Behavior::Defensive),
+            "guard" => Some(Behavior::Guard),
+            _ => None,
+        }
+    }
+
+    pub fn url_frag_string(&self) -> String {
+        match self {
+            Behavior::Aggressive => "aggressive",
+            Behavior::Defensive => "defensive",
+            Behavior::Guard => "guard",
+        }
+        .into()
+    }
+
+    pub fn display_string(&self) -> String {
+        match self {
+            Behavior::Aggressive => "Aggressive",
+            Behavior::Defensive => "Defensive",
+            Behavior::Guard => "Guard",
+        }
+        .into()
+    }
+}
+
+/// What the player has to do to win a game. Losing every player unit in
+/// battle always loses it.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub enum Objective {
+    /// Defeat every enemy unit.
+    Rout,
+    /// Still have units standing once this many turns are over.
+    Survive { turns: u32 },
+    /// Get any player unit onto this tile.
+    Reach { position: (u32, u32) },
+    /// Defeat the unit with this id, which is never a player unit.
+    DefeatBoss { unit_id: u32 },
+}
+
+impl Objective {
+    /// Parses what `url_frag_string` gives, like "rout" or "survive-10".
+    pub fn parse_str(s: &str) -> Option<Self> {
+        let parts: Vec<&str> = s.split('-').collect();
+        let numbers: Vec<u32> = match parts[1..].iter().map(|x| x.parse::<u32>()).collect() {
+            Ok(numbers) => numbers,
+            Err(_) => return None,
+        };
+        match (parts[0], numbers.as_slice()) {
+            ("rout", []) => Some(Objective::Rout),
+            ("survive", [turns]) => Some(Objective::Survive { turns: *turns }),
+            ("reach", [x, y]) => Some(Objective::Reach { position: (*x, *y) }),
+            ("boss", [unit_id]) => Some(Objective::DefeatBoss { unit_id: *unit_id }),
+            _ => None,
+        }
+    }
+
+    pub fn url_frag_string(&self) -> String {
+        match self {
+            Objective::Rout => "rout".into(),
+            Objective::Survive { turns } => format!("survive-{}", turns),
+            Objective::Reach { position } => format!("reach-{}-{}", position.0, position.1),
+            Objective::DefeatBoss { unit_id } => format!("boss-{}", unit_id),
+        }
+    }
+
+    pub fn display_string(&self) -> String {
+        match self {
+            Objective::Rout => "Defeat every enemy".into(),
+            Objective::Survive { turns } => format!("Survive {} turns", turns),
+            Objective::Reach { position } => {
+                format!("Reach the tile at ({}, {})", position.0, position.1)
+            }
+            Objective::DefeatBoss { .. } => "Defeat the boss".into(),
+        }
+    }
+}
+
+impl TryFrom<String> for Objective {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Objective::parse_str(&s).ok_or(format!("unknown objective {:?}", s))
+    }
+}
+
+impl From<Objective> for String {
+    fn from(objective: Objective) -> Self {
+        objective.url_frag_string()
+    }
+}
+
+/// How a finished game ended.
+#[derive(Serialize, Deserialize, Clone, PartialEq)]
+pub enum GameResult {
+    Won,
+    Lost,
+}
+
+impl GameResult {
+    pub fn parse_str(s: &str) -> Option<Self> {
+        match s {
+            "won" => Some(GameResult::Won),
+            "lost" => Some(GameResult::Lost),
+            _ => None,
+        }
+    }
+
+    pub fn url_frag_string(&self) -> String {
+        match self {
+            GameResult::Won => "won",
+            GameResult::Lost => "lost",
+        }
+        .into()
+    }
+
+    pub fn display_string(&self) -> String {
+        match self {
+            GameResult::Won => "Victory",
+            GameResult::Lost => "Defeat",
+        }
+        .into()
+    }
+}
+
+/// Something done to a game, as kept in its log. Replaying a game's log in
+/// order from how it started brings it back to where it is now.
+#[derive(Serialize, Deserialize, Clone)]
+#[serde(try_from = "String", into = "String")]
+pub enum Event {
+    Cursor {
+        position: (u32, u32),
+    },
+    /// The unit under the cursor, at `position`, was selected.
+    Select {
+        position: (u32, u32),
+    },
+    Deselect,
+    Move {
+        from: (u32, u32),
+        to: (u32, u32),
+    },
+    Attack {
+        from: (u32, u32),
+        to: (u32, u32),
+    },
+    /// The turn ended, along with any computer turns after it.
+    EndTurn,
+    SetTerrain {
+        position: (u32, u32),
+        terrain: Terrain,
+    },
+    UnsetTerrain {
+        position: (u32, u32),
+    },
+    /// A character was placed, or an existing one changed class. `unit_id`
+    /// is the id it was stored with.
+    PlaceCharacter {
+        position: (u32, u32),
+        unit_id: u32,
+        class: Character,
+    },
+    RemoveCharacter {
+        position: (u32, u32),
+    },
+    /// `unit` was put back exactly as it is here, replacing anything at its
+    /// position, as when an edit is undone.
+    RestoreCharacter {
+        unit: Unit,
+    },
+    SetFaction {
+        position: (u32, u32),
+        faction: Faction,
+    },
+    SetBehavior {
+        position: (u32, u32),
+        behavior: Behavior,
+    },
+    SetObjective {
+        objective: Objective,
+    },
+    /// Several tiles were painted at once, each left with its terrain here
+    /// or, where that is `None`, the map's default.
+    Paint {
+        tiles: Vec<((u32, u32), Option<Terrain>)>,
+    },
+}
+
+impl Event {
+    /// Parses what `log_string` gives, like "move/0/1/2/1".
+    pub fn parse_str(s: &str) -> Option<Self> {
+        let position = |x: &str, y: &str| match (x.parse::<u32>(), y.parse::<u32>()) {
+            (Ok(x), Ok(y)) => Some((x, y)),
+            _ => None,
+        };
+        let parts: Vec<&str> = s.split('/').collect();
+        match parts.as_slice() {
+            ["cursor", x, y] => Some(Event::Cursor {
+                position: position(x, y)?,
+            }),
+            ["select", x, y] => Some(Event::Select {
+                position: position(x, y)?,
+            }),
+            ["deselect"] => Some(Event::Deselect),
+            ["move", from_x, from_y, to_x, to_y] => Some(Event::Move {
+                from: position(from_x, from_y)?,
+                to: position(to_x, to_y)?,
+            }),
+            ["attack", from_x, from_y, to_x, to_y] => Some(Event::Attack {
+                from: position(from_x, from_y)?,
+                to: position(to_x, to_y)?,
+            }),
+            ["end-turn"] => Some(Event::EndTurn),
+            ["terrain", x, y, terrain] => Some(Event::SetTerrain {
+                position: position(x, y)?,
+                terrain: Terrain::parse_str(terrain)?,
+            }),
+            ["unset-terrain", x, y] => Some(Event::UnsetTerrain {
+                position: position(x, y)?,
+            }),
+            ["character", x, y, unit_id, class] => Some(Event::PlaceCharacter {
+                position: position(x, y)?,
+                unit_id: unit_id.parse::<u32>().ok()?,
+                class: Character::parse_str(class)?,
+            }),
+            ["unset-character", x, y] => Some(Event::RemoveCharacter {
+                position: position(x, y)?,
+            }),
+            ["restore", x, y, id, class, faction, behavior, hp, moved, acted, name] => {
+                Some(Event::RestoreCharacter {
+                    unit: Unit {
+                        id: id.parse::<u32>().ok()?,
+                        name: name.to_string(),
+                        class: Character::parse_str(class)?,
+                        position: position(x, y)?,
+                        faction: Faction::parse_str(faction)?,
+                        behavior: Behavior::parse_str(behavior)?,
+                        hp: hp.parse::<u32>().ok()?,
+                        moved: moved.parse::<bool>().ok()?,
+                        acted: acted.parse::<bool>().ok()?,
+                    },
+                })
+            }
+            ["faction", x, y, faction] => Some(Event::SetFaction {
+                position: position(x, y)?,
+                faction: Faction::parse_str(faction)?,
+            }),
+            ["behavior", x, y, behavior] => Some(Event::SetBehavior {
+                position: position(x, y)?,
+                behavior: Behavior::parse_str(behavior)?,
+            }),
+            ["objective", objective] => Some(Event::SetObjective {
+                objective: Objective::parse_str(objective)?,
+            }),
+            ["paint", tiles @ ..] if tiles.len() % 3 == 0 => Some(Event::Paint {
+                tiles: tiles
+                    .chunks(3)
+                    .map(|tile| {
+                        Some((
+                            position(tile[0], tile[1])?,
+                            match tile[2] {
+                                "default" => None,
+                                terrain => Some(Terrain::parse_str(terrain)?),
+                            },
+                        ))
+                    })
+                    .collect::<Option<Vec<_>>>()?,
+            }),
+            _ => None,
+        }
+    }
+
+    pub fn log_string(&self) -> String {
+        match self {
+            Event::Cursor { position } => format!("cursor/{}/{}", position.0, position.1),
+            Event::Select { position } => format!("select/{}/{}", position.0, position.1),
+            Event::Deselect => "deselect".into(),
+            Event::Move { from, to } => format!("move/{}/{}/{}/{}", from.0, from.1, to.0, to.1),
+            Event::Attack { from, to } => {
+                format!("attack/{}/{}/{}/{}", from.0, from.1, to.0, to.1)
+            }
+            Event::EndTurn => "end-turn".into(),
+            Event::SetTerrain { position, terrain } => format!(
+                "terrain/{}/{}/{}",
+                position.0,
+                position.1,
+                terrain.url_frag_string()
+            ),
+            Event::UnsetTerrain { position } => {
+                format!("unset-terrain/{}/{}", position.0, position.1)
+            }
+            Event::PlaceCharacter {
+                position,
+                unit_id,
+                class,
+            } => format!(
+                "character/{}/{}/{}/{}",
+                position.0,
+                position.1,
+                unit_id,
+                class.url_frag_string()
+            ),
+            Event::RemoveCharacter { position } => {
+                format!("unset-character/{}/{}", position.0, position.1)
+            }
+            // The name goes last, being the only part that is not a number
+            // or a url fragment.
+            Event::RestoreCharacter { unit } => format!(
+                "restore/{}/{}/{}/{}/{}/{}/{}/{}/{}/{}",
+                unit.position.0,
+                unit.position.1,
+                unit.id,
+                unit.class.url_frag_string(),
+                unit.faction.url_frag_string(),
+                unit.behavior.url_frag_string(),
+                unit.hp,
+                unit.moved,
+                unit.acted,
+                unit.name
+            ),
+            Event::SetFaction { position, faction } => format!(
+                "faction/{}/{}/{}",
+                position.0,
+                position.1,
+                faction.url_frag_string()
+            ),
+            Event::SetBehavior { position, behavior } => format!(
+                "behavior/{}/{}/{}",
+                position.0,
+                position.1,
+                behavior.url_frag_string()
+            ),
+            Event::SetObjective { objective } => {
+                format!("objective/{}", objective.url_frag_string())
+            }
+            // Tiles left with the default terrain say so, as no terrain's
+            // url fragment is "default".
+            Event::Paint { tiles } => std::iter::once("paint".to_string())
+                .chain(tiles.iter().map(|(position, terrain)| {
+                    format!(
+                        "{}/{}/{}",
+                        position.0,
+                        position.1,
+                        match terrain {
+                            Some(terrain) => terrain.url_frag_string(),
+                            None => "default".into(),
+                        }
+                    )
+                }))
+                .collect::<Vec<String>>()
+                .join("/"),
+        }
+    }
+
+    pub fn display_string(&self) -> String {
+        match self {
+            Event::Cursor { position } => format!("Cursor moved to {:?}", position),
+            Event::Select { position } => format!("Selected the unit at {:?}", position),
+            Event::Deselect => "Cancelled the selection".into(),
+            Event::Move { from, to } => format!("Moved the unit at {:?} to {:?}", from, to),
+            Event::Attack { from, to } => {
+                format!("The unit at {:?} attacked the unit at {:?}", from, to)
+            }
+            Event::EndTurn => "Ended the turn".into(),
+            Event::SetTerrain { position, terrain } => format!(
+                "Set the terrain at {:?} to {}",
+                position,
+                terrain.display_string()
+            ),
+            Event::UnsetTerrain { position } => format!("Cleared the terrain at {:?}", position),
+            Event::PlaceCharacter {
+                position, class, ..
+            } => format!("Placed a {} at {:?}", class.display_string(), position),
+            Event::RemoveCharacter { position } => {
+                format!("Removed the character at {:?}", position)
+            }
+            Event::RestoreCharacter { unit } => {
+                format!("Put {} back at {:?}", unit.name, unit.position)
+            }
+            Event::SetFaction { position, faction } => format!(
+                "Put the character at {:?} on the {} side",
+                position,
+                faction.display_string()
+            ),
+            Event::SetBehavior { position, behavior } => format!(
+                "Set the character at {:?} to {}",
+                position,
+                behavior.display_string()
+            ),
+            Event::SetObjective { objective } => {
+                format!("Set the objective: {}", objective.display_string())
+            }
+            Event::Paint { tiles } => format!("Painted {} tiles", tiles.len()),
+        }
+    }
+}
+
+impl TryFrom<String> for Event {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        Event::parse_str(&s).ok_or(format!("unknown event {:?}", s))
+    }
+}
+
+impl From<Event> for String {
+    fn from(event: Event) -> Self {
+        event.log_string()
+    }
+}
+
+/// One event in a game's log, with the turn it happened on.
+pub struct LogEntry {
+    pub turn: u32,
+    pub event: Event,
 }
 
 pub struct Map {
@@ -117,6 +740,11 @@ pub struct Map {
     pub specified_terrain: BTreeMap<(u32, u32), Terrain>,
     pub hint_max_x: u32,
     pub hint_max_y: u32,
+    /// Where the map editor's cursor is; games keep their own.
+    pub cursor: (u32, u32),
+    pub name: String,
+    /// Where brushes other than `Fill` paint from, if set.
+    pub anchor: Option<(u32, u32)>,
 }
 
 impl Map {
@@ -133,43 +761,562 @@ impl Map {
             .map(|x| x.clone())
             .unwrap_or(self.default_terrain.clone())
     }
+
+    pub fn move_cursor(&mut self, direction: Direction) {
+        self.cursor = self.step(self.cursor, direction);
+    }
+
+    /// One tile from `position` in `direction`, or `position` itself at the
+    /// edge of the map.
+    pub fn step(&self, position: (u32, u32), direction: Direction) -> (u32, u32) {
+        let (max_x, max_y) = self.maxes();
+        let (x, y) = position;
+        match direction {
+            Direction::Right if x + 1 < max_x => (x + 1, y),
+            Direction::Up if y + 1 < max_y => (x, y + 1),
+            Direction::Left if x > 0 => (x - 1, y),
+            Direction::Down if y > 0 => (x, y - 1),
+            _ => position,
+        }
+    }
+}
+
+/// A character a map starts its games with, becoming a `Unit` in each.
+#[derive(Clone)]
+pub struct StartingUnit {
+    pub class: Character,
+    pub position: (u32, u32),
+    pub faction: Faction,
+    pub behavior: Behavior,
+}
+
+/// A character placed in a game, which keeps its id and name as it moves
+/// around.
+#[derive(Clone)]
+pub struct Unit {
+    pub id: u32,
+    pub name: String,
+    pub class: Character,
+    pub position: (u32, u32),
+    pub faction: Faction,
+    pub behavior: Behavior,
+    /// Down to 0 (when the unit is removed) from its class's `max_hp`.
+    pub hp: u32,
+    /// Whether the unit has moved during its faction's current turn.
+    pub moved: bool,
+    /// Whether the unit has acted during its faction's current turn.
+    pub acted: bool,
 }
 
 pub struct Game {
     pub id: u32,
     pub map: Map,
-    pub characters: BTreeMap<(u32, u32), Character>,
+    pub units: BTreeMap<u32, Unit>,
     pub current_selection: (u32, u32),
+    /// The unit picked to be moved, if any.
+    pub selected_unit: Option<u32>,
+    /// Counts from 1, going up each time every faction has had its turn.
+    pub turn: u32,
+    /// The faction whose units can be selected and moved.
+    pub active_faction: Faction,
+    /// Where every random outcome in the game comes from.
+    pub rng: Rng,
+    pub objective: Objective,
+    /// Set once the game is won or lost, after which nothing more can be
+    /// played.
+    pub result: Option<GameResult>,
+    pub version: u32,
+}
+
+/// Why a unit could not be selected or moved.
+#[derive(Debug)]
+pub enum MoveError {
+    NoUnitAtCursor,
+    NoUnitSelected,
+    GameOver,
+    NotActiveFaction,
+    AlreadyActed,
+    AlreadyMoved,
+    Occupied,
+    OutOfReach { cost: Option<u32>, movement: u32 },
+}
+
+impl MoveError {
+    pub fn display_string(&self) -> String {
+        match self {
+            MoveError::NoUnitAtCursor => "there is no unit at the cursor".into(),
+            MoveError::NoUnitSelected => "no unit is selected".into(),
+            MoveError::GameOver => "the game is over".into(),
+            MoveError::NotActiveFaction => "it is not that unit's faction's turn".into(),
+            MoveError::AlreadyActed => "that unit has already acted this turn".into(),
+            MoveError::AlreadyMoved => "that unit has already moved this turn".into(),
+            MoveError::Occupied => "another unit is already there".into(),
+            MoveError::OutOfReach {
+                cost: Some(cost),
+                movement,
+            } => format!(
+                "getting there costs {} movement, but the unit only has {}",
+                cost, movement
+            ),
+            MoveError::OutOfReach { cost: None, .. } => "there is no way to get there".into(),
+        }
+    }
+}
+
+/// Why the selected unit could not attack.
+#[derive(Debug)]
+pub enum AttackError {
+    NoUnitSelected,
+    GameOver,
+    NoUnitAtCursor,
+    SameFaction,
+    OutOfRange { distance: u32, range: u32 },
+}
+
+impl AttackError {
+    pub fn display_string(&self) -> String {
+        match self {
+            AttackError::NoUnitSelected => "no unit is selected".into(),
+            AttackError::GameOver => "the game is over".into(),
+            AttackError::NoUnitAtCursor => "there is no unit at the cursor to attack".into(),
+            AttackError::SameFaction => "units cannot attack their own faction".into(),
+            AttackError::OutOfRange { distance, range } => format!(
+                "the target is {} steps away, but the unit can only attack {} away",
+                distance, range
+            ),
+        }
+    }
+}
+
+/// The units as they are after an attack, to be stored.
+pub struct Attack {
+    pub attacker: Unit,
+    pub defender: Unit,
+    pub outcome: combat::Outcome,
+}
+
+impl Attack {
+    pub fn display_string(&self) -> String {
+        if !self.outcome.hit {
+            return format!("{} missed {}", self.attacker.name, self.defender.name);
+        }
+        format!(
+            "{} hit {} for {}{} damage{}",
+            self.attacker.name,
+            self.defender.name,
+            if self.outcome.critical {
+                "critical "
+            } else {
+                ""
+            },
+            self.outcome.damage,
+            if self.defender.hp == 0 {
+                ", defeating them"
+            } else {
+                ""
+            }
+        )
+    }
 }
 
 impl Game {
-    pub fn character_at(&self, cursor: &(u32, u32)) -> Option<Character> {
-        self.characters.get(cursor).map(|v| v.clone())
+    pub fn unit_at(&self, cursor: &(u32, u32)) -> Option<&Unit> {
+        self.units.values().find(|unit| &unit.position == cursor)
     }
 
-    pub fn move_cursor(&mut self, direction: Direction) {
-        let (max_x, max_y) = self.map.maxes();
-        match direction {
-            Direction::Right => {
-                if self.current_selection.0 <= max_x - 2 {
-                    self.current_selection.0 += 1
-                }
+    pub fn selected_unit(&self) -> Option<&Unit> {
+        match self.selected_unit {
+            Some(id) => self.units.get(&id),
+            None => None,
+        }
+    }
+
+    /// Picks the unit under the cursor to move or attack with, as long as it
+    /// belongs to the active faction and has not acted yet this turn.
+    pub fn select_unit(&mut self) -> Result<(), MoveError> {
+        if self.result.is_some() {
+            return Err(MoveError::GameOver);
+        }
+        match self.unit_at(&self.current_selection) {
+            Some(unit) if unit.faction != self.active_faction => Err(MoveError::NotActiveFaction),
+            Some(unit) if unit.acted => Err(MoveError::AlreadyActed),
+            Some(unit) => {
+                self.selected_unit = Some(unit.id);
+                Ok(())
             }
-            Direction::Up => {
-                if self.current_selection.1 <= max_y - 2 {
-                    self.current_selection.1 += 1
-                }
+            None => Err(MoveError::NoUnitAtCursor),
+        }
+    }
+
+    pub fn deselect_unit(&mut self) {
+        self.selected_unit = None;
+    }
+
+    /// Moves the selected unit to the cursor, as long as the cheapest way
+    /// there fits in its class's movement, and then deselects it.
+    pub fn move_selected_unit(&mut self) -> Result<Unit, MoveError> {
+        let unit_id = match self.selected_unit() {
+            Some(unit) => unit.id,
+            None => return Err(MoveError::NoUnitSelected),
+        };
+        let moved = self.move_unit(unit_id, self.current_selection)?;
+        self.selected_unit = None;
+        self.check_result();
+        Ok(moved)
+    }
+
+    /// Moves the unit with `unit_id` to `target`, as long as it has not moved
+    /// yet this turn and the cheapest way there fits in its class's movement.
+    pub fn move_unit(&mut self, unit_id: u32, target: (u32, u32)) -> Result<Unit, MoveError> {
+        if self.result.is_some() {
+            return Err(MoveError::GameOver);
+        }
+        let unit = match self.units.get(&unit_id) {
+            Some(unit) => unit.clone(),
+            None => return Err(MoveError::NoUnitSelected),
+        };
+        if unit.moved {
+            return Err(MoveError::AlreadyMoved);
+        }
+        if target != unit.position && self.unit_at(&target).is_some() {
+            return Err(MoveError::Occupied);
+        }
+
+        let movement = unit.class.movement();
+        let cost = self.movement_cost(&unit, target);
+        match cost {
+            Some(cost) if cost <= movement => (),
+            _ => {
+                return Err(MoveError::OutOfReach {
+                    cost: cost,
+                    movement: movement,
+                })
             }
-            Direction::Left => {
-                if self.current_selection.0 > 0 {
-                    self.current_selection.0 -= 1
-                }
+        };
+
+        let moved = Unit {
+            position: target,
+            moved: true,
+            ..unit
+        };
+        self.units.insert(moved.id, moved.clone());
+        Ok(moved)
+    }
+
+    /// Has the selected unit attack the unit under the cursor, removing the
+    /// defender if it is brought down to 0 hp. The attacker is then done for
+    /// the turn and deselected.
+    pub fn attack_with_selected_unit(&mut self) -> Result<Attack, AttackError> {
+        let attacker_id = match self.selected_unit() {
+            Some(unit) => unit.id,
+            None => return Err(AttackError::NoUnitSelected),
+        };
+        let defender_id = match self.unit_at(&self.current_selection) {
+            Some(unit) => unit.id,
+            None => return Err(AttackError::NoUnitAtCursor),
+        };
+        let attack = self.attack_unit(attacker_id, defender_id)?;
+        self.selected_unit = None;
+        self.check_result();
+        Ok(attack)
+    }
+
+    /// Has one unit attack another of a different faction within its range,
+    /// removing the defender if it is brought down to 0 hp. The attacker is
+    /// then done for the turn.
+    pub fn attack_unit(
+        &mut self,
+        attacker_id: u32,
+        defender_id: u32,
+    ) -> Result<Attack, AttackError> {
+        if self.result.is_some() {
+            return Err(AttackError::GameOver);
+        }
+        let (mut attacker, mut defender) =
+            match (self.units.get(&attacker_id), self.units.get(&defender_id)) {
+                (Some(attacker), Some(defender)) => (attacker.clone(), defender.clone()),
+                (None, _) => return Err(AttackError::NoUnitSelected),
+                (_, None) => return Err(AttackError::NoUnitAtCursor),
+            };
+        if defender.faction == attacker.faction {
+            return Err(AttackError::SameFaction);
+        }
+        let distance = combat::distance(attacker.position, defender.position);
+        let range = attacker.class.range();
+        if distance > range {
+            return Err(AttackError::OutOfRange {
+                distance: distance,
+                range: range,
+            });
+        }
+
+        let terrain = self.map.at(&defender.position);
+        let outcome = combat::attack(&attacker, &mut defender, &terrain, &mut self.rng);
+        attacker.moved = true;
+        attacker.acted = true;
+
+        self.units.insert(attacker.id, attacker.clone());
+        if defender.hp == 0 {
+            self.units.remove(&defender.id);
+            self.check_defeat(&defender);
+        } else {
+            self.units.insert(defender.id, defender.clone());
+        }
+        Ok(Attack {
+            attacker: attacker,
+            defender: defender,
+            outcome: outcome,
+        })
+    }
+
+    /// The least total terrain cost for `unit` to walk to `target`, without
+    /// passing through other units. `None` if there is no way there.
+    pub fn movement_cost(&self, unit: &Unit, target: (u32, u32)) -> Option<u32> {
+        pathfinding::cost_to(&self.map, unit.position, target, &|position| {
+            self.blocks(unit, position)
+        })
+    }
+
+    /// Every tile `unit` could move to this turn, with what it costs to get
+    /// there. Includes the tile it is already on.
+    pub fn reachable_tiles(&self, unit: &Unit) -> BTreeMap<(u32, u32), u32> {
+        pathfinding::reachable(
+            &self.map,
+            unit.position,
+            unit.class.movement(),
+            &|position| self.blocks(unit, position),
+        )
+    }
+
+    /// Ends the game if the player has met the objective. Does nothing to a
+    /// game that is already over, or to one without player units (a game is
+    /// only lost when its last player unit is defeated).
+    pub fn check_result(&mut self) {
+        if self.result.is_some() || !self.has_units(&Faction::Player) {
+            return;
+        }
+        let won = match &self.objective {
+            Objective::Rout => !self.has_units(&Faction::Enemy),
+            Objective::Survive { turns } => self.turn > *turns,
+            Objective::Reach { position } => match self.unit_at(position) {
+                Some(unit) => unit.faction == Faction::Player,
+                None => false,
+            },
+            // Won when the boss is defeated, not whenever it is missing.
+            Objective::DefeatBoss { .. } => false,
+        };
+        if won {
+            self.result = Some(GameResult::Won);
+        }
+    }
+
+    /// Decides the game over again after an edit, which can as easily undo
+    /// what it was won or lost by as meet its objective.
+    pub fn recheck_result(&mut self) {
+        self.result = None;
+        self.check_result();
+    }
+
+    /// Ends the game on `defender` being defeated: lost if it was the
+    /// player's last unit, or won if it was the boss.
+    fn check_defeat(&mut self, defender: &Unit) {
+        if defender.faction == Faction::Player {
+            if !self.has_units(&Faction::Player) {
+                self.result = Some(GameResult::Lost);
+            }
+        } else if self.objective
+            == (Objective::DefeatBoss {
+                unit_id: defender.id,
+            })
+        {
+            self.result = Some(GameResult::Won);
+        }
+    }
+
+    pub fn has_units(&self, faction: &Faction) -> bool {
+        self.units.values().any(|unit| &unit.faction == faction)
+    }
+
+    /// Whether another unit stands in `unit`'s way at `position`.
+    pub fn blocks(&self, unit: &Unit, position: &(u32, u32)) -> bool {
+        match self.unit_at(position) {
+            Some(other) => other.id != unit.id,
+            None => false,
+        }
+    }
+
+    /// Hands play to the next faction that has any units, starting the next
+    /// turn once every faction has gone, and readies that faction's units to
+    /// move and act again.
+    pub fn end_turn(&mut self) {
+        let factions = Faction::all_values();
+        let current = factions
+            .iter()
+            .position(|faction| faction == &self.active_faction)
+            .unwrap_or(0);
+        for step in 1..=factions.len() {
+            if current + step == factions.len() {
+                self.turn += 1;
             }
-            Direction::Down => {
-                if self.current_selection.1 > 0 {
-                    self.current_selection.1 -= 1
-                }
+            let next = (current + step) % factions.len();
+            let faction = &factions[next];
+            if next == current || self.units.values().any(|unit| &unit.faction == faction) {
+                self.active_faction = faction.clone();
+                break;
             }
         }
+
+        for unit in self.units.values_mut() {
+            if unit.faction == self.active_faction {
+                unit.moved = false;
+                unit.acted = false;
+            }
+        }
+        self.selected_unit = None;
+    }
+
+    pub fn move_cursor(&mut self, direction: Direction) {
+        self.current_selection = self.map.step(self.current_selection, direction);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unit(id: u32, position: (u32, u32), class: Character, faction: Faction) -> Unit {
+        Unit {
+            id: id,
+            name: class.display_string(),
+            hp: class.max_hp(),
+            class: class,
+            position: position,
+            faction: faction,
+            behavior: Behavior::Aggressive,
+            moved: false,
+            acted: false,
+        }
+    }
+
+    /// A game on a blank 5x5 grass map with `units`, whose first two draws
+    /// (with seed 1) are a hit that is not critical.
+    fn game(objective: Objective, units: Vec<Unit>) -> Game {
+        Game {
+            id: 1,
+            map: Map {
+                id: 1,
+                default_terrain: Terrain::Grass,
+                specified_terrain: BTreeMap::new(),
+                hint_max_x: 5,
+                hint_max_y: 5,
+                cursor: (0, 0),
+                name: "".into(),
+                anchor: None,
+            },
+            units: units.into_iter().map(|unit| (unit.id, unit)).collect(),
+            current_selection: (0, 0),
+            selected_unit: None,
+            turn: 1,
+            active_faction: Faction::Player,
+            rng: Rng { seed: 1, draws: 0 },
+            objective: objective,
+            result: None,
+            version: 0,
+        }
+    }
+
+    #[test]
+    fn the_cursor_stays_on_a_one_tile_map() {
+        let mut game = game(Objective::Rout, Vec::new());
+        game.map.hint_max_x = 1;
+        game.map.hint_max_y = 1;
+        game.move_cursor(Direction::Right);
+        game.move_cursor(Direction::Up);
+        assert_eq!(game.current_selection, (0, 0));
+        game.move_cursor(Direction::Left);
+        game.move_cursor(Direction::Down);
+        assert_eq!(game.current_selection, (0, 0));
+    }
+
+    #[test]
+    fn the_cursor_stops_at_the_map_edges() {
+        let game = game(Objective::Rout, Vec::new());
+        assert_eq!(game.map.step((3, 4), Direction::Right), (4, 4));
+        assert_eq!(game.map.step((4, 4), Direction::Right), (4, 4));
+        assert_eq!(game.map.step((4, 4), Direction::Up), (4, 4));
+        assert_eq!(game.map.step((0, 0), Direction::Left), (0, 0));
+        assert_eq!(game.map.step((0, 0), Direction::Down), (0, 0));
+    }
+
+    #[test]
+    fn a_game_without_player_units_is_not_decided() {
+        let mut game = game(
+            Objective::Survive { turns: 1 },
+            vec![unit(1, (0, 0), Character::Knight, Faction::Enemy)],
+        );
+        game.check_result();
+        assert!(game.result.is_none());
+
+        game.end_turn();
+        game.end_turn();
+        game.check_result();
+        assert!(game.result.is_none());
+    }
+
+    #[test]
+    fn losing_the_last_player_unit_in_an_attack_loses() {
+        let mut knight = unit(1, (0, 0), Character::Knight, Faction::Player);
+        knight.hp = 1;
+        let mut game = game(
+            Objective::Rout,
+            vec![knight, unit(2, (1, 0), Character::Mage, Faction::Enemy)],
+        );
+        game.attack_unit(2, 1).unwrap();
+        assert!(!game.units.contains_key(&1));
+        assert!(game.result == Some(GameResult::Lost));
+    }
+
+    #[test]
+    fn defeating_the_boss_in_an_attack_wins() {
+        let mut boss = unit(2, (1, 0), Character::Knight, Faction::Enemy);
+        boss.hp = 1;
+        let mut game = game(
+            Objective::DefeatBoss { unit_id: 2 },
+            vec![
+                unit(1, (0, 0), Character::Mage, Faction::Player),
+                boss,
+                unit(3, (4, 4), Character::Knight, Faction::Enemy),
+            ],
+        );
+        game.attack_unit(1, 2).unwrap();
+        assert!(!game.units.contains_key(&2));
+        assert!(game.result == Some(GameResult::Won));
+    }
+
+    #[test]
+    fn a_missing_boss_is_not_a_win() {
+        let mut game = game(
+            Objective::DefeatBoss { unit_id: 2 },
+            vec![unit(1, (0, 0), Character::Mage, Faction::Player)],
+        );
+        game.check_result();
+        assert!(game.result.is_none());
+    }
+
+    #[test]
+    fn defeating_a_player_unit_named_as_the_boss_does_not_win() {
+        let mut boss = unit(2, (1, 0), Character::Knight, Faction::Player);
+        boss.hp = 1;
+        let mut game = game(
+            Objective::DefeatBoss { unit_id: 2 },
+            vec![
+                unit(1, (0, 0), Character::Mage, Faction::Enemy),
+                boss,
+                unit(3, (4, 4), Character::Knight, Faction::Player),
+            ],
+        );
+        game.attack_unit(1, 2).unwrap();
+        assert!(!game.units.contains_key(&2));
+        assert!(game.result.is_none());
     }
 }
@@ -0,0 +1,239 @@
+use std::cmp::{max, min};
+use std::collections::BTreeSet;
+
+use crate::models;
+use crate::pathfinding;
+
+/// The tiles `brush` paints on `map` from `anchor` to `cursor`, each once.
+/// `Fill` starts from `cursor` and pays `anchor` no mind.
+pub fn tiles(
+    map: &models::Map,
+    brush: models::Brush,
+    anchor: (u32, u32),
+    cursor: (u32, u32),
+) -> Vec<(u32, u32)> {
+    match brush {
+        models::Brush::Rectangle => rectangle(anchor, cursor, false),
+        models::Brush::Outline => rectangle(anchor, cursor, true),
+        models::Brush::Line => line(anchor, cursor),
+        models::Brush::Fill => fill(map, cursor),
+    }
+}
+
+/// Every tile of the rectangle with opposite corners `a` and `b`, or with
+/// `outline` only the ones along its edges.
+fn rectangle(a: (u32, u32), b: (u32, u32), outline: bool) -> Vec<(u32, u32)> {
+    let (min_x, max_x) = (min(a.0, b.0), max(a.0, b.0));
+    let (min_y, max_y) = (min(a.1, b.1), max(a.1, b.1));
+    (min_y..=max_y)
+        .flat_map(|y| (min_x..=max_x).map(move |x| (x, y)))
+        .filter(|&(x, y)| !outline || x == min_x || x == max_x || y == min_y || y == max_y)
+        .collect()
+}
+
+/// The tiles a straight line from `a` to `b` passes through, both ends
+/// included, by Bresenham's algorithm. Each tile touches the one before it
+/// at least at a corner.
+fn line(a: (u32, u32), b: (u32, u32)) -> Vec<(u32, u32)> {
+    let (x0, y0) = (a.0 as i64, a.1 as i64);
+    let (x1, y1) = (b.0 as i64, b.1 as i64);
+    let dx = (x1 - x0).abs();
+    let dy = -(y1 - y0).abs();
+    let step_x = if x0 < x1 { 1 } else { -1 };
+    let step_y = if y0 < y1 { 1 } else { -1 };
+
+    let mut tiles = Vec::new();
+    let (mut x, mut y) = (x0, y0);
+    let mut error = dx + dy;
+    loop {
+        tiles.push((x as u32, y as u32));
+        if (x, y) == (x1, y1) {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+    tiles
+}
+
+/// `start` and every tile of the same terrain that can be walked to from it
+/// one step left, right, down or up at a time without leaving that terrain.
+fn fill(map: &models::Map, start: (u32, u32)) -> Vec<(u32, u32)> {
+    let terrain = map.at(&start);
+    let maxes = map.maxes();
+    let mut found = BTreeSet::new();
+    found.insert(start);
+    let mut frontier = vec![start];
+    while let Some(position) = frontier.pop() {
+        for next in pathfinding::neighbours(position, maxes) {
+            if map.at(&next) == terrain && found.insert(next) {
+                frontier.push(next);
+            }
+        }
+    }
+    found.into_iter().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    /// A `width` by `height` map of grass but for `terrain`.
+    fn map(width: u32, height: u32, terrain: &[((u32, u32), models::Terrain)]) -> models::Map {
+        models::Map {
+            id: 1,
+            default_terrain: models::Terrain::Grass,
+            specified_terrain: terrain.iter().cloned().collect::<BTreeMap<_, _>>(),
+            hint_max_x: width,
+            hint_max_y: height,
+            cursor: (0, 0),
+            name: "".to_string(),
+            anchor: None,
+        }
+    }
+
+    /// Checks that the line from `a` to `b` runs from one to the other a tile
+    /// at a time, never straying more than half a tile from the true line.
+    fn check_line(a: (u32, u32), b: (u32, u32)) {
+        let tiles = line(a, b);
+        let (dx, dy) = (b.0 as i64 - a.0 as i64, b.1 as i64 - a.1 as i64);
+        assert_eq!(tiles.first(), Some(&a));
+        assert_eq!(tiles.last(), Some(&b));
+        assert_eq!(tiles.len() as i64, std::cmp::max(dx.abs(), dy.abs()) + 1);
+
+        for pair in tiles.windows(2) {
+            let step_x = (pair[1].0 as i64 - pair[0].0 as i64).abs();
+            let step_y = (pair[1].1 as i64 - pair[0].1 as i64).abs();
+            assert_eq!(std::cmp::max(step_x, step_y), 1, "{:?} to {:?}", a, b);
+        }
+        for &(x, y) in tiles.iter() {
+            // How far off the true line, along the shorter axis, in units
+            // of the longer axis's length.
+            let (x, y) = (x as i64 - a.0 as i64, y as i64 - a.1 as i64);
+            let off = (x * dy - y * dx).abs();
+            assert!(
+                2 * off <= std::cmp::max(dx.abs(), dy.abs()),
+                "{:?} to {:?} strays at {:?}",
+                a,
+                b,
+                (x, y)
+            );
+        }
+    }
+
+    #[test]
+    fn draws_a_line_in_every_octant() {
+        let centre = (5, 5);
+        for &end in [
+            (9, 7),
+            (7, 9),
+            (3, 9),
+            (1, 7),
+            (1, 3),
+            (3, 1),
+            (7, 1),
+            (9, 3),
+        ]
+        .iter()
+        {
+            check_line(centre, end);
+            check_line(end, centre);
+        }
+    }
+
+    #[test]
+    fn draws_straight_and_diagonal_lines_exactly() {
+        assert_eq!(line((0, 2), (3, 2)), vec![(0, 2), (1, 2), (2, 2), (3, 2)]);
+        assert_eq!(line((1, 3), (1, 1)), vec![(1, 3), (1, 2), (1, 1)]);
+        assert_eq!(line((3, 0), (0, 3)), vec![(3, 0), (2, 1), (1, 2), (0, 3)]);
+    }
+
+    #[test]
+    fn a_line_to_where_it_starts_is_one_tile() {
+        assert_eq!(line((2, 2), (2, 2)), vec![(2, 2)]);
+        let map = map(5, 5, &[]);
+        assert_eq!(
+            tiles(&map, models::Brush::Line, (4, 0), (4, 0)),
+            vec![(4, 0)]
+        );
+    }
+
+    #[test]
+    fn outlines_a_rectangle_one_tile_across_once() {
+        assert_eq!(
+            rectangle((2, 3), (2, 0), true),
+            vec![(2, 0), (2, 1), (2, 2), (2, 3)]
+        );
+        assert_eq!(
+            rectangle((0, 1), (2, 1), true),
+            vec![(0, 1), (1, 1), (2, 1)]
+        );
+        assert_eq!(rectangle((1, 1), (1, 1), true), vec![(1, 1)]);
+    }
+
+    #[test]
+    fn outlines_only_the_edges() {
+        let outline = rectangle((0, 0), (2, 2), true);
+        assert_eq!(outline.len(), 8);
+        assert!(!outline.contains(&(1, 1)));
+        assert_eq!(rectangle((0, 0), (2, 2), false).len(), 9);
+    }
+
+    #[test]
+    fn fills_up_to_other_terrain_and_the_map_edge() {
+        // A wall of rock down x = 2, with the dirt at (4, 3) cut off by it.
+        let map = map(
+            5,
+            4,
+            &[
+                ((2, 0), models::Terrain::Rock),
+                ((2, 1), models::Terrain::Rock),
+                ((2, 2), models::Terrain::Rock),
+                ((2, 3), models::Terrain::Rock),
+                ((4, 3), models::Terrain::Dirt),
+            ],
+        );
+
+        let left = fill(&map, (1, 2));
+        assert_eq!(
+            left,
+            vec![
+                (0, 0),
+                (0, 1),
+                (0, 2),
+                (0, 3),
+                (1, 0),
+                (1, 1),
+                (1, 2),
+                (1, 3)
+            ]
+        );
+        let right = fill(&map, (3, 0));
+        assert_eq!(right.len(), 7);
+        assert!(!right.contains(&(4, 3)));
+        assert_eq!(fill(&map, (4, 3)), vec![(4, 3)]);
+        assert_eq!(fill(&map, (2, 3)).len(), 4);
+    }
+
+    #[test]
+    fn fills_no_further_than_a_corner() {
+        let map = map(
+            3,
+            3,
+            &[
+                ((1, 0), models::Terrain::Rock),
+                ((0, 1), models::Terrain::Rock),
+            ],
+        );
+        assert_eq!(fill(&map, (0, 0)), vec![(0, 0)]);
+    }
+}
@@ -0,0 +1,242 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BinaryHeap};
+
+use crate::models;
+
+/// The least total terrain cost to walk from `start` to every tile that can
+/// be reached for at most `budget`, one step left, right, up or down at a
+/// time, never entering a tile for which `blocked` is true.
+///
+/// `start` itself is always included, at cost 0.
+pub fn reachable(
+    map: &models::Map,
+    start: (u32, u32),
+    budget: u32,
+    blocked: &dyn Fn(&(u32, u32)) -> bool,
+) -> BTreeMap<(u32, u32), u32> {
+    search(map, start, Some(budget), None, blocked).costs
+}
+
+/// The least total terrain cost to walk from `start` to `target`, with the
+/// same steps as `reachable` but no budget. `None` if there is no way there.
+pub fn cost_to(
+    map: &models::Map,
+    start: (u32, u32),
+    target: (u32, u32),
+    blocked: &dyn Fn(&(u32, u32)) -> bool,
+) -> Option<u32> {
+    search(map, start, None, Some(target), blocked)
+        .costs
+        .get(&target)
+        .cloned()
+}
+
+/// The cheapest ways from `start` to everywhere it can get to, with the same
+/// steps as `reachable` but no budget.
+pub fn paths_from(
+    map: &models::Map,
+    start: (u32, u32),
+    blocked: &dyn Fn(&(u32, u32)) -> bool,
+) -> Paths {
+    search(map, start, None, None, blocked)
+}
+
+/// The result of a search out from one tile.
+pub struct Paths {
+    /// The least total terrain cost to each tile found.
+    pub costs: BTreeMap<(u32, u32), u32>,
+    /// The tile each tile found is best stepped onto from.
+    came_from: BTreeMap<(u32, u32), (u32, u32)>,
+}
+
+impl Paths {
+    /// The tiles stepped onto on the cheapest way to `target`, ending with
+    /// `target` itself. Empty if `target` is where the search started.
+    pub fn path_to(&self, target: (u32, u32)) -> Option<Vec<(u32, u32)>> {
+        if !self.costs.contains_key(&target) {
+            return None;
+        }
+        let mut path = Vec::new();
+        let mut position = target;
+        while let Some(&previous) = self.came_from.get(&position) {
+            path.push(position);
+            position = previous;
+        }
+        path.reverse();
+        Some(path)
+    }
+}
+
+/// Dijkstra's search out from `start`, stopping early once `target` is
+/// settled and never going past `budget`.
+///
+/// Entering a tile costs that tile's terrain movement cost.
+fn search(
+    map: &models::Map,
+    start: (u32, u32),
+    budget: Option<u32>,
+    target: Option<(u32, u32)>,
+    blocked: &dyn Fn(&(u32, u32)) -> bool,
+) -> Paths {
+    let (max_x, max_y) = map.maxes();
+    let mut best: BTreeMap<(u32, u32), u32> = BTreeMap::new();
+    let mut came_from: BTreeMap<(u32, u32), (u32, u32)> = BTreeMap::new();
+    let mut frontier = BinaryHeap::new();
+    best.insert(start, 0);
+    frontier.push(Reverse((0, start)));
+
+    while let Some(Reverse((cost, position))) = frontier.pop() {
+        if matches!(best.get(&position), Some(&known) if known < cost) {
+            continue;
+        }
+        if Some(position) == target {
+            break;
+        }
+        for next in neighbours(position, (max_x, max_y)) {
+            if blocked(&next) {
+                continue;
+            }
+            let next_cost = cost + map.at(&next).movement_cost();
+            match budget {
+                Some(budget) if next_cost > budget => continue,
+                _ => (),
+            };
+            if !matches!(best.get(&next), Some(&known) if known <= next_cost) {
+                best.insert(next, next_cost);
+                came_from.insert(next, position);
+                frontier.push(Reverse((next_cost, next)));
+            }
+        }
+    }
+    Paths {
+        costs: best,
+        came_from: came_from,
+    }
+}
+
+/// The tiles one step left, right, down or up from `position` that are on a
+/// map of size `maxes`.
+pub fn neighbours(position: (u32, u32), maxes: (u32, u32)) -> Vec<(u32, u32)> {
+    let (x, y) = position;
+    let (max_x, max_y) = maxes;
+    vec![
+        (x.checked_sub(1), Some(y)),
+        (Some(x + 1), Some(y)),
+        (Some(x), y.checked_sub(1)),
+        (Some(x), Some(y + 1)),
+    ]
+    .into_iter()
+    .filter_map(|neighbour| match neighbour {
+        (Some(x), Some(y)) if x < max_x && y < max_y => Some((x, y)),
+        _ => None,
+    })
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use super::*;
+
+    /// A `width` by `height` map of grass but for `terrain`.
+    fn map(width: u32, height: u32, terrain: &[((u32, u32), models::Terrain)]) -> models::Map {
+        models::Map {
+            id: 1,
+            default_terrain: models::Terrain::Grass,
+            specified_terrain: terrain.iter().cloned().collect::<BTreeMap<_, _>>(),
+            hint_max_x: width,
+            hint_max_y: height,
+            cursor: (0, 0),
+            name: "".to_string(),
+            anchor: None,
+        }
+    }
+
+    fn open(_position: &(u32, u32)) -> bool {
+        false
+    }
+
+    #[test]
+    fn never_enters_a_blocked_tile() {
+        let map = map(3, 1, &[]);
+        let blocked = |position: &(u32, u32)| *position == (1, 0);
+
+        assert_eq!(cost_to(&map, (0, 0), (2, 0), &blocked), None);
+        assert_eq!(cost_to(&map, (0, 0), (1, 0), &blocked), None);
+        let reached: Vec<(u32, u32)> = reachable(&map, (0, 0), 10, &blocked)
+            .into_iter()
+            .map(|(position, _)| position)
+            .collect();
+        assert_eq!(reached, vec![(0, 0)]);
+    }
+
+    #[test]
+    fn pays_each_tiles_terrain_cost_to_enter_it() {
+        let map = map(3, 1, &[((1, 0), models::Terrain::Rock)]);
+
+        assert_eq!(cost_to(&map, (0, 0), (1, 0), &open), Some(3));
+        assert_eq!(cost_to(&map, (0, 0), (2, 0), &open), Some(4));
+        // Leaving the rock costs only the grass stepped onto.
+        assert_eq!(cost_to(&map, (1, 0), (2, 0), &open), Some(1));
+
+        let reached = reachable(&map, (0, 0), 3, &open);
+        assert_eq!(reached.get(&(1, 0)), Some(&3));
+        assert_eq!(reached.get(&(2, 0)), None);
+    }
+
+    #[test]
+    fn takes_the_cheaper_way_around_costly_terrain() {
+        // Straight across the dirt costs 3, and the way around through the
+        // rock 6.
+        let map = map(
+            3,
+            2,
+            &[
+                ((1, 0), models::Terrain::Dirt),
+                ((1, 1), models::Terrain::Rock),
+            ],
+        );
+
+        let paths = paths_from(&map, (0, 0), &open);
+        assert_eq!(paths.costs.get(&(2, 0)), Some(&3));
+        assert_eq!(paths.path_to((2, 0)), Some(vec![(1, 0), (2, 0)]));
+        assert_eq!(paths.costs.get(&(2, 1)), Some(&4));
+    }
+
+    #[test]
+    fn stays_within_the_map_edges() {
+        let map = map(2, 2, &[]);
+
+        assert_eq!(neighbours((0, 0), map.maxes()), vec![(1, 0), (0, 1)]);
+        assert_eq!(neighbours((1, 1), map.maxes()), vec![(0, 1), (1, 0)]);
+        assert_eq!(neighbours((0, 0), (1, 1)), vec![]);
+
+        let reached = reachable(&map, (0, 0), 100, &open);
+        assert_eq!(
+            reached.into_iter().collect::<Vec<((u32, u32), u32)>>(),
+            vec![((0, 0), 0), ((0, 1), 1), ((1, 0), 1), ((1, 1), 2)]
+        );
+    }
+
+    #[test]
+    fn goes_around_a_unit_in_the_way() {
+        let map = map(3, 3, &[]);
+        let unit_at = |position: &(u32, u32)| *position == (1, 1);
+
+        let paths = paths_from(&map, (0, 1), &unit_at);
+        assert_eq!(paths.costs.get(&(2, 1)), Some(&4));
+        assert_eq!(paths.costs.get(&(1, 1)), None);
+        let path = paths.path_to((2, 1)).unwrap();
+        assert_eq!(path.len(), 4);
+        assert!(!path.contains(&(1, 1)));
+        let mut previous = (0, 1);
+        for &position in path.iter() {
+            assert!(neighbours(previous, map.maxes()).contains(&position));
+            previous = position;
+        }
+        assert_eq!(previous, (2, 1));
+
+        assert_eq!(paths.path_to((0, 1)), Some(vec![]));
+    }
+}
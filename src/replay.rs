@@ -0,0 +1,167 @@
+use crate::ai;
+use crate::models;
+
+/// Re-applies the events in `log` that happened on or before `through_turn`
+/// to `game`, which should be the game as it started, giving the game as it
+/// stood at the end of that turn.
+///
+/// Turn 0 is the game as it started. Events that no longer make sense (like
+/// selecting where there is no unit) are passed over, just as they would
+/// have been turned away when they were first tried.
+pub fn replay(mut game: models::Game, log: &[models::LogEntry], through_turn: u32) -> models::Game {
+    for entry in log.iter().take_while(|entry| entry.turn <= through_turn) {
+        apply(&mut game, &entry.event);
+    }
+    game
+}
+
+/// The last turn anything happened on, which replays all of `log`.
+pub fn last_turn(log: &[models::LogEntry]) -> u32 {
+    match log.last() {
+        Some(entry) => entry.turn,
+        None => 0,
+    }
+}
+
+/// Does to `game` what was done to the stored game when `event` was logged,
+/// going through the same rules as the routes that log each kind of event.
+fn apply(game: &mut models::Game, event: &models::Event) {
+    apply_event(game, event);
+    // Each edit decides the game over again, as the edit page does.
+    match event {
+        models::Event::Cursor { .. }
+        | models::Event::Select { .. }
+        | models::Event::Deselect
+        | models::Event::Move { .. }
+        | models::Event::Attack { .. }
+        | models::Event::EndTurn => (),
+        _ => game.recheck_result(),
+    };
+}
+
+fn apply_event(game: &mut models::Game, event: &models::Event) {
+    match event {
+        models::Event::Cursor { position } => game.current_selection = *position,
+        models::Event::Select { .. } => {
+            let _ = game.select_unit();
+        }
+        models::Event::Deselect => game.deselect_unit(),
+        models::Event::Move { .. } => {
+            let _ = game.move_selected_unit();
+        }
+        models::Event::Attack { .. } => {
+            let _ = game.attack_with_selected_unit();
+        }
+        models::Event::EndTurn => {
+            game.end_turn();
+            ai::play_computer_turns(game);
+        }
+        models::Event::SetTerrain { position, terrain } => {
+            game.map
+                .specified_terrain
+                .insert(*position, terrain.clone());
+        }
+        models::Event::UnsetTerrain { position } => {
+            game.map.specified_terrain.remove(position);
+        }
+        models::Event::PlaceCharacter {
+            position,
+            unit_id,
+            class,
+        } => place_unit(game, *position, *unit_id, class),
+        models::Event::RemoveCharacter { position } => {
+            game.units.retain(|_, unit| unit.position != *position);
+        }
+        models::Event::RestoreCharacter { unit } => {
+            game.units
+                .retain(|id, other| *id != unit.id && other.position != unit.position);
+            game.units.insert(unit.id, unit.clone());
+        }
+        models::Event::SetFaction { position, faction } => {
+            match game
+                .units
+                .values_mut()
+                .find(|unit| unit.position == *position)
+            {
+                Some(unit) => unit.faction = faction.clone(),
+                None => (),
+            };
+        }
+        models::Event::SetBehavior { position, behavior } => {
+            match game
+                .units
+                .values_mut()
+                .find(|unit| unit.position == *position)
+            {
+                Some(unit) => unit.behavior = behavior.clone(),
+                None => (),
+            };
+        }
+        models::Event::SetObjective { objective } => {
+            game.objective = objective.clone();
+            game.result = None;
+        }
+        models::Event::Paint { tiles } => {
+            for (position, terrain) in tiles.iter() {
+                match terrain {
+                    Some(terrain) => {
+                        game.map
+                            .specified_terrain
+                            .insert(*position, terrain.clone());
+                    }
+                    None => {
+                        game.map.specified_terrain.remove(position);
+                    }
+                };
+            }
+        }
+    }
+}
+
+/// Places a fresh unit with `unit_id` at `position`, or if one is already
+/// there changes its class (renaming it if the class is new), as the edit
+/// page does.
+fn place_unit(
+    game: &mut models::Game,
+    position: (u32, u32),
+    unit_id: u32,
+    class: &models::Character,
+) {
+    let name = class.next_unit_name(
+        &game
+            .units
+            .values()
+            .map(|unit| unit.name.as_str())
+            .collect::<Vec<&str>>(),
+    );
+    match game
+        .units
+        .values_mut()
+        .find(|unit| unit.position == position)
+    {
+        Some(unit) => {
+            if unit.class != *class {
+                unit.name = name;
+            }
+            unit.class = class.clone();
+            unit.hp = class.max_hp();
+            return;
+        }
+        None => (),
+    };
+
+    game.units.insert(
+        unit_id,
+        models::Unit {
+            id: unit_id,
+            name: name,
+            class: class.clone(),
+            position: position,
+            faction: models::Faction::Player,
+            behavior: models::Behavior::Aggressive,
+            hp: class.max_hp(),
+            moved: false,
+            acted: false,
+        },
+    );
+}
@@ -0,0 +1,89 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A counter-based random number generator: the `n`th draw from a seed is
+/// always the same number, so any outcome can be reproduced from the seed and
+/// how many draws came before it.
+///
+/// Each game keeps its own, stored as these two numbers, so its randomness
+/// picks up where it left off between requests.
+#[derive(Clone)]
+pub struct Rng {
+    pub seed: u32,
+    pub draws: u32,
+}
+
+impl Rng {
+    /// A seed for a new game, taken from the clock.
+    pub fn fresh_seed() -> u32 {
+        match SystemTime::now().duration_since(UNIX_EPOCH) {
+            Ok(elapsed) => elapsed.subsec_nanos() ^ elapsed.as_secs() as u32,
+            Err(_) => 0,
+        }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        // splitmix64, run on `seed + draws` steps of its increment.
+        let mut z = (self.seed as u64).wrapping_add(
+            (self.draws as u64)
+                .wrapping_add(1)
+                .wrapping_mul(0x9e37_79b9_7f4a_7c15),
+        );
+        self.draws = self.draws.wrapping_add(1);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A number from `0` up to but not including `below`, which must not be
+    /// 0.
+    pub fn below(&mut self, below: u32) -> u32 {
+        (self.next_u64() % below as u64) as u32
+    }
+
+    /// True `percent` times out of 100.
+    pub fn chance(&mut self, percent: u32) -> bool {
+        self.below(100) < percent
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn draws_splitmix64_from_the_seed() {
+        let mut rng = Rng { seed: 0, draws: 0 };
+        assert_eq!(rng.next_u64(), 0xe220_a839_7b1d_cdaf);
+        assert_eq!(rng.next_u64(), 0x6e78_9e6a_a1b9_65f4);
+        assert_eq!(rng.next_u64(), 0x06c4_5d18_8009_454f);
+        assert_eq!(rng.draws, 3);
+    }
+
+    #[test]
+    fn draws_the_same_numbers_from_the_same_seed() {
+        let mut rng = Rng { seed: 42, draws: 0 };
+        let drawn: Vec<u32> = (0..8).map(|_| rng.below(100)).collect();
+        assert_eq!(drawn, vec![13, 91, 58, 64, 50, 62, 25, 8]);
+        assert_eq!(rng.draws, 8);
+    }
+
+    #[test]
+    fn picks_up_where_the_draws_left_off() {
+        let mut from_start = Rng { seed: 42, draws: 0 };
+        for _ in 0..3 {
+            from_start.next_u64();
+        }
+        let mut resumed = Rng { seed: 42, draws: 3 };
+        assert_eq!(resumed.next_u64(), from_start.next_u64());
+        assert_eq!(resumed.draws, 4);
+    }
+
+    #[test]
+    fn never_or_always_takes_a_0_or_100_percent_chance() {
+        let mut rng = Rng { seed: 7, draws: 0 };
+        for _ in 0..100 {
+            assert!(!rng.chance(0));
+            assert!(rng.chance(100));
+        }
+    }
+}